@@ -0,0 +1,55 @@
+/*
+ * C interface of files-ingest, built with the `ffi` feature:
+ *
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ *   cargo rustc --release --lib --features ffi --crate-type staticlib
+ *
+ * Options are passed as command-line arguments, e.g. {"--cxml", "src"}.
+ */
+
+#ifndef FILES_INGEST_H
+#define FILES_INGEST_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define FILES_INGEST_OK 0
+#define FILES_INGEST_INVALID_ARGUMENT 1
+#define FILES_INGEST_PATH_NOT_FOUND 2
+#define FILES_INGEST_IO_ERROR 3
+#define FILES_INGEST_WALK_ERROR 4
+#define FILES_INGEST_OUTPUT_LIMIT_EXCEEDED 5
+#define FILES_INGEST_CANCELLED 6
+#define FILES_INGEST_INTERNAL_ERROR 7
+#define FILES_INGEST_PLUGIN_ERROR 8
+#define FILES_INGEST_NO_FILES_SELECTED 9
+
+/* Receives each document; `path` and `text` are only valid during the call.
+ * Returning non-zero stops the run with FILES_INGEST_CANCELLED. */
+typedef int (*files_ingest_document_cb)(void *user_data, size_t index, const char *path,
+                                        const char *text, size_t text_len);
+
+/* Runs the ingestion; on success `*output` holds the NUL-terminated output
+ * (`*output_len` bytes, without the NUL), to release with files_ingest_free. */
+int files_ingest_to_buffer(const char *const *args, size_t args_len, char **output,
+                           size_t *output_len);
+
+/* Runs the ingestion, calling `callback` with each document. */
+int files_ingest_with_callback(const char *const *args, size_t args_len,
+                               files_ingest_document_cb callback, void *user_data);
+
+/* Releases a buffer returned by files_ingest_to_buffer. */
+void files_ingest_free(char *output, size_t output_len);
+
+/* The message of the last error on the calling thread, or NULL. Valid until
+ * the next call on the same thread. */
+const char *files_ingest_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FILES_INGEST_H */
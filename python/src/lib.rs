@@ -0,0 +1,171 @@
+//! Python bindings for files-ingest.
+//!
+//! ```python
+//! import files_ingest
+//!
+//! prompt = files_ingest.ingest(["src"], cxml=True, extension=["rs", "toml"])
+//! for chunk in files_ingest.ingest(["logs"], stream=True):
+//!     print(chunk, end="")
+//! ```
+//!
+//! Keyword options are the long command-line options of `files-ingest`, with
+//! underscores in place of dashes: `True` enables a flag, a list repeats the
+//! option and `None` or `False` leaves it out.
+
+use clap::Parser;
+use files_ingest::{AppError, Ingestor, Options};
+use pyo3::exceptions::{PyIOError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyList, PyTuple};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+
+/// Size of the chunks yielded when streaming.
+const CHUNK_SIZE: usize = 64 * 1024;
+/// Number of chunks buffered ahead of the Python consumer.
+const CHUNKS_AHEAD: usize = 4;
+
+/// Concatenates the files under `paths` into a single prompt.
+///
+/// Returns the whole output as a string, or an iterator over chunks of it with `stream=True`.
+#[pyfunction]
+#[pyo3(signature = (paths, *, stream = false, **options))]
+fn ingest(
+    py: Python<'_>,
+    paths: Vec<PathBuf>,
+    stream: bool,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<Py<PyAny>> {
+    let mut options = parse_options(options)?;
+    options.paths = paths;
+    let ingestor = Ingestor::new(options);
+
+    if stream {
+        let (sender, receiver) = mpsc::sync_channel(CHUNKS_AHEAD);
+        thread::spawn(move || {
+            let mut writer = ChunkWriter {
+                buffer: Vec::new(),
+                sender: sender.clone(),
+            };
+            if let Err(e) = ingestor.ingest_to(&mut writer) {
+                let _ = sender.send(Err(e)); // The iterator may have been dropped
+            }
+        });
+        let chunks = Chunks {
+            receiver: Mutex::new(receiver),
+        };
+        return Ok(Py::new(py, chunks)?.into_any());
+    }
+
+    let output = py.detach(|| ingestor.ingest()).map_err(to_py_err)?;
+    Ok(output.into_pyobject(py)?.into_any().unbind())
+}
+
+/// An iterator over the output of a streaming `ingest`, produced on a background thread.
+#[pyclass]
+struct Chunks {
+    receiver: Mutex<Receiver<Result<String, AppError>>>,
+}
+
+#[pymethods]
+impl Chunks {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<String>> {
+        let receiver = &self.receiver;
+        let next = py.detach(|| {
+            receiver
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .recv()
+        });
+        match next {
+            Ok(chunk) => chunk.map(Some).map_err(to_py_err),
+            Err(_) => Ok(None), // The ingestion finished
+        }
+    }
+}
+
+/// A writer sending its output through a channel, in chunks of whole UTF-8 characters.
+struct ChunkWriter {
+    buffer: Vec<u8>,
+    sender: SyncSender<Result<String, AppError>>,
+}
+
+impl ChunkWriter {
+    /// Sends the buffered output, keeping back an incomplete trailing character.
+    fn send_buffer(&mut self) -> io::Result<()> {
+        let valid = match std::str::from_utf8(&self.buffer) {
+            Ok(_) => self.buffer.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        if valid == 0 {
+            return Ok(());
+        }
+        let rest = self.buffer.split_off(valid);
+        let chunk = String::from_utf8(std::mem::replace(&mut self.buffer, rest))
+            .expect("chunk was checked to be valid UTF-8");
+        self.sender
+            .send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "iterator was dropped"))
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+/// Builds [`Options`] from keyword arguments named after the command-line options.
+///
+/// Values are attached to their option (`--flag=value`), so that values starting
+/// with a dash aren't taken for options.
+fn parse_options(kwargs: Option<&Bound<'_, PyDict>>) -> PyResult<Options> {
+    let mut args = vec!["files-ingest".to_string()];
+    for (key, value) in kwargs.iter().flat_map(|kwargs| kwargs.iter()) {
+        let flag = format!("--{}", key.extract::<String>()?.replace('_', "-"));
+        if value.is_none() {
+            continue;
+        }
+        if value.is_instance_of::<PyBool>() {
+            if value.extract::<bool>()? {
+                args.push(flag);
+            }
+        } else if value.is_instance_of::<PyList>() || value.is_instance_of::<PyTuple>() {
+            for item in value.try_iter()? {
+                args.push(format!("{flag}={}", item?.str()?));
+            }
+        } else {
+            args.push(format!("{flag}={}", value.str()?));
+        }
+    }
+    Options::try_parse_from(args).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn to_py_err(err: AppError) -> PyErr {
+    match err {
+        AppError::Io(e) => PyIOError::new_err(e.to_string()),
+        other => PyRuntimeError::new_err(other.to_string()),
+    }
+}
+
+#[pymodule(name = "files_ingest")]
+fn files_ingest_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(ingest, module)?)?;
+    module.add_class::<Chunks>()?;
+    Ok(())
+}
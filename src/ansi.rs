@@ -0,0 +1,21 @@
+//! Removal of ANSI terminal escape sequences (colors, cursor moves, hyperlinks).
+
+use regex::Regex;
+use std::borrow::Cow;
+use std::sync::OnceLock;
+
+static ESCAPE_SEQUENCE: OnceLock<Regex> = OnceLock::new();
+
+/// Removes the escape sequences from `text`.
+pub fn strip(text: &str) -> Cow<'_, str> {
+    if !text.contains('\x1b') {
+        return Cow::Borrowed(text);
+    }
+    let pattern = ESCAPE_SEQUENCE.get_or_init(|| {
+        // CSI sequences (ESC [ ... final byte), OSC sequences (ESC ] ... BEL or ST)
+        // and the other two-character sequences
+        Regex::new(r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)?|\x1b[@-Z\\-_]")
+            .expect("valid regex")
+    });
+    pattern.replace_all(text, "")
+}
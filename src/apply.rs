@@ -0,0 +1,444 @@
+//! The `apply` subcommand: writes the files of a model's response back to the
+//! working tree, closing the edit round-trip.
+//!
+//! The response may hold whole files in any of the bundle formats (plain,
+//! Markdown, Claude XML or `hf-jsonl`, told apart by sniffing), surrounded by
+//! prose, and unified diffs: fenced as `diff` or `patch`, or making up the
+//! whole response. Diffs are applied with `git apply`, which also works outside
+//! of a repository.
+
+use files_ingest::AppError;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::info;
+
+/// The changes found in a response.
+#[derive(Default)]
+struct Changes {
+    /// Whole files, with their new content.
+    files: Vec<(PathBuf, String)>,
+    /// Unified diffs.
+    patches: Vec<String>,
+}
+
+/// Applies the changes of the response in `input` (stdin if `None`), after
+/// showing them and asking for confirmation unless `yes`; with `dry_run`,
+/// only shows them.
+pub fn run(input: Option<&Path>, yes: bool, dry_run: bool) -> Result<(), AppError> {
+    let mut response = String::new();
+    match input {
+        Some(path) => File::open(path)?.read_to_string(&mut response)?,
+        None => io::stdin().read_to_string(&mut response)?,
+    };
+    let mut changes = parse(&response);
+    if changes.files.is_empty() && changes.patches.is_empty() {
+        info!("No files or diffs found in the response");
+        return Ok(());
+    }
+
+    // Preview
+    let mut preview = Vec::new();
+    changes.files.retain(|(path, content)| {
+        let line = match fs::read_to_string(path) {
+            Ok(old) if old == *content => return false,
+            Ok(old) => format!(
+                "update {} ({} -> {} lines)",
+                path.display(),
+                old.lines().count(),
+                content.lines().count()
+            ),
+            Err(_) => format!(
+                "create {} ({} lines)",
+                path.display(),
+                content.lines().count()
+            ),
+        };
+        preview.push(line);
+        true
+    });
+    for line in preview {
+        writeln!(io::stdout(), "{line}")?;
+    }
+    for patch in &changes.patches {
+        git_apply(patch, &["--check"])?; // Fails before anything is written
+        git_apply(patch, &["--stat"])?;
+    }
+    if changes.files.is_empty() && changes.patches.is_empty() {
+        info!("The files in the response are unchanged");
+        return Ok(());
+    }
+    if dry_run || (!yes && !confirm(input.is_none())?) {
+        info!("Nothing applied");
+        return Ok(());
+    }
+
+    for (path, content) in &changes.files {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, content)?;
+    }
+    for patch in &changes.patches {
+        git_apply(patch, &[])?;
+    }
+    Ok(())
+}
+
+/// Asks whether to apply the changes, on the terminal if the response was read
+/// from stdin.
+fn confirm(stdin_is_input: bool) -> Result<bool, AppError> {
+    eprint!("Apply these changes? [y/N] ");
+    let mut answer = String::new();
+    if stdin_is_input {
+        let terminal = if cfg!(windows) { "CONIN$" } else { "/dev/tty" };
+        let terminal = File::open(terminal).map_err(|e| {
+            io::Error::other(format!("confirmation needs a terminal ({e}), pass --yes"))
+        })?;
+        BufReader::new(terminal).read_line(&mut answer)?;
+    } else {
+        io::stdin().lock().read_line(&mut answer)?;
+    }
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+/// Runs `git apply` with `args` on `patch`, tolerating the wrong hunk line
+/// counts models often write.
+fn git_apply(patch: &str, args: &[&str]) -> Result<(), AppError> {
+    let mut child = Command::new("git")
+        .args(["apply", "--recount"])
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::new(e.kind(), format!("running git apply: {e}")))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(patch.as_bytes())?;
+    }
+    if !child.wait()?.success() {
+        return Err(io::Error::other("git apply failed, the diff doesn't apply").into());
+    }
+    Ok(())
+}
+
+/// The formats a response can hold files in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Path lines followed by `---` separated content (the default output format).
+    Plain,
+    /// Path lines followed by code fences, as in the Markdown output format and
+    /// most model answers.
+    Markdown,
+    /// `<document>` elements, as in the Claude XML output format.
+    Cxml,
+    /// JSON Lines, as in the `hf-jsonl` output format.
+    Jsonl,
+}
+
+/// Guesses the format of `response`, so old bundles can be read without
+/// remembering the options that wrote them.
+fn sniff(response: &str, lines: &[&str]) -> Format {
+    let first = lines.iter().find(|line| !line.trim().is_empty());
+    if first.is_some_and(|line| {
+        serde_json::from_str::<serde_json::Value>(line)
+            .is_ok_and(|record| record["text"].is_string())
+    }) {
+        return Format::Jsonl;
+    }
+    if response.contains("<document_content>") {
+        return Format::Cxml;
+    }
+    // Whichever of a `---` separator or a code fence comes first after a path
+    let document = lines.windows(2).find_map(|pair| {
+        let separator = pair[1].trim_end();
+        if separator != "---" && fence_of(separator).is_none() {
+            return None;
+        }
+        path_of(pair[0]).map(|_| separator == "---")
+    });
+    if document == Some(true) {
+        Format::Plain
+    } else {
+        Format::Markdown
+    }
+}
+
+/// Finds the files and diffs of `response`.
+fn parse(response: &str) -> Changes {
+    let lines: Vec<&str> = response.lines().collect();
+    let mut changes = Changes::default();
+    match sniff(response, &lines) {
+        Format::Plain => parse_plain(&lines, &mut changes),
+        Format::Markdown => parse_markdown(&lines, &mut changes),
+        Format::Cxml => {
+            parse_xml(response, &mut changes);
+            // Diffs may come along in the prose around the documents
+            let prose = without_documents(response);
+            parse_diffs(&prose.lines().collect::<Vec<_>>(), &mut changes);
+        }
+        Format::Jsonl => parse_jsonl(&lines, &mut changes),
+    }
+    if changes.files.is_empty() && changes.patches.is_empty() && is_diff(response) {
+        changes.patches.push(response.to_string());
+    }
+    changes
+}
+
+/// Finds the plain-format documents of `lines`, and the diffs fenced between
+/// them (code blocks inside the documents are content).
+fn parse_plain(lines: &[&str], changes: &mut Changes) {
+    let mut i = 0;
+    while i < lines.len() {
+        if let Some(end) = read_plain_document(lines, i, changes) {
+            i = end + 2;
+        } else if let Some(fence) = fence_of(lines[i].trim_end()) {
+            i = read_diff_block(lines, i, fence, changes) + 1;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Finds the diffs fenced in `lines`, leaving the other code blocks alone.
+fn parse_diffs(lines: &[&str], changes: &mut Changes) {
+    let mut i = 0;
+    while i < lines.len() {
+        match fence_of(lines[i].trim_end()) {
+            Some(fence) => i = read_diff_block(lines, i, fence, changes) + 1,
+            None => i += 1,
+        }
+    }
+}
+
+/// Reads the plain-format document whose `---` line would be `lines[i]`, if
+/// there is one: a path, a `---` line, and the content up to a `---` line
+/// followed by a blank one. Returns the index of its closing `---` line.
+fn read_plain_document(lines: &[&str], i: usize, changes: &mut Changes) -> Option<usize> {
+    if lines[i].trim_end() != "---" {
+        return None;
+    }
+    let path = path_of(lines[i.checked_sub(1)?])?;
+    let end = (i + 1..lines.len())
+        .find(|&j| lines[j] == "---" && lines.get(j + 1).is_none_or(|next| next.is_empty()))
+        .unwrap_or(lines.len());
+    changes.files.push((path, body_of(&lines[i + 1..end])));
+    Some(end)
+}
+
+/// Reads the code block opened by `fence` at `lines[i]`, keeping it if it is
+/// fenced as a diff. Returns the index of its closing fence.
+fn read_diff_block(lines: &[&str], i: usize, fence: &str, changes: &mut Changes) -> usize {
+    let (info, end) = code_block(lines, i, fence);
+    if matches!(info, "diff" | "patch") {
+        changes.patches.push(body_of(&lines[i + 1..end]));
+    }
+    end
+}
+
+/// The info string of the code block opened by `fence` at `lines[i]`, and the
+/// index of its closing fence (the same fence).
+fn code_block<'a>(lines: &[&'a str], i: usize, fence: &str) -> (&'a str, usize) {
+    let info = lines[i]
+        .trim()
+        .get(fence.len()..)
+        .unwrap_or_default()
+        .trim();
+    let end = (i + 1..lines.len())
+        .find(|&j| lines[j].trim() == fence)
+        .unwrap_or(lines.len());
+    (info, end)
+}
+
+/// Finds the code blocks of `lines`: files, if a path leads them, and diffs.
+/// Plain-format documents outside of the blocks are taken too.
+fn parse_markdown(lines: &[&str], changes: &mut Changes) {
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i].trim_end();
+        let Some(fence) = fence_of(line) else {
+            i = read_plain_document(lines, i, changes).map_or(i + 1, |end| end + 2);
+            continue;
+        };
+        // The path may be separated from the block by blank lines, or be in
+        // its front matter (--front-matter)
+        let mut above = lines[..i]
+            .iter()
+            .rev()
+            .filter(|line| !line.trim().is_empty());
+        let path = match above.next() {
+            Some(line) if line.trim_end() == "---" => above
+                .take_while(|line| line.trim_end() != "---")
+                .find_map(|line| line.strip_prefix("path: "))
+                .and_then(|path| serde_json::from_str::<String>(path).ok())
+                .and_then(|path| path_of(&path)),
+            line => line.and_then(|line| path_of(line)),
+        };
+        let (info, end) = code_block(lines, i, fence);
+        let body = body_of(&lines[i + 1..end]);
+        if matches!(info, "diff" | "patch") {
+            changes.patches.push(body);
+        } else if let Some(path) = path {
+            changes.files.push((path, body));
+        }
+        i = end + 1;
+    }
+}
+
+/// Finds the records of JSON Lines `lines`: `{"text": ..., "meta": {"path": ...}}`.
+fn parse_jsonl(lines: &[&str], changes: &mut Changes) {
+    for line in lines {
+        let Ok(record) = serde_json::from_str::<serde_json::Value>(line) else {
+            continue;
+        };
+        let path = record["meta"]["path"].as_str().and_then(path_of);
+        if let (Some(path), Some(text)) = (path, record["text"].as_str()) {
+            changes.files.push((path, text.to_string()));
+        }
+    }
+}
+
+/// Finds the Claude XML documents of `response`.
+fn parse_xml(response: &str, changes: &mut Changes) {
+    let mut rest = response;
+    while let Some(start) = rest.find("<source>") {
+        rest = &rest[start + "<source>".len()..];
+        let Some((source, after)) = rest.split_once("</source>") else {
+            return;
+        };
+        let Some((content, after)) = after
+            .split_once("<document_content>")
+            .and_then(|(_, content)| content.split_once("</document_content>"))
+        else {
+            return;
+        };
+        rest = after;
+        let content = content.strip_prefix('\n').unwrap_or(content);
+        let lines: Vec<&str> = content.lines().collect();
+        if let Some(path) = path_of(&unescape_xml(source)) {
+            changes.files.push((path, unescape_xml(&body_of(&lines))));
+        }
+    }
+}
+
+/// `response` without the content of its Claude XML documents.
+fn without_documents(response: &str) -> String {
+    let mut prose = String::new();
+    let mut rest = response;
+    while let Some((before, after)) = rest.split_once("<document_content>") {
+        prose.push_str(before);
+        rest = after
+            .split_once("</document_content>")
+            .map_or("", |(_, after)| after);
+    }
+    prose.push_str(rest);
+    prose
+}
+
+/// The content of a file from the lines of its body in the output (which adds
+/// a newline after the content). Files written back always end with a newline.
+fn body_of(lines: &[&str]) -> String {
+    let mut content = lines.join("\n");
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content
+}
+
+/// The opening fence of a Markdown code block (three backticks or more) starting `line`.
+fn fence_of(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let backticks = line.len() - line.trim_start_matches('`').len();
+    (backticks >= 3).then(|| &line[..backticks])
+}
+
+/// The path named by a header line (`src/main.rs`, `` `src/main.rs` ``,
+/// `### src/main.rs`, `**File: src/main.rs**`...), if it is a safe relative path.
+fn path_of(line: &str) -> Option<PathBuf> {
+    let name = line
+        .trim()
+        .trim_start_matches('#')
+        .trim()
+        .trim_matches(['*', '`'])
+        .trim();
+    let name = name.strip_prefix("File:").unwrap_or(name).trim();
+    let name = name.trim_end_matches(':').trim_matches('`');
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    let path = PathBuf::from(name);
+    // Nothing outside of the working tree
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+    // A bare word is only taken for a file name if there is one (Makefile, README)
+    (name.contains(['/', '.']) || path.exists()).then_some(path)
+}
+
+/// Whether `text` looks like a unified diff.
+fn is_diff(text: &str) -> bool {
+    text.lines().any(|line| line.starts_with("--- "))
+        && text.lines().any(|line| line.starts_with("+++ "))
+        && text.lines().any(|line| line.starts_with("@@ "))
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "```diff\n--- a/b.txt\n+++ b/b.txt\n@@ -1 +1 @@\n-old\n+new\n```\n";
+
+    #[test]
+    fn plain_documents_come_with_fenced_diffs() {
+        let response = format!("a.txt\n---\nhello\n---\n\nAnd for b.txt:\n\n{DIFF}");
+        let changes = parse(&response);
+        assert_eq!(
+            changes.files,
+            [(PathBuf::from("a.txt"), "hello\n".to_string())]
+        );
+        assert_eq!(changes.patches.len(), 1);
+        assert!(changes.patches[0].starts_with("--- a/b.txt\n"));
+    }
+
+    #[test]
+    fn markdown_files_come_with_plain_documents_and_diffs() {
+        let response = format!("c.txt\n```\nnew\n```\n\na.txt\n---\nhello\n---\n\n{DIFF}");
+        let changes = parse(&response);
+        assert_eq!(
+            changes.files,
+            [
+                (PathBuf::from("c.txt"), "new\n".to_string()),
+                (PathBuf::from("a.txt"), "hello\n".to_string())
+            ]
+        );
+        assert_eq!(changes.patches.len(), 1);
+    }
+
+    #[test]
+    fn code_blocks_inside_plain_documents_are_content() {
+        let response = format!("a.md\n---\n{DIFF}---\n");
+        let changes = parse(&response);
+        assert_eq!(changes.files, [(PathBuf::from("a.md"), DIFF.to_string())]);
+        assert!(changes.patches.is_empty());
+    }
+
+    #[test]
+    fn xml_documents_come_with_fenced_diffs() {
+        let response = format!(
+            "<documents>\n<document index=\"1\">\n<source>a.txt</source>\n<document_content>\n{DIFF}</document_content>\n</document>\n</documents>\n\n{DIFF}"
+        );
+        let changes = parse(&response);
+        assert_eq!(changes.files, [(PathBuf::from("a.txt"), DIFF.to_string())]);
+        assert_eq!(changes.patches.len(), 1);
+    }
+}
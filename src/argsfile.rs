@@ -0,0 +1,59 @@
+//! Response files: `@FILE` on the command line stands for the arguments
+//! listed in FILE, so long selections and pattern lists can be versioned and
+//! reused.
+//!
+//! FILE holds one argument per line, taken as is after trimming surrounding
+//! whitespace (no quoting needed); blank lines and lines starting with `#` are
+//! skipped. Response files can name others. An `@` argument that isn't a file
+//! (such as an `@types` directory) is left alone, as is everything after `--`.
+
+use files_ingest::AppError;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// How deep response files may name others, to stop at cycles.
+const MAX_DEPTH: usize = 16;
+
+/// Returns `args` with the response files replaced by their arguments.
+pub fn expand(args: impl IntoIterator<Item = OsString>) -> Result<Vec<OsString>, AppError> {
+    let mut expanded = Vec::new();
+    let mut args = args.into_iter();
+    for arg in args.by_ref() {
+        if arg == "--" {
+            expanded.push(arg);
+            break;
+        }
+        expand_into(arg, &mut expanded, 0)?;
+    }
+    expanded.extend(args);
+    Ok(expanded)
+}
+
+fn expand_into(arg: OsString, expanded: &mut Vec<OsString>, depth: usize) -> Result<(), AppError> {
+    let Some(path) = arg
+        .to_str()
+        .and_then(|arg| arg.strip_prefix('@'))
+        .map(Path::new)
+        .filter(|path| path.is_file())
+    else {
+        expanded.push(arg);
+        return Ok(());
+    };
+    if depth == MAX_DEPTH {
+        return Err(io::Error::other(format!(
+            "response files nested too deep at @{} (do they name each other?)",
+            path.display()
+        ))
+        .into());
+    }
+    let text = fs::read_to_string(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("@{}: {e}", path.display())))?;
+    for line in text.lines().map(str::trim) {
+        if !line.is_empty() && !line.starts_with('#') {
+            expand_into(OsString::from(line), expanded, depth + 1)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! Splitting files into overlapping chunks (`--chunk`, `--chunk-overlap`), each
+//! written as a document of its own, for retrieval indexes.
+//!
+//! Chunks are made of whole lines. Without a parser, the syntactic boundaries
+//! they prefer to end at are found by layout: a chunk ends before a top-level
+//! line (not indented) after a blank line if it can, else after any blank line,
+//! as long as it stays at least half the size.
+//!
+//! Markdown files are split at their headings instead, each chunk carrying the
+//! breadcrumb of the headings it falls under (`Guide > Install > Linux`), so a
+//! retrieved chunk still says what it is about.
+
+use crate::format::language;
+use crate::{Options, tokens};
+use std::path::Path;
+
+/// The chunk size of --format vectors without --chunk, in estimated tokens.
+#[cfg(feature = "embed")]
+const EMBED_SIZE: usize = 512;
+
+/// A piece of a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// Position among the file's chunks, from 0 (the `#chunk-i` identifier).
+    pub index: usize,
+    /// First line in the file, from 1.
+    pub start_line: usize,
+    /// Last line in the file, inclusive.
+    pub end_line: usize,
+    /// The headings the chunk falls under, outermost first (Markdown files).
+    pub headings: Vec<String>,
+    pub text: String,
+}
+
+/// The size of the chunks to split files into, if they are: --chunk, or a size
+/// fit for embedding models with --format vectors.
+pub fn size(options: &Options) -> Option<usize> {
+    #[cfg(feature = "embed")]
+    if options.format == Some(crate::OutputFormat::Vectors) {
+        return Some(options.chunk.unwrap_or(EMBED_SIZE));
+    }
+    options.chunk
+}
+
+/// Splits the `content` of the file at `path` into chunks of about `size`
+/// estimated tokens, each starting with up to `overlap` tokens of the end of
+/// the previous one (within a Markdown section). A line longer than `size`
+/// makes a chunk of its own.
+pub fn split(path: &Path, content: &str, size: usize, overlap: usize) -> Vec<Chunk> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut chunks = Vec::new();
+    if language(path) == Some("markdown") {
+        split_sections(&lines, size, overlap, &mut chunks);
+    } else {
+        split_lines(&lines, 0, &[], size, overlap, &mut chunks);
+    }
+    chunks
+}
+
+/// Splits Markdown `lines` at their headings: a section makes a chunk along
+/// with as many of its subsections as fit, and is split by lines if it doesn't
+/// fit on its own.
+fn split_sections(lines: &[&str], size: usize, overlap: usize, chunks: &mut Vec<Chunk>) {
+    // (first line, level, breadcrumb) of each section; the text before the first
+    // heading is a section of level 0
+    let mut sections = vec![(0, 0, Vec::new())];
+    let mut stack: Vec<(usize, String)> = Vec::new();
+    let mut fence: Option<&str> = None;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            continue;
+        }
+        let Some((level, title)) = heading(line) else {
+            continue;
+        };
+        stack.retain(|(open, _)| *open < level);
+        stack.push((level, title.to_string()));
+        let breadcrumb = stack.iter().map(|(_, title)| title.clone()).collect();
+        if i == 0 {
+            sections.clear();
+        }
+        sections.push((i, level, breadcrumb));
+    }
+
+    let cost: Vec<usize> = lines.iter().map(|line| tokens::estimate(line)).collect();
+    let start_of = |section: usize| sections.get(section).map_or(lines.len(), |s| s.0);
+    let mut section = 0;
+    while section < sections.len() {
+        let (start, level, breadcrumb) = &sections[section];
+        // The subsections that fit along
+        let mut next = section + 1;
+        while next < sections.len()
+            && sections[next].1 > *level
+            && *level > 0
+            && cost[*start..start_of(next + 1)].iter().sum::<usize>() <= size
+        {
+            next += 1;
+        }
+        let end = start_of(next);
+        split_lines(
+            &lines[*start..end],
+            *start,
+            breadcrumb,
+            size,
+            overlap,
+            chunks,
+        );
+        section = next;
+    }
+}
+
+/// The level and title of a Markdown ATX heading (`## Install`).
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let line = line.trim_end();
+    let level = line.len() - line.trim_start_matches('#').len();
+    let title = &line[level..];
+    ((1..=6).contains(&level) && (title.is_empty() || title.starts_with(' ')))
+        .then(|| (level, title.trim().trim_end_matches('#').trim_end()))
+}
+
+/// Splits `lines` (starting at line `offset` of the file, from 0) by size,
+/// preferring blank lines before top-level code.
+fn split_lines(
+    lines: &[&str],
+    offset: usize,
+    headings: &[String],
+    size: usize,
+    overlap: usize,
+    chunks: &mut Vec<Chunk>,
+) {
+    let cost: Vec<usize> = lines.iter().map(|line| tokens::estimate(line)).collect();
+    let blank = |i: usize| lines[i].trim().is_empty();
+
+    let mut start = 0;
+    while start < lines.len() {
+        // As many lines as fit
+        let mut end = start + 1;
+        let mut total = cost[start];
+        while end < lines.len() && total + cost[end] <= size {
+            total += cost[end];
+            end += 1;
+        }
+        // Back off to a boundary, keeping at least half the size
+        if end < lines.len() {
+            let fits = |boundary: usize| cost[start..boundary].iter().sum::<usize>() * 2 >= size;
+            let top_level = (start + 1..=end)
+                .rev()
+                .find(|&i| blank(i - 1) && !blank(i) && !lines[i].starts_with(char::is_whitespace));
+            let after_blank = (start + 1..=end).rev().find(|&i| blank(i - 1) && !blank(i));
+            if let Some(boundary) = top_level
+                .filter(|&i| fits(i))
+                .or(after_blank.filter(|&i| fits(i)))
+            {
+                end = boundary;
+            }
+        }
+
+        let text: String = lines[start..end].concat();
+        if !text.trim().is_empty() {
+            chunks.push(Chunk {
+                index: chunks.len(),
+                start_line: offset + start + 1,
+                end_line: offset + end,
+                headings: headings.to_vec(),
+                text,
+            });
+        }
+        if end == lines.len() {
+            break;
+        }
+        // The next chunk starts with the last lines of this one, as long as it moves on
+        let mut next = end;
+        let mut carried = 0;
+        while next > start + 1 && carried + cost[next - 1] <= overlap {
+            carried += cost[next - 1];
+            next -= 1;
+        }
+        start = next;
+    }
+}
@@ -0,0 +1,149 @@
+//! Line counts per language (the `cloc` subcommand): files, blank, comment and
+//! code lines of the selected documents, and their size and estimated tokens,
+//! to size a selection before sending it.
+//!
+//! Comments are recognized by their markers rather than by parsing: whole-line
+//! comments, and the lines of `/* */` blocks in C-like languages and `<!-- -->`
+//! blocks in markup. Lines mixing code and a comment count as code.
+
+use crate::filter::comment_marker;
+use crate::format::language;
+use crate::units::Units;
+use crate::{AppError, Ingestor, Summary, tokens};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+
+/// The counts of a language.
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    files: usize,
+    blank: usize,
+    comment: usize,
+    code: usize,
+    bytes: u64,
+    tokens: usize,
+}
+
+impl Counts {
+    fn add(&mut self, other: Counts) {
+        self.files += other.files;
+        self.blank += other.blank;
+        self.comment += other.comment;
+        self.code += other.code;
+        self.bytes += other.bytes;
+        self.tokens += other.tokens;
+    }
+}
+
+/// Runs the ingestion, writing a table of the counts per language to `writer`,
+/// the languages with the most code first.
+pub fn write(ingestor: &Ingestor, writer: &mut dyn Write) -> Result<Summary, AppError> {
+    let mut languages: HashMap<String, Counts> = HashMap::new();
+    let summary = ingestor.ingest_with(|document| {
+        languages
+            .entry(language_name(&document.path))
+            .or_default()
+            .add(count(&document.path, &document.content));
+        Ok(())
+    })?;
+    let mut rows: Vec<(String, Counts)> = languages.into_iter().collect();
+    rows.sort_by(|(a_name, a), (b_name, b)| b.code.cmp(&a.code).then_with(|| a_name.cmp(b_name)));
+    let mut total = Counts::default();
+    for (_, counts) in &rows {
+        total.add(*counts);
+    }
+
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .chain(["Language".len()])
+        .max()
+        .unwrap_or_default();
+    let units = Units::new(ingestor.options());
+    let rule = "-".repeat(width + 4 * 10 + 2 * 12);
+    writeln!(
+        writer,
+        "{:<width$}{:>10}{:>10}{:>10}{:>10}{:>12}{:>12}",
+        "Language", "files", "blank", "comment", "code", "size", "tokens"
+    )?;
+    writeln!(writer, "{rule}")?;
+    for (name, counts) in &rows {
+        write_row(writer, name, counts, width, &units)?;
+    }
+    writeln!(writer, "{rule}")?;
+    write_row(writer, "Total", &total, width, &units)?;
+    writer.flush()?;
+    Ok(summary)
+}
+
+fn write_row(
+    writer: &mut dyn Write,
+    name: &str,
+    counts: &Counts,
+    width: usize,
+    units: &Units,
+) -> std::io::Result<()> {
+    let count = |count: usize| units.count(count as u64);
+    writeln!(
+        writer,
+        "{name:<width$}{:>10}{:>10}{:>10}{:>10}{:>12}{:>12}",
+        count(counts.files),
+        count(counts.blank),
+        count(counts.comment),
+        count(counts.code),
+        units.size(counts.bytes),
+        count(counts.tokens)
+    )
+}
+
+/// The language of the file at `path`, or its extension if it isn't a
+/// recognized one.
+fn language_name(path: &Path) -> String {
+    match language(path) {
+        Some(language) => language.to_string(),
+        None => match path.extension() {
+            Some(extension) => extension.to_string_lossy().to_lowercase(),
+            None => "(no extension)".to_string(),
+        },
+    }
+}
+
+/// Counts the lines of the file at `path` holding `content`.
+fn count(path: &Path, content: &str) -> Counts {
+    let marker = comment_marker(path);
+    let block = match language(path) {
+        Some(
+            "c" | "cpp" | "java" | "javascript" | "typescript" | "rust" | "go" | "php" | "swift"
+            | "kotlin" | "css" | "sql",
+        ) => Some(("/*", "*/")),
+        Some("html" | "xml" | "markdown") => Some(("<!--", "-->")),
+        _ => None,
+    };
+    let mut counts = Counts {
+        files: 1,
+        bytes: content.len() as u64,
+        tokens: tokens::estimate(content),
+        ..Counts::default()
+    };
+    let mut in_block = false;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            counts.blank += 1;
+        } else if in_block {
+            counts.comment += 1;
+            in_block = block.is_some_and(|(_, close)| !line.contains(close));
+        } else if let Some((open, close)) = block.filter(|(open, _)| line.starts_with(open)) {
+            counts.comment += 1;
+            in_block = !line[open.len()..].contains(close);
+        } else if marker.is_some_and(|marker| line.starts_with(marker))
+            && !(i == 0 && line.starts_with("#!"))
+        {
+            counts.comment += 1;
+        } else {
+            counts.code += 1;
+        }
+    }
+    counts
+}
@@ -0,0 +1,54 @@
+//! Files that would be shown under the same path: two roots holding the same
+//! relative paths with `--label-roots` (two versions of a project), directories
+//! given the same `--alias`, or names equal once normalized with `--nfc`.
+//!
+//! Rather than writing documents that can't be told apart, the colliding files
+//! are shown under their path with its root (or as walked, without aliases),
+//! and numbered if that still doesn't set them apart.
+
+use crate::Options;
+use crate::format::{Section, display_path, header_path, output_path, section_of};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use tracing::warn;
+
+/// Gives the `files` whose output paths collide distinct ones, recorded in
+/// the options for the run.
+pub fn disambiguate(files: &[PathBuf], options: &mut Cow<Options>) {
+    // Path as shown -> the files shown under it
+    let mut shown: BTreeMap<PathBuf, Vec<&PathBuf>> = BTreeMap::new();
+    for path in files {
+        shown
+            .entry(header_path(path, options).into_owned())
+            .or_default()
+            .push(path);
+    }
+    let mut taken: HashSet<PathBuf> = shown.keys().cloned().collect();
+    for (name, paths) in shown.iter().filter(|(_, paths)| paths.len() > 1) {
+        let mut renamed = Vec::with_capacity(paths.len());
+        for path in paths {
+            let mut new_name = match section_of(path, options) {
+                Some(Section::Root(_)) => output_path(path, options).into_owned(),
+                _ => display_path(path).to_path_buf(),
+            };
+            let mut number = 1;
+            while !taken.insert(new_name.clone()) {
+                number += 1;
+                new_name = PathBuf::from(format!("{} ({number})", name.display()));
+            }
+            renamed.push(((*path).clone(), new_name));
+        }
+        warn!(
+            "{} files would be shown as {}, shown as {} instead",
+            paths.len(),
+            name.display(),
+            renamed
+                .iter()
+                .map(|(_, new_name)| new_name.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        options.to_mut().renamed.extend(renamed);
+    }
+}
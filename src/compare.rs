@@ -0,0 +1,178 @@
+//! The comparison of two trees (`--compare-dirs OLD NEW`), for prompts such as
+//! "explain the differences between these two releases".
+//!
+//! Both trees are walked and filtered like any input path, and their files
+//! paired by path relative to their tree. The output starts with an overview
+//! listing the changed, added and removed files, followed by the old and new
+//! versions of each changed file or, with `--compare-diff`, its unified diff
+//! (from `git diff --no-index`, which works outside of a repository).
+
+use crate::format::{
+    contained_path, display_path, format_body, write_file_footer, write_file_header,
+};
+use crate::{AppError, Ingestor, Options, Summary, scratch};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs the ingestion of the `old` and `new` trees, writing their comparison to `writer`.
+pub fn write(
+    ingestor: &Ingestor,
+    old: &Path,
+    new: &Path,
+    writer: &mut dyn Write,
+) -> Result<Summary, AppError> {
+    // A tree without files has all of its files added (or removed), unless both are empty
+    let (old_files, new_files) = match (files_of(ingestor, old), files_of(ingestor, new)) {
+        (Err(AppError::NoFilesSelected(_)), Err(e @ AppError::NoFilesSelected(_))) => {
+            return Err(e);
+        }
+        (old_files, new_files) => (or_empty(old_files)?, or_empty(new_files)?),
+    };
+    let changed: Vec<&PathBuf> = new_files
+        .iter()
+        .filter(|(path, content)| old_files.get(*path).is_some_and(|old| old != *content))
+        .map(|(path, _)| path)
+        .collect();
+    let added: Vec<&PathBuf> = new_files
+        .keys()
+        .filter(|path| !old_files.contains_key(*path))
+        .collect();
+    let removed: Vec<&PathBuf> = old_files
+        .keys()
+        .filter(|path| !new_files.contains_key(*path))
+        .collect();
+    let unchanged = new_files.len() - changed.len() - added.len();
+
+    let (old_name, new_name) = (display_path(old).display(), display_path(new).display());
+    let mut overview = format!(
+        "Comparing {old_name} (old) with {new_name} (new): {} changed, {} added, {} removed, {unchanged} unchanged\n",
+        changed.len(),
+        added.len(),
+        removed.len()
+    );
+    for (label, paths) in [
+        ("Changed", &changed),
+        ("Added", &added),
+        ("Removed", &removed),
+    ] {
+        if !paths.is_empty() {
+            let _ = writeln!(overview, "\n{label}:");
+            for path in paths {
+                let _ = writeln!(overview, "  {}", path.display());
+            }
+        }
+    }
+
+    let options = ingestor.options();
+    let mut documents = Documents {
+        writer,
+        options,
+        index: 0,
+    };
+    if options.cxml {
+        writeln!(documents.writer, "<documents>")?;
+    }
+    documents.write(Path::new(&format!("{old_name} vs {new_name}")), &overview)?;
+    if options.compare_diff {
+        let dir = scratch::dir("compare")?; // Removed when dropped
+        for path in &changed {
+            let diff = diff(dir.path(), path, &old_files[*path], &new_files[*path])?;
+            let mut name = path.as_os_str().to_owned();
+            name.push(".diff");
+            documents.write(Path::new(&name), &diff)?;
+        }
+    } else {
+        for path in &changed {
+            documents.write(&old.join(path), &old_files[*path])?;
+            documents.write(&new.join(path), &new_files[*path])?;
+        }
+    }
+    if options.cxml {
+        writeln!(documents.writer, "</documents>")?;
+    }
+    documents.writer.flush()?;
+    let mut summary = Summary::default();
+    summary.documents = documents.index;
+    Ok(summary)
+}
+
+/// The files of a tree, or none if the filters selected none.
+fn or_empty(
+    files: Result<BTreeMap<PathBuf, String>, AppError>,
+) -> Result<BTreeMap<PathBuf, String>, AppError> {
+    match files {
+        Err(AppError::NoFilesSelected(_)) => Ok(BTreeMap::new()),
+        files => files,
+    }
+}
+
+/// The files of the tree at `root`, by path relative to it, with their content.
+fn files_of(ingestor: &Ingestor, root: &Path) -> Result<BTreeMap<PathBuf, String>, AppError> {
+    let mut files = BTreeMap::new();
+    ingestor.ingest_paths_with(vec![root.to_path_buf()], |document| {
+        let relative = document
+            .path
+            .strip_prefix(display_path(root))
+            .map_or_else(|_| document.path.clone(), Path::to_path_buf);
+        files.insert(relative, document.content);
+        Ok(())
+    })?;
+    Ok(files)
+}
+
+/// The unified diff between the `old` and `new` content of the file at `path`,
+/// written to `dir` for `git diff`.
+fn diff(dir: &Path, path: &Path, old: &str, new: &str) -> Result<String, AppError> {
+    let path = contained_path(path);
+    for (side, content) in [("old", old), ("new", new)] {
+        let file = dir.join(side).join(&path);
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(file, content)?;
+    }
+    let output = Command::new("git")
+        .args(["diff", "--no-index", "--no-color", "--no-prefix", "--"])
+        .arg(Path::new("old").join(&path))
+        .arg(Path::new("new").join(&path))
+        .current_dir(dir)
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("--compare-diff needs git: {e}")))?;
+    // 1 means the files differ
+    if !matches!(output.status.code(), Some(0 | 1)) {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("git diff failed: {}", error.trim())).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The documents of the comparison, written in the selected format.
+struct Documents<'a> {
+    writer: &'a mut dyn Write,
+    options: &'a Options,
+    /// The index of the last document written.
+    index: usize,
+}
+
+impl Documents<'_> {
+    fn write(&mut self, path: &Path, content: &str) -> Result<(), AppError> {
+        self.index += 1;
+        let (body, backticks) = format_body(path, content, self.options);
+        write_file_header(
+            self.writer,
+            path,
+            self.options,
+            &backticks,
+            self.index,
+            &[],
+            None,
+        )?;
+        self.writer.write_all(body.as_bytes())?;
+        write_file_footer(self.writer, self.options, &backticks)?;
+        Ok(())
+    }
+}
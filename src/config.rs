@@ -0,0 +1,71 @@
+//! The configuration file (`--config`, or `.files-ingest.toml` in the current directory).
+//!
+//! It holds settings that are too long-winded for the command line:
+//!
+//! ```toml
+//! # Tags: files matching any of a tag's patterns (gitignore syntax) carry the tag
+//! [tags]
+//! tests = ["tests/", "*_test.go"]
+//! infra = ["Dockerfile", "*.tf", ".github/"]
+//!
+//! # Pattern sets (gitignore syntax), selected with --use-set and --skip-set
+//! [sets]
+//! frontend = ["*.css", "*.tsx"]
+//! generated = ["*.pb.go", "dist/"]
+//!
+//! # The bundle kept up to date by `files-ingest snapshot`
+//! [snapshot]
+//! file = "CONTEXT.md"
+//! args = ["--markdown", "src", "README.md"]
+//! ```
+
+use crate::AppError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The configuration file looked for in the current directory.
+pub const DEFAULT_CONFIG_FILE: &str = ".files-ingest.toml";
+
+/// The contents of a configuration file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Tag -> path patterns (gitignore syntax) of the files carrying it.
+    #[serde(default)]
+    pub tags: BTreeMap<String, Vec<String>>,
+    /// Set -> path patterns (gitignore syntax) of the files in it.
+    #[serde(default)]
+    pub sets: BTreeMap<String, Vec<String>>,
+    /// The committed bundle checked by `files-ingest snapshot --check`.
+    pub snapshot: Option<Snapshot>,
+}
+
+/// A bundle committed to the repository, and the options generating it.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Snapshot {
+    /// The bundle's file.
+    pub file: PathBuf,
+    /// The command-line arguments generating it (options and paths). The paths
+    /// shouldn't take in the bundle itself, or it never stays up to date.
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl Config {
+    /// Loads the configuration file at `path`, or the empty configuration if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`AppError::Config`] if the file can't be read or is invalid.
+    pub fn load(path: Option<&Path>) -> Result<Self, AppError> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let text = fs::read_to_string(path)
+            .map_err(|e| AppError::Config(format!("{}: {e}", path.display())))?;
+        toml::from_str(&text).map_err(|e| AppError::Config(format!("{}: {e}", path.display())))
+    }
+}
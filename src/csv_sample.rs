@@ -0,0 +1,54 @@
+//! Row sampling for CSV/TSV data files.
+//!
+//! Full data files are rarely useful in a code prompt, but their shape is: the
+//! header, a few leading and trailing rows, and how many rows there are in total.
+
+use std::path::Path;
+
+/// Samples `content` down to its header plus the first and last `rows` records
+/// if `path` is a CSV or TSV file.
+///
+/// Returns `None` when the file is not tabular data or is already small enough.
+pub fn sample_rows(path: &Path, content: &str, rows: usize) -> Option<String> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    if !matches!(ext.as_str(), "csv" | "tsv") {
+        return None;
+    }
+
+    let records = split_records(content);
+    let (header, data) = records.split_first()?;
+    if data.len() <= rows * 2 {
+        return None;
+    }
+
+    let omitted = data.len() - rows * 2;
+    let mut sampled = Vec::with_capacity(rows * 2 + 2);
+    sampled.push(*header);
+    sampled.extend_from_slice(&data[..rows]);
+    let marker = format!("[... {omitted} of {} data rows omitted ...]", data.len());
+    sampled.push(&marker);
+    sampled.extend_from_slice(&data[data.len() - rows..]);
+    Some(sampled.join("\n"))
+}
+
+/// Splits tabular content into records, keeping newlines inside quoted fields.
+fn split_records(content: &str) -> Vec<&str> {
+    let mut records = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+
+    for (i, c) in content.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes, // Escaped quotes ("") toggle twice, so this stays correct
+            '\n' if !in_quotes => {
+                records.push(content[start..i].trim_end_matches('\r'));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < content.len() {
+        records.push(content[start..].trim_end_matches('\r'));
+    }
+    records
+}
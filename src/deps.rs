@@ -0,0 +1,180 @@
+//! The dependency summary of `--deps-summary`, from the project's own tooling.
+//!
+//! Each ecosystem found at the top of the repository gets a compact tree: the
+//! project's packages and their direct dependencies, with versions.
+//!
+//! - Cargo: `cargo metadata`, for every workspace member;
+//! - npm: `npm ls --json`;
+//! - Go: the `require` directives of `go.mod`, read directly.
+
+use crate::git;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tracing::warn;
+
+/// The path the summary document is shown under.
+pub const PATH: &str = "(dependency summary)";
+
+/// Summarizes the dependencies of the repository holding `path` (or of `path`
+/// itself outside of a repository). Tools that fail are skipped with a warning.
+pub fn summarize(path: &Path) -> String {
+    let dir = git::dir_of(path);
+    let top = git::toplevel(dir).map_or_else(|| dir.to_path_buf(), Into::into);
+    let mut summary = String::new();
+    if top.join("Cargo.toml").is_file()
+        && let Some(metadata) = run_json(&top, "cargo", &["metadata", "--format-version", "1"])
+    {
+        let _ = writeln!(summary, "Cargo (cargo metadata):");
+        summary.push_str(&cargo_tree(&metadata));
+    }
+    if top.join("package.json").is_file()
+        && let Some(tree) = run_json(&top, "npm", &["ls", "--json"])
+    {
+        let _ = writeln!(summary, "npm (npm ls):");
+        summary.push_str(&npm_tree(&tree));
+    }
+    if let Ok(go_mod) = fs::read_to_string(top.join("go.mod")) {
+        let _ = writeln!(summary, "Go modules (go.mod):");
+        summary.push_str(&go_tree(&go_mod));
+    }
+    if summary.is_empty() {
+        summary.push_str("No Cargo, npm or Go project found.\n");
+    }
+    summary
+}
+
+/// Runs `program` with `args` in `dir`, parsing its output as JSON.
+///
+/// `npm ls` exits with an error on missing or extraneous packages, but still
+/// lists the others, so the output counts even then.
+fn run_json(dir: &Path, program: &str, args: &[&str]) -> Option<Value> {
+    let output = match Command::new(program).args(args).current_dir(dir).output() {
+        Ok(output) => output,
+        Err(e) => {
+            warn!("Skipping {program} dependencies - could not run {program}: {e}");
+            return None;
+        }
+    };
+    let value = serde_json::from_slice(&output.stdout).ok();
+    if value.is_none() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        warn!(
+            "Skipping {program} dependencies - {program} {} failed: {}",
+            args.join(" "),
+            error.trim()
+        );
+    }
+    value
+}
+
+/// The workspace members of `cargo metadata` output, with their direct dependencies.
+fn cargo_tree(metadata: &Value) -> String {
+    let packages: HashMap<&str, &Value> = metadata["packages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|package| Some((package["id"].as_str()?, package)))
+        .collect();
+    let name_version = |id: &str| {
+        packages.get(id).map_or_else(
+            || id.to_string(),
+            |package| {
+                format!(
+                    "{} {}",
+                    str_of(&package["name"]),
+                    str_of(&package["version"])
+                )
+            },
+        )
+    };
+    let nodes: HashMap<&str, &Value> = metadata["resolve"]["nodes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|node| Some((node["id"].as_str()?, node)))
+        .collect();
+
+    let mut tree = String::new();
+    for member in metadata["workspace_members"]
+        .as_array()
+        .into_iter()
+        .flatten()
+    {
+        let Some(member) = member.as_str() else {
+            continue;
+        };
+        let _ = writeln!(tree, "  {}", name_version(member));
+        let deps = nodes
+            .get(member)
+            .and_then(|node| node["deps"].as_array())
+            .into_iter()
+            .flatten();
+        for dep in deps {
+            // Dependencies that are only for development or building are flagged
+            let kinds: Vec<&str> = dep["dep_kinds"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|kind| kind["kind"].as_str().unwrap_or("normal"))
+                .collect();
+            let flag = if kinds.contains(&"normal") || kinds.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", kinds.join(", "))
+            };
+            let _ = writeln!(tree, "    {}{flag}", name_version(str_of(&dep["pkg"])));
+        }
+    }
+    let _ = writeln!(tree, "  ({} packages in total)", packages.len());
+    tree
+}
+
+/// The top-level packages of `npm ls --json` output.
+fn npm_tree(tree: &Value) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "  {} {}",
+        str_of(&tree["name"]),
+        str_of(&tree["version"])
+    );
+    if let Some(dependencies) = tree["dependencies"].as_object() {
+        for (name, dependency) in dependencies {
+            let version = dependency["version"].as_str().unwrap_or("(missing)");
+            let _ = writeln!(out, "    {name} {version}");
+        }
+    }
+    out
+}
+
+/// The module and `require` directives of a `go.mod` file.
+fn go_tree(go_mod: &str) -> String {
+    let mut out = String::new();
+    let mut in_block = false;
+    for line in go_mod.lines().map(str::trim) {
+        if let Some(module) = line.strip_prefix("module ") {
+            let _ = writeln!(out, "  {}", module.trim());
+        } else if line == "require (" {
+            in_block = true;
+        } else if in_block && line == ")" {
+            in_block = false;
+        } else if let Some(requirement) = line
+            .strip_prefix("require ")
+            .or_else(|| in_block.then_some(line))
+            .filter(|requirement| !requirement.is_empty() && !requirement.starts_with("//"))
+        {
+            let requirement = requirement.replace("// indirect", "(indirect)");
+            let _ = writeln!(out, "    {}", requirement.trim());
+        }
+    }
+    out
+}
+
+/// The string in `value`, or nothing.
+fn str_of(value: &Value) -> &str {
+    value.as_str().unwrap_or_default()
+}
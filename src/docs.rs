@@ -0,0 +1,42 @@
+//! Selection of documentation files (`--docs`), for prompts about writing or
+//! editing docs that don't need the implementation.
+
+use crate::Options;
+use crate::format::relative_to_root;
+use std::path::{Component, Path};
+
+/// Extensions of documentation formats.
+const EXTENSIONS: &[&str] = &[
+    "md", "markdown", "mdx", "rst", "adoc", "asciidoc", "txt", "org",
+];
+
+/// Directories holding documentation, whatever the format of their files.
+const DIRS: &[&str] = &["docs", "doc", "documentation"];
+
+/// Extension-less files that are documentation by name.
+const NAMES: &[&str] = &["README", "CHANGELOG", "CONTRIBUTING", "AUTHORS", "NOTICE"];
+
+/// Whether the file at `path` is documentation: a document by extension or
+/// name, or any file under a `docs` directory (below its input path).
+pub fn is_doc(path: &Path, options: &Options) -> bool {
+    let in_docs_dir = relative_to_root(path, options).parent().is_some_and(|dir| {
+        dir.components().any(|component| match component {
+            Component::Normal(name) => DIRS.iter().any(|dir| name.eq_ignore_ascii_case(dir)),
+            _ => false,
+        })
+    });
+    let by_extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        });
+    let by_name = path.extension().is_none()
+        && path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| NAMES.iter().any(|known| name.eq_ignore_ascii_case(known)));
+    in_docs_dir || by_extension || by_name
+}
@@ -0,0 +1,120 @@
+//! Embeddings of the files (`--format vectors`, `embed` feature), covering the
+//! indexing half of a RAG pipeline: the chunks of the files (`--chunk`) are
+//! embedded through an OpenAI-compatible `/embeddings` API (a local model served
+//! by Ollama by default) and written as JSON Lines, one record per chunk, ready
+//! to load into a vector store.
+//!
+//! Requests go through `curl`, which gets them on its stdin as a config file,
+//! so the API key doesn't show on its command line.
+
+use crate::{AppError, Document, Ingestor, Summary};
+use serde_json::{Value, json};
+use std::env;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+/// Number of chunks embedded per request.
+const BATCH_SIZE: usize = 64;
+
+/// Runs the ingestion, writing a record with the embedding of each document
+/// (a chunk of a file, see [`crate::chunk::size`]) to `writer`.
+pub fn write(
+    ingestor: &Ingestor,
+    model: &str,
+    writer: &mut dyn Write,
+) -> Result<Summary, AppError> {
+    let url = &ingestor.options().embed_url;
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let summary = ingestor.ingest_with(|document| {
+        batch.push(document);
+        if batch.len() == BATCH_SIZE {
+            write_batch(writer, &batch, model, url)?;
+            batch.clear();
+        }
+        Ok(())
+    })?;
+    write_batch(writer, &batch, model, url)?;
+    writer.flush()?;
+    Ok(summary)
+}
+
+/// Embeds `batch` and writes its records.
+fn write_batch(
+    writer: &mut dyn Write,
+    batch: &[Document],
+    model: &str,
+    url: &str,
+) -> io::Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let texts: Vec<&str> = batch
+        .iter()
+        .map(|document| document.content.as_str())
+        .collect();
+    let embeddings = embed(&texts, model, url)?;
+    for (document, embedding) in batch.iter().zip(embeddings) {
+        let mut meta = serde_json::Map::new();
+        meta.insert("language".to_string(), document.language.clone().into());
+        meta.extend(document.metadata.clone());
+        let record = json!({
+            "id": document.path.to_string_lossy(),
+            "text": document.content,
+            "embedding": embedding,
+            "meta": meta,
+        });
+        serde_json::to_writer(&mut *writer, &record)?;
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+/// Requests the embeddings of `texts` from the API at `url`, authenticated with
+/// `EMBEDDING_API_KEY` (or `OPENAI_API_KEY`) if set.
+fn embed(texts: &[&str], model: &str, url: &str) -> io::Result<Vec<Value>> {
+    let body = json!({ "model": model, "input": texts }).to_string();
+    // A curl config file: quoted values escape backslashes and quotes
+    let quote = |value: &str| format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut config = String::from("header = \"Content-Type: application/json\"\n");
+    if let Ok(key) = env::var("EMBEDDING_API_KEY").or_else(|_| env::var("OPENAI_API_KEY")) {
+        let _ = writeln!(
+            config,
+            "header = {}",
+            quote(&format!("Authorization: Bearer {key}"))
+        );
+    }
+    let _ = writeln!(config, "data-binary = {}", quote(&body));
+
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail-with-body", "--location"])
+        .args(["--config", "-", "--"])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::other(format!("could not run curl for --embed: {e}")))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(config.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    let response = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "embedding request to {url} failed: {}",
+            response.trim()
+        )));
+    }
+    let response: Value = serde_json::from_str(&response)
+        .map_err(|e| io::Error::other(format!("embedding response from {url} is not JSON: {e}")))?;
+    let embeddings: Vec<Value> = response["data"]
+        .as_array()
+        .map(|data| data.iter().map(|item| item["embedding"].clone()).collect())
+        .unwrap_or_default();
+    if embeddings.len() != texts.len() || embeddings.iter().any(|embedding| !embedding.is_array()) {
+        return Err(io::Error::other(format!(
+            "embedding response from {url} doesn't hold one embedding per input"
+        )));
+    }
+    Ok(embeddings)
+}
@@ -0,0 +1,140 @@
+//! Encryption of the output with age (`--encrypt`, `encrypt` feature), and the
+//! `decrypt` subcommand reading it back.
+//!
+//! Bundles are ASCII-armored, so they stay text and can also be decrypted with
+//! the `age` tool (`age -d -i KEY`).
+
+pub use imp::{Recipient, Writer, decrypt};
+
+#[cfg(feature = "encrypt")]
+mod imp {
+    use age::armor::{ArmoredReader, ArmoredWriter, Format};
+    use age::stream::StreamWriter;
+    use age::{Decryptor, Encryptor, IdentityFile, x25519};
+    use files_ingest::AppError;
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::path::Path;
+    use std::str::FromStr;
+
+    /// A recipient of the encrypted output, as in `age:age1...`.
+    #[derive(Debug, Clone)]
+    pub struct Recipient(x25519::Recipient);
+
+    impl FromStr for Recipient {
+        type Err = String;
+
+        fn from_str(spec: &str) -> Result<Self, Self::Err> {
+            let Some(key) = spec.strip_prefix("age:") else {
+                return Err(format!("expected age:RECIPIENT, found '{spec}'"));
+            };
+            key.parse()
+                .map(Recipient)
+                .map_err(|e| format!("'{key}': {e}"))
+        }
+    }
+
+    /// Encrypts what is written to it for the recipients, until [`Writer::finish`].
+    pub struct Writer(StreamWriter<ArmoredWriter<Box<dyn Write>>>);
+
+    impl Writer {
+        pub fn new(output: Box<dyn Write>, recipients: &[Recipient]) -> Result<Self, AppError> {
+            let recipients = recipients.iter().map(|r| &r.0 as &dyn age::Recipient);
+            let encryptor = Encryptor::with_recipients(recipients).map_err(io::Error::other)?;
+            let armored = ArmoredWriter::wrap_output(output, Format::AsciiArmor)?;
+            Ok(Writer(encryptor.wrap_output(armored)?))
+        }
+
+        /// Writes the end of the encrypted output, returning the underlying writer.
+        pub fn finish(self) -> io::Result<Box<dyn Write>> {
+            self.0.finish()?.finish()
+        }
+    }
+
+    impl Write for Writer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    /// Decrypts the bundle `input` (stdin if `None`) with the identities of the
+    /// `identity` file, writing it to `output`.
+    pub fn decrypt(
+        identity: &Path,
+        input: Option<&Path>,
+        output: &mut dyn Write,
+    ) -> Result<(), AppError> {
+        let identities = IdentityFile::from_file(identity.to_string_lossy().into_owned())?
+            .into_identities()
+            .map_err(io::Error::other)?;
+        let input: Box<dyn Read> = match input {
+            Some(path) => Box::new(File::open(path)?),
+            None => Box::new(io::stdin()),
+        };
+        let decryptor = Decryptor::new(ArmoredReader::new(input)).map_err(io::Error::other)?;
+        let mut reader = decryptor
+            .decrypt(identities.iter().map(|identity| identity.as_ref() as _))
+            .map_err(io::Error::other)?;
+        io::copy(&mut reader, output)?;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "encrypt"))]
+mod imp {
+    use files_ingest::AppError;
+    use std::io::{self, Write};
+    use std::path::Path;
+    use std::str::FromStr;
+
+    const MISSING_FEATURE: &str = "encryption needs files-ingest built with the `encrypt` feature";
+
+    /// A recipient of the encrypted output (none can be given without the feature).
+    #[derive(Debug, Clone)]
+    pub enum Recipient {}
+
+    impl FromStr for Recipient {
+        type Err = String;
+
+        fn from_str(_spec: &str) -> Result<Self, Self::Err> {
+            Err(MISSING_FEATURE.to_string())
+        }
+    }
+
+    pub enum Writer {}
+
+    impl Writer {
+        pub fn new(_output: Box<dyn Write>, recipients: &[Recipient]) -> Result<Self, AppError> {
+            match recipients.first() {
+                Some(recipient) => match *recipient {},
+                None => Err(io::Error::other(MISSING_FEATURE).into()),
+            }
+        }
+
+        pub fn finish(self) -> io::Result<Box<dyn Write>> {
+            match self {}
+        }
+    }
+
+    impl Write for Writer {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            match *self {}
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            match *self {}
+        }
+    }
+
+    pub fn decrypt(
+        _identity: &Path,
+        _input: Option<&Path>,
+        _output: &mut dyn Write,
+    ) -> Result<(), AppError> {
+        Err(io::Error::other(MISSING_FEATURE).into())
+    }
+}
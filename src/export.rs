@@ -0,0 +1,422 @@
+//! Document-oriented output formats (`--format`), for tools other than LLM prompts.
+//!
+//! Unlike the prompt formats, these are built from each file's [`Document`]
+//! rather than from the formatted text.
+
+use crate::{
+    AppError, Document, Ingestor, Summary, graph, images, obsidian, symbols, template, tokens,
+};
+use clap::ValueEnum;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// An output format selected with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// One CSV row per file, with its path, extension, language, size, line
+    /// count, estimated tokens, license and SHA-256 (no content).
+    CsvManifest,
+    /// JSON Lines in the `{"text": ..., "meta": {...}}` schema of LLM training
+    /// corpora, with the path, language, size and license of each file in `meta`.
+    HfJsonl,
+    /// An Obsidian vault: one Markdown note per file in --output-dir, with
+    /// wiki-links to the files it references.
+    Obsidian,
+    /// The --graph between the files, in Graphviz DOT.
+    Dot,
+    /// The --graph between the files, as JSON nodes and edges.
+    Json,
+    /// The `contents`/`parts` request body of the Gemini API: a text part per
+    /// file, and an `inline_data` part per image with `--images base64`.
+    Gemini,
+    /// A complete Anthropic Messages API request body (--model, --max-output-tokens,
+    /// --system), with a content block per file and per image, then the --prompt.
+    AnthropicRequest,
+    /// JSON Lines with the symbols defined in the files (name, kind, file,
+    /// line, signature), found by universal-ctags.
+    Symbols,
+    /// JSON Lines with the embedding of each chunk of the files, computed with
+    /// the --embed model (`embed` feature).
+    #[cfg(feature = "embed")]
+    Vectors,
+    /// A Parquet file with one row per file, including its content (`parquet` feature).
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// A tar archive of the files (after content filters), at their paths in
+    /// the output (`archive` feature).
+    #[cfg(feature = "archive")]
+    Tar,
+    /// A zip archive of the files (after content filters), at their paths in
+    /// the output (`archive` feature).
+    #[cfg(feature = "archive")]
+    Zip,
+}
+
+/// Runs the ingestion, writing the documents to `writer` in `format`.
+pub(crate) fn write(
+    ingestor: &Ingestor,
+    format: OutputFormat,
+    writer: &mut dyn Write,
+) -> Result<Summary, AppError> {
+    let summary =
+        match format {
+            OutputFormat::CsvManifest => {
+                writeln!(
+                    writer,
+                    "path,extension,language,bytes,lines,tokens,license,sha256"
+                )?;
+                ingestor.ingest_with(|document| write_manifest_row(writer, &document))?
+            }
+            OutputFormat::HfJsonl => {
+                ingestor.ingest_with(|document| write_hf_record(writer, document))?
+            }
+            OutputFormat::Obsidian => {
+                let dir = ingestor.options().output_dir.as_deref().ok_or_else(|| {
+                    io::Error::other("--format obsidian needs --output-dir for the vault")
+                })?;
+                obsidian::write(ingestor, dir)?
+            }
+            OutputFormat::Dot | OutputFormat::Json => {
+                let kind = ingestor.options().graph.ok_or_else(|| {
+                    io::Error::other("--format dot and --format json need --graph")
+                })?;
+                graph::write(ingestor, kind, format, writer)?
+            }
+            OutputFormat::Symbols => symbols::write(ingestor, writer)?,
+            OutputFormat::Gemini => write_gemini(ingestor, writer)?,
+            OutputFormat::AnthropicRequest => write_anthropic_request(ingestor, writer)?,
+            #[cfg(feature = "embed")]
+            OutputFormat::Vectors => {
+                let model =
+                    ingestor.options().embed.as_deref().ok_or_else(|| {
+                        io::Error::other("--format vectors needs an --embed model")
+                    })?;
+                crate::embed::write(ingestor, model, writer)?
+            }
+            #[cfg(feature = "parquet")]
+            OutputFormat::Parquet => parquet::write(ingestor, writer)?,
+            #[cfg(feature = "archive")]
+            OutputFormat::Tar => archive::write_tar(ingestor, writer)?,
+            #[cfg(feature = "archive")]
+            OutputFormat::Zip => archive::write_zip(ingestor, writer)?,
+        };
+    writer.flush()?;
+    Ok(summary)
+}
+
+fn write_manifest_row(writer: &mut dyn Write, document: &Document) -> io::Result<()> {
+    let content = &document.content;
+    let extension = document
+        .path
+        .extension()
+        .map(|ext| ext.to_string_lossy())
+        .unwrap_or_default();
+    let license = document
+        .metadata
+        .get("license")
+        .and_then(|license| license.as_str())
+        .unwrap_or("");
+    let sha256 = Sha256::digest(content.as_bytes())
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        });
+    writeln!(
+        writer,
+        "{},{},{},{},{},{},{},{sha256}",
+        csv_field(&document.path.to_string_lossy()),
+        csv_field(&extension),
+        document.language.as_deref().unwrap_or(""),
+        content.len(),
+        content.lines().count(),
+        tokens::estimate(content),
+        csv_field(license),
+    )
+}
+
+/// Writes a document as a line of JSON in the Hugging Face datasets schema.
+fn write_hf_record(writer: &mut dyn Write, document: Document) -> io::Result<()> {
+    let mut meta = serde_json::Map::new();
+    meta.insert("path".to_string(), document.path.to_string_lossy().into());
+    meta.insert("language".to_string(), document.language.into());
+    meta.extend(document.metadata);
+    meta.entry("license").or_insert(serde_json::Value::Null);
+    let record = serde_json::json!({ "text": document.content, "meta": meta });
+    serde_json::to_writer(&mut *writer, &record)?;
+    writeln!(writer)
+}
+
+/// Writes the documents as the body of a Gemini `generateContent` request: one
+/// user turn, with a text part per file (as in the default format) and the
+/// images embedded as base64 `data:` URIs as `inline_data` parts, after a text
+/// part naming them.
+fn write_gemini(ingestor: &Ingestor, writer: &mut dyn Write) -> Result<Summary, AppError> {
+    let mut parts = Vec::new();
+    let summary = ingestor.ingest_with(|document| {
+        match inline_image(&document) {
+            Some((mime_type, data)) => {
+                parts.push(serde_json::json!({ "text": document.path.to_string_lossy() }));
+                parts.push(serde_json::json!({
+                    "inline_data": { "mime_type": mime_type, "data": data }
+                }));
+            }
+            None => parts.push(serde_json::json!({ "text": document.text })),
+        }
+        Ok(())
+    })?;
+    let request = serde_json::json!({ "contents": [{ "role": "user", "parts": parts }] });
+    serde_json::to_writer(&mut *writer, &request).map_err(io::Error::from)?;
+    writeln!(writer)?;
+    Ok(summary)
+}
+
+/// Writes the documents as the body of an Anthropic Messages API request: the
+/// --model, --max-output-tokens and --system, and one user turn with a text
+/// block per file (as in the default format), an image block per image embedded
+/// as a base64 `data:` URI (after a text block naming it), and the --prompt.
+fn write_anthropic_request(
+    ingestor: &Ingestor,
+    writer: &mut dyn Write,
+) -> Result<Summary, AppError> {
+    let options = ingestor.options();
+    let model = options
+        .model
+        .as_deref()
+        .ok_or_else(|| io::Error::other("--format anthropic-request needs a --model"))?;
+    let mut content = Vec::new();
+    let summary = ingestor.ingest_with(|document| {
+        match inline_image(&document) {
+            Some((media_type, data)) => {
+                let path = document.path.to_string_lossy();
+                content.push(serde_json::json!({ "type": "text", "text": path }));
+                content.push(serde_json::json!({
+                    "type": "image",
+                    "source": { "type": "base64", "media_type": media_type, "data": data }
+                }));
+            }
+            None => content.push(serde_json::json!({ "type": "text", "text": document.text })),
+        }
+        Ok(())
+    })?;
+    let expand = |text: &str| template::expand(text, options, summary.documents);
+    if let Some(prompt) = &options.prompt {
+        content.push(serde_json::json!({ "type": "text", "text": expand(prompt) }));
+    }
+    let mut request = serde_json::json!({
+        "model": model,
+        "max_tokens": options.max_output_tokens,
+    });
+    if let Some(system) = &options.system {
+        request["system"] = expand(system).into();
+    }
+    request["messages"] = serde_json::json!([{ "role": "user", "content": content }]);
+    serde_json::to_writer(&mut *writer, &request).map_err(io::Error::from)?;
+    writeln!(writer)?;
+    Ok(summary)
+}
+
+/// The MIME type and base64 data of an image document embedded as a `data:`
+/// URI (`--images base64`).
+fn inline_image(document: &Document) -> Option<(&str, &str)> {
+    images::image_mime_type(&document.path)?;
+    document
+        .content
+        .strip_prefix("data:")?
+        .split_once(";base64,")
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Archive output: the files go in at their paths in the output, kept within
+/// the archive, with fixed permissions and dates so the same selection always
+/// gives the same archive. Zip archives are built in memory (their central
+/// directory comes last, pointing back at the files).
+#[cfg(feature = "archive")]
+mod archive {
+    use crate::format::contained_path;
+    use crate::{AppError, Ingestor, Summary};
+    use std::io::{self, Cursor, Write};
+    use std::path::Path;
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    /// Permissions of the files in the archive.
+    const MODE: u32 = 0o644;
+
+    pub(super) fn write_tar(
+        ingestor: &Ingestor,
+        writer: &mut dyn Write,
+    ) -> Result<Summary, AppError> {
+        let mut archive = tar::Builder::new(writer);
+        let summary = ingestor.ingest_with(|document| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(document.content.len() as u64);
+            header.set_mode(MODE);
+            header.set_mtime(0);
+            archive.append_data(
+                &mut header,
+                name(&document.path),
+                document.content.as_bytes(),
+            )
+        })?;
+        archive.into_inner()?;
+        Ok(summary)
+    }
+
+    pub(super) fn write_zip(
+        ingestor: &Ingestor,
+        writer: &mut dyn Write,
+    ) -> Result<Summary, AppError> {
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .unix_permissions(MODE);
+        let summary = ingestor.ingest_with(|document| {
+            archive
+                .start_file(name(&document.path), options)
+                .map_err(io::Error::from)?;
+            archive.write_all(document.content.as_bytes())
+        })?;
+        let archive = archive.finish().map_err(io::Error::from)?;
+        writer.write_all(archive.get_ref())?;
+        Ok(summary)
+    }
+
+    /// The name of the file at `path` in the archive: relative, with `/` separators.
+    fn name(path: &Path) -> String {
+        contained_path(path).to_string_lossy().replace('\\', "/")
+    }
+}
+
+/// Parquet output: the file is built in memory (its footer describes the whole
+/// file), one row group per batch of documents.
+#[cfg(feature = "parquet")]
+mod parquet {
+    use crate::{AppError, Document, Ingestor, Summary, tokens};
+    use parquet::basic::Compression;
+    use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::io::{self, Write};
+    use std::sync::Arc;
+
+    const SCHEMA: &str = "message document {
+        REQUIRED BYTE_ARRAY path (UTF8);
+        OPTIONAL BYTE_ARRAY language (UTF8);
+        REQUIRED BYTE_ARRAY content (UTF8);
+        REQUIRED INT64 bytes;
+        REQUIRED INT64 lines;
+        REQUIRED INT64 tokens;
+    }";
+
+    /// Number of documents per row group.
+    const ROW_GROUP_SIZE: usize = 1024;
+
+    pub(super) fn write(ingestor: &Ingestor, writer: &mut dyn Write) -> Result<Summary, AppError> {
+        let schema = Arc::new(parse_message_type(SCHEMA).map_err(to_io)?);
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut file =
+            SerializedFileWriter::new(Vec::new(), schema, Arc::new(properties)).map_err(to_io)?;
+
+        let mut batch = Vec::with_capacity(ROW_GROUP_SIZE);
+        let summary = ingestor.ingest_with(|document| {
+            batch.push(document);
+            if batch.len() == ROW_GROUP_SIZE {
+                write_row_group(&mut file, &batch).map_err(to_io)?;
+                batch.clear();
+            }
+            Ok(())
+        })?;
+        if !batch.is_empty() {
+            write_row_group(&mut file, &batch).map_err(to_io)?;
+        }
+        writer.write_all(&file.into_inner().map_err(to_io)?)?;
+        Ok(summary)
+    }
+
+    fn write_row_group(
+        file: &mut SerializedFileWriter<Vec<u8>>,
+        documents: &[Document],
+    ) -> Result<(), ParquetError> {
+        let text = |text: &str| ByteArray::from(text.as_bytes().to_vec());
+        let count = |n: usize| i64::try_from(n).unwrap_or(i64::MAX);
+
+        let paths: Vec<_> = documents
+            .iter()
+            .map(|document| text(&document.path.to_string_lossy()))
+            .collect();
+        let languages: Vec<_> = documents
+            .iter()
+            .filter_map(|document| document.language.as_deref().map(text))
+            .collect();
+        let language_levels: Vec<_> = documents
+            .iter()
+            .map(|document| i16::from(document.language.is_some()))
+            .collect();
+        let contents: Vec<_> = documents
+            .iter()
+            .map(|document| text(&document.content))
+            .collect();
+        let bytes: Vec<_> = documents
+            .iter()
+            .map(|document| count(document.content.len()))
+            .collect();
+        let lines: Vec<_> = documents
+            .iter()
+            .map(|document| count(document.content.lines().count()))
+            .collect();
+        let tokens: Vec<_> = documents
+            .iter()
+            .map(|document| count(tokens::estimate(&document.content)))
+            .collect();
+
+        let mut row_group = file.next_row_group()?;
+        for column in 0.. {
+            let Some(mut writer) = row_group.next_column()? else {
+                break;
+            };
+            match column {
+                0 => writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&paths, None, None)?,
+                1 => writer.typed::<ByteArrayType>().write_batch(
+                    &languages,
+                    Some(&language_levels),
+                    None,
+                )?,
+                2 => writer
+                    .typed::<ByteArrayType>()
+                    .write_batch(&contents, None, None)?,
+                3 => writer
+                    .typed::<Int64Type>()
+                    .write_batch(&bytes, None, None)?,
+                4 => writer
+                    .typed::<Int64Type>()
+                    .write_batch(&lines, None, None)?,
+                _ => writer
+                    .typed::<Int64Type>()
+                    .write_batch(&tokens, None, None)?,
+            };
+            writer.close()?;
+        }
+        row_group.close()?;
+        Ok(())
+    }
+
+    fn to_io(err: ParquetError) -> io::Error {
+        io::Error::other(err)
+    }
+}
@@ -0,0 +1,226 @@
+//! C-compatible interface (`ffi` feature), declared in `include/files_ingest.h`.
+//!
+//! Options are passed as command-line arguments (`{"--cxml", "src"}`), so the
+//! interface doesn't change as options are added. Functions return one of the
+//! `FILES_INGEST_*` status codes; the message of the last error on the calling
+//! thread is available from [`files_ingest_last_error`].
+
+use crate::{AppError, Document, Ingestor, Options};
+use clap::Parser;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+
+pub const FILES_INGEST_OK: c_int = 0;
+/// Invalid arguments: null pointers, invalid UTF-8, unknown options or an invalid config file.
+pub const FILES_INGEST_INVALID_ARGUMENT: c_int = 1;
+pub const FILES_INGEST_PATH_NOT_FOUND: c_int = 2;
+pub const FILES_INGEST_IO_ERROR: c_int = 3;
+/// An error while walking directories, with `--on-walk-error fail`.
+pub const FILES_INGEST_WALK_ERROR: c_int = 4;
+/// The output would exceed `--max-output-bytes` or `--max-tokens`, in abort mode.
+pub const FILES_INGEST_OUTPUT_LIMIT_EXCEEDED: c_int = 5;
+/// The document callback asked to stop.
+pub const FILES_INGEST_CANCELLED: c_int = 6;
+/// An internal error: a panic was caught at the boundary, or `--validate-xml`
+/// found malformed output.
+pub const FILES_INGEST_INTERNAL_ERROR: c_int = 7;
+/// A plugin could not be found or failed.
+pub const FILES_INGEST_PLUGIN_ERROR: c_int = 8;
+/// The filters selected no files; the error message explains why.
+pub const FILES_INGEST_NO_FILES_SELECTED: c_int = 9;
+
+/// Receives each document: its index, its path and its formatted text, with
+/// the text's length in bytes. Returning non-zero stops the run.
+pub type DocumentCallback = unsafe extern "C" fn(
+    user_data: *mut c_void,
+    index: usize,
+    path: *const c_char,
+    text: *const c_char,
+    text_len: usize,
+) -> c_int;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Runs the ingestion and returns the whole output in a new buffer.
+///
+/// On success, `*output` points to the NUL-terminated output, `*output_len` is
+/// its length (without the NUL), and the buffer must be released with
+/// [`files_ingest_free`].
+///
+/// # Safety
+///
+/// `args` must point to `args_len` valid NUL-terminated strings, and `output`
+/// and `output_len` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn files_ingest_to_buffer(
+    args: *const *const c_char,
+    args_len: usize,
+    output: *mut *mut c_char,
+    output_len: *mut usize,
+) -> c_int {
+    if output.is_null() || output_len.is_null() {
+        return fail(FILES_INGEST_INVALID_ARGUMENT, "output pointers are null");
+    }
+    guard(|| {
+        // SAFETY: guaranteed by the caller.
+        let ingestor = unsafe { ingestor(args, args_len) }?;
+        let text = ingestor.ingest().map_err(|e| status(&e))?;
+        let mut bytes = text.into_bytes();
+        let len = bytes.len();
+        bytes.push(0);
+        // SAFETY: both pointers were checked for null and are valid for writes.
+        unsafe {
+            *output = Box::into_raw(bytes.into_boxed_slice()).cast();
+            *output_len = len;
+        }
+        Ok(())
+    })
+}
+
+/// Runs the ingestion, calling `callback` with each document instead of
+/// building the whole output.
+///
+/// The pointers passed to `callback` are only valid during the call.
+///
+/// # Safety
+///
+/// `args` must point to `args_len` valid NUL-terminated strings, and `callback`
+/// must be safe to call with `user_data`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn files_ingest_with_callback(
+    args: *const *const c_char,
+    args_len: usize,
+    callback: DocumentCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    guard(|| {
+        // SAFETY: guaranteed by the caller.
+        let ingestor = unsafe { ingestor(args, args_len) }?;
+        let mut cancelled = false;
+        let result = ingestor.ingest_with(|document: Document| {
+            let path =
+                CString::new(document.path.to_string_lossy().into_owned()).unwrap_or_default();
+            let mut text = document.text.into_bytes();
+            let text_len = text.len();
+            text.push(0);
+            // SAFETY: guaranteed by the caller; the strings outlive the call.
+            let stop = unsafe {
+                callback(
+                    user_data,
+                    document.index,
+                    path.as_ptr(),
+                    text.as_ptr().cast(),
+                    text_len,
+                )
+            };
+            if stop != 0 {
+                cancelled = true;
+                return Err(io::Error::other("cancelled by the document callback"));
+            }
+            Ok(())
+        });
+        match result {
+            Err(_) if cancelled => Err(fail(
+                FILES_INGEST_CANCELLED,
+                "cancelled by the document callback",
+            )),
+            result => result.map(drop).map_err(|e| status(&e)),
+        }
+    })
+}
+
+/// Releases a buffer returned by [`files_ingest_to_buffer`].
+///
+/// # Safety
+///
+/// `output` and `output_len` must be exactly as returned by
+/// [`files_ingest_to_buffer`] (or `output` null), and the buffer must not be
+/// used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn files_ingest_free(output: *mut c_char, output_len: usize) {
+    if output.is_null() {
+        return;
+    }
+    // SAFETY: the buffer was allocated as a boxed slice of `output_len + 1` bytes.
+    drop(unsafe {
+        Box::from_raw(ptr::slice_from_raw_parts_mut(
+            output.cast::<u8>(),
+            output_len + 1,
+        ))
+    });
+}
+
+/// Returns the message of the last error on the calling thread, or null.
+///
+/// The string stays valid until the next call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn files_ingest_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|error| error.as_ref().map_or(ptr::null(), |error| error.as_ptr()))
+}
+
+/// Builds an ingestor from C command-line arguments (without the program name).
+///
+/// # Safety
+///
+/// See [`files_ingest_to_buffer`].
+unsafe fn ingestor(args: *const *const c_char, args_len: usize) -> Result<Ingestor, c_int> {
+    if args.is_null() && args_len > 0 {
+        return Err(fail(FILES_INGEST_INVALID_ARGUMENT, "arguments are null"));
+    }
+    let mut arguments = vec!["files-ingest".to_string()];
+    for i in 0..args_len {
+        // SAFETY: `args` points to `args_len` pointers.
+        let arg = unsafe { *args.add(i) };
+        if arg.is_null() {
+            return Err(fail(FILES_INGEST_INVALID_ARGUMENT, "an argument is null"));
+        }
+        // SAFETY: the argument is a valid NUL-terminated string.
+        let arg = unsafe { CStr::from_ptr(arg) }.to_str().map_err(|_| {
+            fail(
+                FILES_INGEST_INVALID_ARGUMENT,
+                "an argument is not valid UTF-8",
+            )
+        })?;
+        arguments.push(arg.to_string());
+    }
+    let options = Options::try_parse_from(&arguments)
+        .map_err(|e| fail(FILES_INGEST_INVALID_ARGUMENT, &e.to_string()))?;
+    Ok(Ingestor::new(options).with_command_line(arguments))
+}
+
+/// Runs `f`, clearing the last error first and turning panics into an error code.
+fn guard(f: impl FnOnce() -> Result<(), c_int>) -> c_int {
+    LAST_ERROR.set(None);
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => FILES_INGEST_OK,
+        Ok(Err(code)) => code,
+        Err(_) => fail(FILES_INGEST_INTERNAL_ERROR, "internal error (panic)"),
+    }
+}
+
+/// Records the error message for `err` and returns its status code.
+fn status(err: &AppError) -> c_int {
+    let code = match err {
+        AppError::Io(_) | AppError::Download(_) => FILES_INGEST_IO_ERROR,
+        AppError::Ignore(_) => FILES_INGEST_WALK_ERROR,
+        AppError::PathNotFound(_) => FILES_INGEST_PATH_NOT_FOUND,
+        AppError::OutputLimitExceeded(_) => FILES_INGEST_OUTPUT_LIMIT_EXCEEDED,
+        AppError::Plugin(_) => FILES_INGEST_PLUGIN_ERROR,
+        AppError::Config(_) | AppError::Workspace(_) => FILES_INGEST_INVALID_ARGUMENT,
+        AppError::InvalidXml(_) => FILES_INGEST_INTERNAL_ERROR,
+        AppError::NoFilesSelected(_) => FILES_INGEST_NO_FILES_SELECTED,
+    };
+    fail(code, &err.to_string())
+}
+
+/// Records `message` as the last error and returns `code`.
+fn fail(code: c_int, message: &str) -> c_int {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.set(Some(message));
+    code
+}
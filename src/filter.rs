@@ -0,0 +1,248 @@
+//! Content filters: the chain of transforms applied to each document before formatting.
+//!
+//! The built-in transforms (`--normalize`, `--nfc-content`, `--strip-ansi`,
+//! `--structured-max-depth`, `--csv-sample`, `--logs-mode`, `--sample-large`)
+//! and the ones selected with `--filter` are filters like any other, and library
+//! users can add their own with [`Ingestor::with_filter`](crate::Ingestor::with_filter).
+
+use crate::format::language;
+use crate::plugin::{self, PluginFilter};
+use crate::sample::{self, SampleSpec};
+use crate::{Document, ansi, csv_sample, logs, structured};
+use regex::Regex;
+use std::borrow::Cow;
+use std::path::Path;
+use std::str::FromStr;
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+
+/// What to do with a document once a filter has seen it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterDecision {
+    /// Pass it on to the next filter, and then to the output.
+    Keep,
+    /// Leave it out of the output.
+    Skip,
+}
+
+/// A step of the filter chain.
+///
+/// Filters see documents before formatting: `content` is the file's content
+/// (as changed by the previous filters), `index` is not assigned yet and
+/// `text` is empty.
+pub trait ContentFilter: Send + Sync {
+    fn apply(&self, doc: &mut Document) -> FilterDecision;
+}
+
+/// A built-in filter, as selected with `--filter`.
+#[derive(Debug, Clone)]
+pub enum FilterSpec {
+    /// `structured-max-depth=N`: see `--structured-max-depth`.
+    StructuredMaxDepth(usize),
+    /// `csv-sample=N`: see `--csv-sample`.
+    CsvSample(usize),
+    /// `logs` or `logs=N`: see `--logs-mode` (and `--log-lines`).
+    Logs(usize),
+    /// `sample-large=head=N,tail=M`: see `--sample-large`.
+    SampleLarge(SampleSpec),
+    /// `redact=REGEX`: replace every match of the regex with `[REDACTED]`.
+    Redact(Regex),
+    /// `strip-comments`: remove whole-line comments, for languages recognized by extension.
+    StripComments,
+    /// `strip-ansi`: see `--strip-ansi`.
+    StripAnsi,
+    /// `normalize`: see `--normalize`.
+    Normalize,
+    /// `nfc`: see `--nfc-content`.
+    Nfc,
+    /// `plugin=NAME`: run each document through the `files-ingest-NAME` executable.
+    Plugin(PluginFilter),
+}
+
+impl FilterSpec {
+    /// Creates the filter.
+    #[must_use]
+    pub fn build(&self) -> Box<dyn ContentFilter> {
+        match self {
+            FilterSpec::StructuredMaxDepth(max_depth) => Box::new(StructuredMaxDepth(*max_depth)),
+            FilterSpec::CsvSample(rows) => Box::new(CsvSample(*rows)),
+            FilterSpec::Logs(max_lines) => Box::new(Logs(*max_lines)),
+            FilterSpec::SampleLarge(spec) => Box::new(SampleLarge(*spec)),
+            FilterSpec::Redact(pattern) => Box::new(Redact(pattern.clone())),
+            FilterSpec::StripComments => Box::new(StripComments),
+            FilterSpec::StripAnsi => Box::new(StripAnsi),
+            FilterSpec::Normalize => Box::new(Normalize),
+            FilterSpec::Nfc => Box::new(Nfc),
+            FilterSpec::Plugin(plugin) => Box::new(plugin.clone()),
+        }
+    }
+}
+
+impl FromStr for FilterSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (name, argument) = match spec.split_once('=') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (spec, None),
+        };
+        let count = |argument: Option<&str>| {
+            argument
+                .and_then(|argument| argument.parse().ok())
+                .ok_or_else(|| format!("filter '{name}' needs a number, as in '{name}=N'"))
+        };
+        match name {
+            "structured-max-depth" => count(argument).map(FilterSpec::StructuredMaxDepth),
+            "csv-sample" => count(argument).map(FilterSpec::CsvSample),
+            "logs" if argument.is_none() => Ok(FilterSpec::Logs(logs::DEFAULT_LINES)),
+            "logs" => count(argument).map(FilterSpec::Logs),
+            "sample-large" => argument
+                .ok_or_else(|| {
+                    "filter 'sample-large' needs lines to keep, as in 'sample-large=head=N,tail=M'"
+                        .to_string()
+                })?
+                .parse()
+                .map(FilterSpec::SampleLarge),
+            "redact" => {
+                let pattern =
+                    argument.ok_or("filter 'redact' needs a regex, as in 'redact=REGEX'")?;
+                Regex::new(pattern)
+                    .map(FilterSpec::Redact)
+                    .map_err(|e| e.to_string())
+            }
+            "strip-comments" => Ok(FilterSpec::StripComments),
+            "strip-ansi" => Ok(FilterSpec::StripAnsi),
+            "normalize" => Ok(FilterSpec::Normalize),
+            "nfc" => Ok(FilterSpec::Nfc),
+            "plugin" => {
+                let plugin = argument.ok_or("filter 'plugin' needs a name, as in 'plugin=NAME'")?;
+                PluginFilter::new(plugin)
+                    .map(FilterSpec::Plugin)
+                    .ok_or_else(|| plugin::not_found(plugin).to_string())
+            }
+            _ => Err(format!(
+                "unknown filter '{name}' (expected structured-max-depth, csv-sample, logs, sample-large, redact, strip-comments, strip-ansi, normalize, nfc or plugin)"
+            )),
+        }
+    }
+}
+
+struct StructuredMaxDepth(usize);
+
+impl ContentFilter for StructuredMaxDepth {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        if let Some(truncated) = structured::truncate_depth(&doc.path, &doc.content, self.0) {
+            doc.content = truncated;
+        }
+        FilterDecision::Keep
+    }
+}
+
+struct CsvSample(usize);
+
+impl ContentFilter for CsvSample {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        if let Some(sampled) = csv_sample::sample_rows(&doc.path, &doc.content, self.0) {
+            doc.content = sampled;
+        }
+        FilterDecision::Keep
+    }
+}
+
+struct Logs(usize);
+
+impl ContentFilter for Logs {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        if logs::is_log(&doc.path) {
+            doc.content = logs::compact(&doc.content, self.0);
+        }
+        FilterDecision::Keep
+    }
+}
+
+struct SampleLarge(SampleSpec);
+
+impl ContentFilter for SampleLarge {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        if let Some(sampled) = sample::sample_lines(&doc.content, self.0) {
+            doc.content = sampled;
+        }
+        FilterDecision::Keep
+    }
+}
+
+struct Redact(Regex);
+
+impl ContentFilter for Redact {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        if self.0.is_match(&doc.content) {
+            doc.content = self.0.replace_all(&doc.content, "[REDACTED]").into_owned();
+        }
+        FilterDecision::Keep
+    }
+}
+
+/// The marker starting the line comments of the language of the file at `path`.
+pub fn comment_marker(path: &Path) -> Option<&'static str> {
+    match language(path) {
+        Some(
+            "c" | "cpp" | "java" | "javascript" | "typescript" | "rust" | "go" | "php" | "swift"
+            | "kotlin",
+        ) => Some("//"),
+        Some("python" | "ruby" | "bash" | "yaml" | "toml") => Some("#"),
+        Some("sql") => Some("--"),
+        _ => None,
+    }
+}
+
+struct StripComments;
+
+impl ContentFilter for StripComments {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        let Some(marker) = comment_marker(&doc.path) else {
+            return FilterDecision::Keep;
+        };
+        let mut stripped = String::with_capacity(doc.content.len());
+        for (i, line) in doc.content.split_inclusive('\n').enumerate() {
+            let is_shebang = i == 0 && line.starts_with("#!");
+            if is_shebang || !line.trim_start().starts_with(marker) {
+                stripped.push_str(line);
+            }
+        }
+        doc.content = stripped;
+        FilterDecision::Keep
+    }
+}
+
+struct StripAnsi;
+
+impl ContentFilter for StripAnsi {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        if let Cow::Owned(stripped) = ansi::strip(&doc.content) {
+            doc.content = stripped;
+        }
+        FilterDecision::Keep
+    }
+}
+
+struct Normalize;
+
+impl ContentFilter for Normalize {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        let content = doc.content.strip_prefix('\u{feff}').unwrap_or(&doc.content);
+        if content.len() != doc.content.len() || content.contains("\r\n") {
+            doc.content = content.replace("\r\n", "\n");
+        }
+        FilterDecision::Keep
+    }
+}
+
+struct Nfc;
+
+impl ContentFilter for Nfc {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        if !is_nfc(&doc.content) {
+            doc.content = doc.content.nfc().collect();
+        }
+        FilterDecision::Keep
+    }
+}
@@ -0,0 +1,585 @@
+//! Output formats: how each file is framed (headers, fences, tags) and its content rendered.
+
+use crate::Options;
+use crate::{highlight, manifests, tabs, wrap};
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+
+// --- Configuration & Constants ---
+
+// Static map for file extensions to Markdown language tags
+static EXT_TO_LANG: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
+fn initialize_language_map() -> HashMap<&'static str, &'static str> {
+    let mut m = HashMap::new();
+    m.insert("py", "python");
+    m.insert("rs", "rust");
+    m.insert("c", "c");
+    m.insert("h", "c");
+    m.insert("cpp", "cpp");
+    m.insert("hpp", "cpp");
+    m.insert("java", "java");
+    m.insert("js", "javascript");
+    m.insert("ts", "typescript");
+    m.insert("html", "html");
+    m.insert("css", "css");
+    m.insert("xml", "xml");
+    m.insert("json", "json");
+    m.insert("yaml", "yaml");
+    m.insert("yml", "yaml");
+    m.insert("sh", "bash");
+    m.insert("rb", "ruby");
+    m.insert("md", "markdown");
+    m.insert("toml", "toml");
+    m.insert("go", "go");
+    m.insert("php", "php");
+    m.insert("swift", "swift");
+    m.insert("kt", "kotlin");
+    m.insert("sql", "sql");
+    m.insert("diff", "diff");
+    m.insert("patch", "diff");
+    m
+}
+
+/// Returns the Markdown language tag for `path`, guessed from its extension.
+pub fn language(path: &Path) -> Option<&'static str> {
+    path.extension()
+        .and_then(|ext| ext.to_str()) // Get extension as &str
+        .and_then(|ext_str| {
+            // Access the OnceLock, initializing it if this is the first time
+            EXT_TO_LANG
+                .get_or_init(|| {
+                    // This closure runs only once to initialize the map
+                    // eprintln!("Initializing language map..."); // Optional debug print
+                    initialize_language_map() // Call the initializer function
+                    // Alternatively, put the HashMap creation logic directly here:
+                    // let mut m = HashMap::new(); /* ... inserts ... */ m
+                })
+                .get(ext_str.to_lowercase().as_str()) // Now get from the initialized HashMap
+                .copied()
+        })
+}
+
+/// Adds line numbers to the content string.
+pub fn add_line_numbers(content: &str) -> String {
+    let lines: Vec<&str> = content.lines().collect();
+    let num_lines = lines.len();
+    // Calculate padding needed for line numbers (e.g., 1, 10, 100)
+    let padding = if num_lines == 0 {
+        1
+    } else {
+        num_lines.to_string().len()
+    };
+
+    lines
+        .into_iter()
+        .enumerate()
+        .map(|(i, line)| format!("{:<width$}  {}", i + 1, line, width = padding))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Where `--line-numbers` puts the numbers.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineNumberStyle {
+    /// Before each line of the content (inside the Markdown code fence).
+    #[default]
+    Inline,
+    /// In a column of their own, so the code stays intact for highlighting: an
+    /// HTML table cell next to the code fence in Markdown, a `│`-separated gutter
+    /// otherwise.
+    Gutter,
+}
+
+/// Formats a file's content for the selected format (line numbers, escaping, highlighting).
+///
+/// Returns the formatted body, ending with a newline, and the Markdown code fence
+/// to put around it (long enough not to clash with backticks in the content).
+/// The fence is empty if the body brings its own, as with Markdown line number gutters.
+pub fn format_body(path: &Path, content: &str, options: &Options) -> (String, String) {
+    // Expand tabs before numbering lines, so tab stops don't count the number column
+    let content = match options.expand_tabs {
+        Some(width) => Cow::Owned(tabs::expand_tabs(content, width)),
+        None => Cow::Borrowed(content),
+    };
+    // No gutter for empty files, which have no line to number
+    let gutter = options.line_numbers
+        && options.line_numbers_style == LineNumberStyle::Gutter
+        && !content.is_empty();
+
+    // Apply line numbers if requested *before* formatting
+    let processed_content = if options.line_numbers && !gutter {
+        Cow::Owned(add_line_numbers(&content))
+    } else {
+        content // Keep original content if no line numbers
+    };
+
+    // Determine necessary backtick count (handle content with backticks)
+    let mut backticks = "```".to_string();
+    while processed_content.contains(&backticks) {
+        backticks.push('`');
+    }
+
+    // Wrap after picking the fence: breaks only ever shorten runs of backticks
+    let processed_content = match options.wrap {
+        Some(columns) => Cow::Owned(wrap::wrap_lines(&processed_content, columns)),
+        None => processed_content,
+    };
+
+    // Which lines the gutter leaves unnumbered: continuations of wrapped lines
+    let continued: Vec<bool> = if gutter {
+        processed_content
+            .lines()
+            .map(|line| options.wrap.is_some() && line.starts_with(wrap::MARKER))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut body = if options.cxml {
+        escape_xml(&processed_content)
+    } else if options.color && !options.markdown {
+        highlight::highlight(path, &processed_content)
+    } else {
+        processed_content.into_owned() // File content (potentially line-numbered)
+    };
+    body.push('\n');
+    if continued.is_empty() {
+        return (body, backticks);
+    }
+    let numbers = number_lines(&continued);
+    if options.markdown && !options.cxml {
+        let lang = language(path).unwrap_or("");
+        let column: String = numbers
+            .iter()
+            .map(|number| number.map(|number| number.to_string()).unwrap_or_default() + "\n")
+            .collect();
+        let table = format!(
+            "<table><tr><td><pre>\n{column}</pre></td><td>\n\n{backticks}{lang}\n{body}{backticks}\n\n</td></tr></table>\n"
+        );
+        return (table, String::new());
+    }
+    let dim = options.color && !options.markdown;
+    (add_gutter(&body, &numbers, dim), backticks)
+}
+
+/// Numbers the lines, leaving the `continued` ones (of wrapped lines) without a number.
+fn number_lines(continued: &[bool]) -> Vec<Option<usize>> {
+    let mut number = 0;
+    continued
+        .iter()
+        .map(|&continued| {
+            (!continued).then(|| {
+                number += 1;
+                number
+            })
+        })
+        .collect()
+}
+
+/// Prefixes the lines of `body` with a gutter holding their `numbers`, separated
+/// from the code by a `│` and dimmed if `dim`.
+fn add_gutter(body: &str, numbers: &[Option<usize>], dim: bool) -> String {
+    let width = numbers
+        .iter()
+        .flatten()
+        .max()
+        .unwrap_or(&0)
+        .to_string()
+        .len();
+    let (dim, reset) = if dim {
+        (highlight::DIM, highlight::RESET)
+    } else {
+        ("", "")
+    };
+    let mut numbered = String::with_capacity(body.len() + numbers.len() * (width + 3));
+    for (i, line) in body.split_inclusive('\n').enumerate() {
+        // Highlighting may append a reset after the last line
+        if let Some(number) = numbers.get(i) {
+            let number = number.map(|number| number.to_string()).unwrap_or_default();
+            let _ = write!(numbered, "{dim}{number:>width$} │{reset} ");
+        }
+        numbered.push_str(line);
+    }
+    numbered
+}
+
+/// Basic XML escaping for content - replace '&', '<', '>'
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Returns the path as shown in the output: relative if possible for cleaner output, fallback to absolute.
+///
+/// Windows extended-length paths lose their `\\?\` prefix (`\\?\C:\src` is shown as `C:\src`).
+pub fn display_path(path: &Path) -> &Path {
+    let path = strip_verbatim(path);
+    path.strip_prefix(".").unwrap_or(path)
+}
+
+/// Strips the `\\?\` prefix of a Windows extended-length path to a drive.
+fn strip_verbatim(path: &Path) -> &Path {
+    if !cfg!(windows) {
+        return path;
+    }
+    path.to_str()
+        .and_then(strip_verbatim_prefix)
+        .map_or(path, Path::new)
+}
+
+/// Returns what follows the `\\?\` prefix of `path`, if it is followed by a drive.
+///
+/// UNC paths (`\\?\UNC\server\share`) keep it: they would have to be rewritten
+/// as `\\server\share`, not just shortened.
+fn strip_verbatim_prefix(path: &str) -> Option<&str> {
+    path.strip_prefix(r"\\?\")
+        .filter(|rest| rest.as_bytes().get(1) == Some(&b':'))
+}
+
+/// A short name shown in place of a directory in the output, as in `DIR=NAME`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    pub dir: PathBuf,
+    pub name: String,
+}
+
+impl FromStr for Alias {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        match spec.rsplit_once('=') {
+            Some((dir, name)) if !dir.is_empty() && !name.is_empty() => Ok(Alias {
+                dir: PathBuf::from(dir),
+                name: name.to_string(),
+            }),
+            _ => Err(format!("expected DIR=NAME, found '{spec}'")),
+        }
+    }
+}
+
+/// Returns the path as shown in the output: [`display_path`], starting with the
+/// name of its directory's --alias, if any, unless it was renamed for colliding
+/// with another.
+pub fn output_path<'a>(path: &'a Path, options: &Options) -> Cow<'a, Path> {
+    if let Some(renamed) = options.renamed.get(path) {
+        return Cow::Owned(renamed.clone());
+    }
+    let shown = display_path(path);
+    let aliased = options
+        .aliases
+        .iter()
+        .find_map(|alias| {
+            let rest = shown.strip_prefix(display_path(&alias.dir)).ok()?;
+            Some(Cow::Owned(Path::new(&alias.name).join(rest)))
+        })
+        .unwrap_or(Cow::Borrowed(shown));
+    nfc(aliased, options)
+}
+
+/// Normalizes `path` to Unicode NFC with --nfc (paths that aren't UTF-8 are kept).
+fn nfc<'a>(path: Cow<'a, Path>, options: &Options) -> Cow<'a, Path> {
+    match path.to_str() {
+        Some(name) if options.nfc && !is_nfc(name) => {
+            Cow::Owned(PathBuf::from(name.nfc().collect::<String>()))
+        }
+        _ => path,
+    }
+}
+
+/// Returns the index of the input path (root) `path` was found under.
+pub fn root_index(path: &Path, options: &Options) -> Option<usize> {
+    let path = display_path(path);
+    options
+        .paths
+        .iter()
+        .position(|root| path.starts_with(display_path(root)))
+}
+
+/// Returns `path` without the components that would lead out of a directory it
+/// is joined to (roots, `..`), for writing files under an output directory.
+pub fn contained_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
+
+/// `path` with its `.` and `..` components resolved, without touching the filesystem.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Returns `path` relative to the input path it was found under (itself for a
+/// file given as an input path).
+pub fn relative_to_root<'a>(path: &'a Path, options: &Options) -> &'a Path {
+    root_index(path, options)
+        .and_then(|root| {
+            display_path(path)
+                .strip_prefix(display_path(&options.paths[root]))
+                .ok()
+        })
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .unwrap_or(path)
+}
+
+/// A section of the output, under a header of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    /// The project manifests leading the output, with --manifests.
+    Manifests,
+    /// The files of an input path (by index), with --label-roots.
+    Root(usize),
+}
+
+/// Returns the section `path` belongs to, if the output has sections.
+pub fn section_of(path: &Path, options: &Options) -> Option<Section> {
+    if options.manifests && manifests::is_manifest(path) {
+        return Some(Section::Manifests);
+    }
+    options
+        .label_roots
+        .then(|| root_index(path, options))
+        .flatten()
+        .map(Section::Root)
+}
+
+/// Returns the path of a file as written in the file header: relative to its root
+/// with --label-roots (the file name for a file given as a root).
+pub fn header_path<'a>(path: &'a Path, options: &Options) -> Cow<'a, Path> {
+    let shown = display_path(path);
+    let Some(Section::Root(root)) = section_of(path, options) else {
+        return output_path(path, options);
+    };
+    if let Some(renamed) = options.renamed.get(path) {
+        return Cow::Owned(renamed.clone());
+    }
+    let relative = match shown.strip_prefix(display_path(&options.paths[root])) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative,
+        _ => shown.file_name().map_or(shown, Path::new),
+    };
+    nfc(Cow::Borrowed(relative), options)
+}
+
+/// Writes the header opening `section`.
+pub fn write_section_header(
+    writer: &mut dyn Write,
+    section: Section,
+    options: &Options,
+) -> io::Result<()> {
+    let label = match section {
+        Section::Manifests => "Manifests".to_string(),
+        Section::Root(root) => {
+            let root = &options.paths[root];
+            let label = output_path(root, options);
+            match label.components().as_path() {
+                label if label.as_os_str().is_empty() => root.display().to_string(),
+                label => label.display().to_string(), // Without a trailing slash
+            }
+        }
+    };
+    if options.cxml {
+        match section {
+            Section::Manifests => writeln!(writer, "<manifests>"),
+            Section::Root(_) => {
+                let label = escape_xml(&label).replace('"', "&quot;");
+                writeln!(writer, "<root path=\"{label}\">")
+            }
+        }
+    } else if options.markdown {
+        writeln!(writer, "## {label}")?;
+        writeln!(writer)
+    } else if options.color {
+        let (bold, reset) = (highlight::BOLD, highlight::RESET);
+        writeln!(writer, "{bold}=== {label} ==={reset}")?;
+        writeln!(writer)
+    } else {
+        writeln!(writer, "=== {label} ===")?;
+        writeln!(writer)
+    }
+}
+
+/// Writes what closes `section` (only Claude XML has something).
+pub fn write_section_footer(
+    writer: &mut dyn Write,
+    section: Section,
+    options: &Options,
+) -> io::Result<()> {
+    match section {
+        _ if !options.cxml => Ok(()),
+        Section::Manifests => writeln!(writer, "</manifests>"),
+        Section::Root(_) => writeln!(writer, "</root>"),
+    }
+}
+
+/// Writes everything that precedes a file's content in the selected format.
+///
+/// `backticks` is the Markdown code fence, long enough not to clash with the content,
+/// `index` the Claude XML document index, `tags` the file's tags (a Claude XML attribute)
+/// and `chunk` the index of the chunk of the file, with --chunk (shown as `path#chunk-i`).
+pub fn write_file_header(
+    writer: &mut dyn Write,
+    path: &Path,
+    options: &Options,
+    backticks: &str,
+    index: usize,
+    tags: &[&str],
+    chunk: Option<usize>,
+) -> io::Result<()> {
+    let display_path = header_path(path, options);
+    let display_path = match chunk {
+        Some(chunk) => format!("{}#chunk-{chunk}", display_path.display()),
+        None => display_path.display().to_string(),
+    };
+
+    // --- Select Output Format ---
+    if options.cxml {
+        // Claude XML Format
+        if tags.is_empty() {
+            writeln!(writer, "<document index=\"{index}\">")?;
+        } else {
+            let tags = escape_xml(&tags.join(",")).replace('"', "&quot;");
+            writeln!(writer, "<document index=\"{index}\" tags=\"{tags}\">")?;
+        }
+        let source = escape_xml(&display_path); // Use relative path
+        writeln!(writer, "<source>{source}</source>")?;
+        writeln!(writer, "<document_content>")?;
+    } else if options.markdown {
+        // Markdown Format
+        let lang = language(path).unwrap_or(""); // Get language tag or empty string
+
+        if !options.front_matter {
+            writeln!(writer, "{display_path}")?; // File path (relative)
+        }
+        if !backticks.is_empty() {
+            writeln!(writer, "{backticks}{lang}")?; // Opening fence with language tag
+        }
+    } else if options.color {
+        // Default Format, highlighted for the terminal
+        let (bold, dim, reset) = (highlight::BOLD, highlight::DIM, highlight::RESET);
+        writeln!(writer, "{bold}{display_path}{reset}")?;
+        writeln!(writer, "{dim}---{reset}")?;
+    } else {
+        // Default Format
+        writeln!(writer, "{display_path}")?; // File path (relative)
+        writeln!(writer, "---")?;
+    }
+
+    Ok(())
+}
+
+/// Writes everything that follows a file's content in the selected format.
+pub fn write_file_footer(
+    writer: &mut dyn Write,
+    options: &Options,
+    backticks: &str,
+) -> io::Result<()> {
+    if options.cxml {
+        writeln!(writer, "</document_content>")?;
+        writeln!(writer, "</document>")?;
+    } else if options.markdown {
+        if !backticks.is_empty() {
+            writeln!(writer, "{backticks}")?; // Closing fence
+        }
+        writeln!(writer)?; // Add a blank line for separation
+    } else if options.color {
+        let (dim, reset) = (highlight::DIM, highlight::RESET);
+        writeln!(writer, "{dim}---{reset}")?;
+        writeln!(writer)?;
+    } else {
+        // writeln!(writer)?; // Original python version adds blank line here - removed for closer match
+        writeln!(writer, "---")?;
+        writeln!(writer)?; // Add blank line after the closing separator
+    }
+
+    Ok(())
+}
+
+/// Writes the marker noting that the output was cut short, for `reason`
+/// (--max-output-bytes, or an interruption).
+pub fn write_truncation_marker(
+    writer: &mut dyn Write,
+    reason: &str,
+    options: &Options,
+) -> io::Result<()> {
+    let message = format!("Output truncated: {reason}");
+    if options.cxml {
+        writeln!(writer, "<!-- {message} -->")
+    } else {
+        writeln!(writer, "[... {message} ...]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn options(args: &[&str]) -> Options {
+        Options::parse_from(["files-ingest"].iter().chain(args))
+    }
+
+    #[test]
+    fn verbatim_prefix_is_stripped_before_a_drive_only() {
+        assert_eq!(
+            strip_verbatim_prefix(r"\\?\C:\src\lib.rs"),
+            Some(r"C:\src\lib.rs")
+        );
+        assert_eq!(strip_verbatim_prefix(r"\\?\UNC\server\share\lib.rs"), None);
+        assert_eq!(strip_verbatim_prefix(r"C:\src\lib.rs"), None);
+        assert_eq!(strip_verbatim_prefix("src/lib.rs"), None);
+    }
+
+    #[test]
+    fn output_path_shows_verbatim_and_device_paths() {
+        let options = options(&["."]);
+        let shown = |path: &str| output_path(Path::new(path), &options).into_owned();
+
+        let drive = r"\\?\C:\src\lib.rs";
+        let expected = if cfg!(windows) {
+            r"C:\src\lib.rs"
+        } else {
+            drive
+        };
+        assert_eq!(shown(drive), Path::new(expected));
+        assert_eq!(display_path(Path::new(drive)), Path::new(expected));
+
+        let unc = r"\\?\UNC\server\share\lib.rs";
+        assert_eq!(shown(unc), Path::new(unc));
+        assert_eq!(display_path(Path::new(unc)), Path::new(unc));
+
+        // Device names are only special to the walk, which skips them
+        assert_eq!(shown("./src/nul.txt"), Path::new("src/nul.txt"));
+        assert_eq!(shown("CON"), Path::new("CON"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn verbatim_paths_match_their_root_and_alias() {
+        let options = options(&[r"\\?\C:\src", "--alias", r"\\?\C:\src=app"]);
+        let path = Path::new(r"\\?\C:\src\lib.rs");
+        assert_eq!(root_index(path, &options), Some(0));
+        assert_eq!(output_path(path, &options), Path::new(r"app\lib.rs"));
+    }
+}
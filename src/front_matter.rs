@@ -0,0 +1,58 @@
+//! The YAML front matter preceding each file with `--front-matter`, for static
+//! site generators and knowledge bases that index Markdown files by it.
+
+use crate::Options;
+use crate::format::{header_path, language};
+use crate::provenance::format_timestamp;
+use crate::provider::FileProvider;
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Writes the front-matter block of the file at `path`. The SHA-256 is that of
+/// the file as read, before filters; files without a modification time (such
+/// as in-memory ones) have no `mtime`.
+pub fn write(
+    writer: &mut dyn Write,
+    path: &Path,
+    provider: &dyn FileProvider,
+    options: &Options,
+) -> io::Result<()> {
+    let mut hasher = Sha256::new();
+    let mut reader = provider.open(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => break,
+            read => hasher.update(&buffer[..read]),
+        }
+    }
+    let sha = hasher
+        .finalize()
+        .iter()
+        .fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        });
+    // JSON strings are valid YAML scalars, whatever characters the path holds
+    let display_path = serde_json::to_string(&header_path(path, options).to_string_lossy())
+        .map_err(io::Error::other)?;
+
+    writeln!(writer, "---")?;
+    writeln!(writer, "path: {display_path}")?;
+    if let Some(lang) = language(path) {
+        writeln!(writer, "lang: {lang}")?;
+    }
+    writeln!(writer, "sha: {sha}")?;
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok());
+    if let Some(modified) = modified {
+        writeln!(writer, "mtime: {}", format_timestamp(modified.as_secs()))?;
+    }
+    writeln!(writer, "---")
+}
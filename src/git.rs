@@ -0,0 +1,59 @@
+//! Git metadata of the source tree, for the run header and the repository summary.
+
+use std::path::Path;
+use std::process::Command;
+
+/// Runs `git` with `args` in `dir`, returning its trimmed output if it succeeds.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// The directory to run git in for an input path (the parent of a file).
+pub fn dir_of(path: &Path) -> &Path {
+    if path.is_dir() {
+        return path;
+    }
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// The commit checked out in the repository holding `dir`, if any.
+pub fn commit(dir: &Path) -> Option<String> {
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// Whether the repository holding `dir` has uncommitted changes.
+pub fn is_dirty(dir: &Path) -> bool {
+    git(dir, &["status", "--porcelain"]).is_some_and(|status| !status.is_empty())
+}
+
+/// The top-level directory of the repository holding `dir`.
+pub fn toplevel(dir: &Path) -> Option<String> {
+    git(dir, &["rev-parse", "--show-toplevel"])
+}
+
+/// The checked-out branch, or `None` on a detached `HEAD`.
+pub fn branch(dir: &Path) -> Option<String> {
+    git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]).filter(|branch| branch != "HEAD")
+}
+
+/// The URL of the `origin` remote, or else of the first remote.
+pub fn remote_url(dir: &Path) -> Option<String> {
+    let remotes = git(dir, &["remote"])?;
+    let remote = remotes
+        .lines()
+        .find(|remote| *remote == "origin")
+        .or_else(|| remotes.lines().next())?;
+    git(dir, &["remote", "get-url", remote])
+}
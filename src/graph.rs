@@ -0,0 +1,253 @@
+//! Cross-file reference graphs (`--graph`): which files import which, or call
+//! the functions of which. Written out with `--format dot` or `--format json`,
+//! and a signal for `--pack`, which favors the files many others depend on.
+//!
+//! References are found by patterns rather than by parsing, so the graphs are
+//! approximate: imports by file name or stem (see [`references`]), calls by the
+//! names of the functions defined in Rust, Python, Ruby, JavaScript, TypeScript
+//! and Go files.
+
+use crate::export::OutputFormat;
+use crate::format::language;
+use crate::{AppError, Ingestor, Summary};
+use clap::ValueEnum;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// What the edges of a graph stand for.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphKind {
+    /// A file imports (or names) another.
+    Imports,
+    /// A file calls functions defined in another.
+    Callers,
+}
+
+impl GraphKind {
+    fn name(self) -> &'static str {
+        match self {
+            GraphKind::Imports => "imports",
+            GraphKind::Callers => "callers",
+        }
+    }
+}
+
+/// Keywords starting the lines that import other files, in common languages.
+const IMPORT_KEYWORDS: &[&str] = &[
+    "use", "pub use", "mod", "pub mod", "import", "from", "require", "#include", "include",
+    "@import",
+];
+
+/// File stems too common to be linked on import lines alone.
+const GENERIC_STEMS: &[&str] = &["mod", "lib", "main", "index", "__init__", "init"];
+
+/// Function names too short to tell calls apart.
+const MIN_NAME_LEN: usize = 3;
+
+static DEFINITION: OnceLock<Regex> = OnceLock::new();
+static CALL: OnceLock<Regex> = OnceLock::new();
+
+/// The edges between files, by index in the list of files: (from, to) ->
+/// weight (1 for imports, the number of call sites for calls).
+pub type Edges = BTreeMap<(usize, usize), usize>;
+
+/// Builds the graph of `kind` between `files` (path and content).
+pub fn build(kind: GraphKind, files: &[(PathBuf, String)]) -> Edges {
+    let mut edges = Edges::new();
+    match kind {
+        GraphKind::Imports => {
+            for (from, (_, content)) in files.iter().enumerate() {
+                for (to, (path, _)) in files.iter().enumerate() {
+                    if from != to && references(content, path) {
+                        edges.insert((from, to), 1);
+                    }
+                }
+            }
+        }
+        GraphKind::Callers => {
+            // Function name -> defining files
+            let mut definitions: HashMap<&str, Vec<usize>> = HashMap::new();
+            for (i, (path, content)) in files.iter().enumerate() {
+                for name in defined_functions(path, content) {
+                    definitions.entry(name).or_default().push(i);
+                }
+            }
+            let module = |i: usize| files[i].0.file_stem().and_then(|stem| stem.to_str());
+            // Calls of a name defined in one file only, or qualified by the module
+            // of the definition (`graph::build(`, `utils.parse(`); other methods
+            // are left out, as their receiver's type is unknown
+            let call = CALL.get_or_init(|| {
+                Regex::new(r"(?:([A-Za-z_]\w*)(?:::|\.))?([A-Za-z_$][\w$]*)\s*\(")
+                    .expect("valid regex")
+            });
+            for (from, (path, content)) in files.iter().enumerate() {
+                if !is_supported(path) {
+                    continue;
+                }
+                for captures in call.captures_iter(content) {
+                    let (Some(whole), Some(name)) = (captures.get(0), captures.get(2)) else {
+                        continue;
+                    };
+                    if content[..whole.start()].ends_with(['.', ':']) {
+                        continue; // Chained (`a.b().c(`) or qualified further
+                    }
+                    let Some(definers) = definitions.get(name.as_str()) else {
+                        continue;
+                    };
+                    let to = match captures.get(1) {
+                        Some(qualifier) => definers
+                            .iter()
+                            .copied()
+                            .find(|&i| module(i) == Some(qualifier.as_str())),
+                        None if definers.len() == 1 => Some(definers[0]),
+                        None => None,
+                    };
+                    if let Some(to) = to.filter(|&to| to != from) {
+                        *edges.entry((from, to)).or_default() += 1;
+                    }
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// The number of other files referencing each of `files` in the graph of `kind`.
+pub fn in_degrees(kind: GraphKind, files: &[(PathBuf, String)]) -> HashMap<PathBuf, usize> {
+    let mut degrees = HashMap::new();
+    for (_, to) in build(kind, files).into_keys() {
+        *degrees.entry(files[to].0.clone()).or_default() += 1;
+    }
+    degrees
+}
+
+/// Runs the ingestion, writing the graph of `kind` between the documents to
+/// `writer` in `format` (`dot` or `json`).
+pub fn write(
+    ingestor: &Ingestor,
+    kind: GraphKind,
+    format: OutputFormat,
+    writer: &mut dyn Write,
+) -> Result<Summary, AppError> {
+    let mut files = Vec::new();
+    let summary = ingestor.ingest_with(|document| {
+        files.push((document.path, document.content));
+        Ok(())
+    })?;
+    let edges = build(kind, &files);
+    let label = |i: usize| files[i].0.to_string_lossy();
+    if format == OutputFormat::Dot {
+        writeln!(writer, "digraph {} {{", kind.name())?;
+        for (path, _) in &files {
+            writeln!(writer, "  {};", dot_id(&path.to_string_lossy()))?;
+        }
+        for (&(from, to), &weight) in &edges {
+            let (from, to) = (dot_id(&label(from)), dot_id(&label(to)));
+            if weight > 1 {
+                writeln!(writer, "  {from} -> {to} [weight={weight}];")?;
+            } else {
+                writeln!(writer, "  {from} -> {to};")?;
+            }
+        }
+        writeln!(writer, "}}")?;
+    } else {
+        let graph = serde_json::json!({
+            "kind": kind.name(),
+            "nodes": files.iter().map(|(path, _)| path.to_string_lossy()).collect::<Vec<_>>(),
+            "edges": edges
+                .iter()
+                .map(|(&(from, to), &weight)| serde_json::json!({
+                    "from": label(from),
+                    "to": label(to),
+                    "weight": weight,
+                }))
+                .collect::<Vec<_>>(),
+        });
+        serde_json::to_writer_pretty(&mut *writer, &graph).map_err(io::Error::from)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(summary)
+}
+
+/// A quoted DOT identifier.
+fn dot_id(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Whether the language of the file at `path` has its functions found.
+fn is_supported(path: &Path) -> bool {
+    matches!(
+        language(path),
+        Some("rust" | "python" | "ruby" | "javascript" | "typescript" | "go")
+    )
+}
+
+/// The names of the functions defined in `content`, for supported languages.
+fn defined_functions<'a>(path: &Path, content: &'a str) -> HashSet<&'a str> {
+    if !is_supported(path) {
+        return HashSet::new();
+    }
+    let definition = DEFINITION.get_or_init(|| {
+        // fn (Rust), def (Python, Ruby), function and arrow functions (JavaScript,
+        // TypeScript), func with an optional receiver (Go)
+        Regex::new(concat!(
+            r"(?m)\bfn\s+([A-Za-z_]\w*)",
+            r"|^\s*def\s+(?:self\.)?([A-Za-z_]\w*)",
+            r"|\bfunction\s*\*?\s*([A-Za-z_$][\w$]*)",
+            r"|\b(?:const|let|var)\s+([A-Za-z_$][\w$]*)\s*=\s*(?:async\s*)?(?:\([^)]*\)|[A-Za-z_$][\w$]*)\s*=>",
+            r"|^func\s+(?:\([^)]*\)\s*)?([A-Za-z_]\w*)",
+        ))
+        .expect("valid regex")
+    });
+    definition
+        .captures_iter(content)
+        .filter_map(|captures| captures.iter().skip(1).flatten().next())
+        .map(|name| name.as_str())
+        .filter(|name| name.len() >= MIN_NAME_LEN)
+        .collect()
+}
+
+/// Whether `content` references the file at `path`: by its file name
+/// (`utils.py`), its path without extension (`components/Button`), or its stem
+/// on an import line (`use crate::format`, `from .models import User`).
+pub fn references(content: &str, path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    if mentions(content, name) {
+        return true;
+    }
+    let without_extension = path.with_extension("");
+    let without_extension = without_extension.to_string_lossy().replace('\\', "/");
+    if without_extension.contains('/') && mentions(content, &without_extension) {
+        return true;
+    }
+    let Some(stem) = path.file_stem().and_then(|stem| stem.to_str()) else {
+        return false;
+    };
+    if stem.len() < 3 || GENERIC_STEMS.contains(&stem) {
+        return false;
+    }
+    content.lines().any(|line| {
+        let line = line.trim_start();
+        IMPORT_KEYWORDS.iter().any(|keyword| {
+            line.strip_prefix(keyword)
+                .is_some_and(|rest| rest.starts_with([' ', '(', '<', '"']))
+        }) && mentions(line, stem)
+    })
+}
+
+/// Whether `needle` appears in `haystack` as a whole word (not within a longer
+/// identifier or file name).
+fn mentions(haystack: &str, needle: &str) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    haystack.match_indices(needle).any(|(start, _)| {
+        let before = haystack[..start].chars().next_back();
+        let after = haystack[start + needle.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
@@ -0,0 +1,51 @@
+//! ANSI syntax highlighting for interactive terminal previews (`--color`).
+
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Theme, ThemeSet};
+use syntect::parsing::SyntaxSet;
+use syntect::util::{LinesWithEndings, as_24_bit_terminal_escaped};
+
+/// ANSI sequence dimming the text that follows (used for separators).
+pub const DIM: &str = "\x1b[2m";
+/// ANSI sequence making the text that follows bold (used for paths).
+pub const BOLD: &str = "\x1b[1m";
+/// ANSI sequence resetting all attributes.
+pub const RESET: &str = "\x1b[0m";
+
+const THEME_NAME: &str = "base16-ocean.dark";
+
+// Loading the syntax and theme sets is comparatively slow, so do it once, on first use.
+static SYNTAX_SET: OnceLock<SyntaxSet> = OnceLock::new();
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Highlights `content` using the syntax matching the extension of `path`.
+///
+/// Files without a known syntax are returned as plain text.
+pub fn highlight(path: &Path, content: &str) -> String {
+    let syntax_set = SYNTAX_SET.get_or_init(SyntaxSet::load_defaults_newlines);
+    let theme = THEME.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        themes.remove(THEME_NAME).unwrap_or_default()
+    });
+
+    let Some(syntax) = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(|ext| syntax_set.find_syntax_by_extension(&ext.to_lowercase()))
+    else {
+        return content.to_string();
+    };
+
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut output = String::with_capacity(content.len() * 2);
+    for line in LinesWithEndings::from(content) {
+        match highlighter.highlight_line(line, syntax_set) {
+            Ok(ranges) => output.push_str(&as_24_bit_terminal_escaped(&ranges, false)),
+            Err(_) => output.push_str(line), // Fall back to plain text for this line
+        }
+    }
+    output.push_str(RESET);
+    output
+}
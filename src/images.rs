@@ -0,0 +1,74 @@
+//! Image handling for vision-capable models.
+//!
+//! Image files are not valid UTF-8 (except SVG) and would normally be skipped.
+//! With `--images` they are included either inline as base64 data URIs or as a
+//! short reference line carrying the path and pixel dimensions.
+
+use base64::Engine;
+use clap::ValueEnum;
+use std::path::Path;
+
+/// How image files selected by the filters are included in the output.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageMode {
+    /// Embed the image as a base64 `data:` URI.
+    Base64,
+    /// Emit a reference with the image path and dimensions.
+    Ref,
+}
+
+/// Returns the MIME type of `path` if it is a supported image file.
+pub fn image_mime_type(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    let mime = match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+/// Renders the image file at `path`, with content `bytes`, as document content according to `mode`.
+pub fn render_image(path: &Path, bytes: &[u8], mime: &str, mode: ImageMode) -> String {
+    match mode {
+        ImageMode::Base64 => {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+            format!("data:{mime};base64,{encoded}")
+        }
+        ImageMode::Ref => {
+            let dimensions = image_dimensions(bytes, mime)
+                .map(|(width, height)| format!(", {width}x{height}"))
+                .unwrap_or_default();
+            format!(
+                "[image: {} ({mime}{dimensions}, {} bytes)]",
+                path.display(),
+                bytes.len()
+            )
+        }
+    }
+}
+
+/// Determines the pixel dimensions of an image, if they can be read from its header.
+fn image_dimensions(bytes: &[u8], mime: &str) -> Option<(String, String)> {
+    if mime == "image/svg+xml" {
+        return svg_dimensions(std::str::from_utf8(bytes).ok()?);
+    }
+    let size = imagesize::blob_size(bytes).ok()?;
+    Some((size.width.to_string(), size.height.to_string()))
+}
+
+/// Reads the `width`/`height` attributes of the root `<svg>` element.
+fn svg_dimensions(svg: &str) -> Option<(String, String)> {
+    let start = svg.find("<svg")?;
+    let end = start + svg[start..].find('>')?;
+    let tag = &svg[start..end];
+    let attribute = |name: &str| {
+        let value_start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+        let value_len = tag[value_start..].find('"')?;
+        Some(tag[value_start..value_start + value_len].to_string())
+    };
+    Some((attribute("width")?, attribute("height")?))
+}
@@ -0,0 +1,110 @@
+//! Following C/C++ includes (`--follow-includes`): of the headers, only those
+//! the sources include, directly or through other headers, are kept.
+//!
+//! `#include "..."` is looked up next to the including file, then under each
+//! input directory and the current directory (and their `include` directory):
+//! among the files the walk selected, else on disk, so that headers next to a
+//! source given on its own are found too. The lookup is therefore not limited
+//! to the input paths. `#include <...>` is left to the system.
+
+use crate::Options;
+use crate::format::normalize;
+use crate::provider::FileProvider;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Extensions of header files.
+const HEADER_EXTENSIONS: &[&str] = &["h", "hh", "hpp", "hxx", "h++", "inc", "ipp", "tpp"];
+
+static INCLUDE: OnceLock<Regex> = OnceLock::new();
+
+/// Keeps the files of `files` that aren't headers, and the headers they include
+/// transitively (adding those the walk didn't select).
+pub fn follow(files: &mut Vec<PathBuf>, provider: &dyn FileProvider, options: &Options) {
+    // Lexically normalized path -> path as walked
+    let walked: HashMap<PathBuf, PathBuf> = files
+        .iter()
+        .map(|path| (normalize(path), path.clone()))
+        .collect();
+    let roots: Vec<PathBuf> = options
+        .paths
+        .iter()
+        .map(PathBuf::as_path)
+        .chain([Path::new("")])
+        .flat_map(|root| [normalize(root), normalize(&root.join("include"))])
+        .collect();
+    let include = INCLUDE
+        .get_or_init(|| Regex::new(r#"(?m)^\s*#\s*include\s*"([^"]+)""#).expect("valid regex"));
+
+    let mut kept: HashSet<PathBuf> = HashSet::new();
+    let mut added = Vec::new();
+    let mut pending: Vec<PathBuf> = files
+        .iter()
+        .filter(|path| !is_header(path))
+        .cloned()
+        .collect();
+    while let Some(path) = pending.pop() {
+        if !kept.insert(path.clone()) {
+            continue;
+        }
+        let Ok(content) = provider.read_text(&path) else {
+            continue;
+        };
+        let dir = normalize(path.parent().unwrap_or(Path::new("")));
+        for name in include
+            .captures_iter(&content)
+            .filter_map(|captures| captures.get(1))
+        {
+            let Some((header, was_walked)) =
+                resolve(name.as_str(), &dir, &roots, &walked, provider)
+            else {
+                continue;
+            };
+            if !kept.contains(&header) {
+                if !was_walked && !added.contains(&header) {
+                    added.push(header.clone());
+                }
+                pending.push(header);
+            }
+        }
+    }
+    files.retain(|path| kept.contains(path));
+    files.extend(added);
+}
+
+/// The header `name` included from a file in `dir`: the first of its candidate
+/// paths the walk selected, else the first one on disk (and whether it was walked).
+fn resolve(
+    name: &str,
+    dir: &Path,
+    roots: &[PathBuf],
+    walked: &HashMap<PathBuf, PathBuf>,
+    provider: &dyn FileProvider,
+) -> Option<(PathBuf, bool)> {
+    let candidates: Vec<PathBuf> = std::iter::once(dir)
+        .chain(roots.iter().map(PathBuf::as_path))
+        .map(|base| normalize(&base.join(name)))
+        .collect();
+    candidates
+        .iter()
+        .find_map(|candidate| Some((walked.get(candidate)?.clone(), true)))
+        .or_else(|| {
+            candidates
+                .into_iter()
+                .find(|candidate| is_header(candidate) && provider.exists(candidate))
+                .map(|candidate| (candidate, false))
+        })
+}
+
+/// Whether the file at `path` is a C/C++ header.
+fn is_header(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            HEADER_EXTENSIONS
+                .iter()
+                .any(|header| ext.eq_ignore_ascii_case(header))
+        })
+}
@@ -0,0 +1,73 @@
+//! Selection of infrastructure and configuration files (`--config-only`), for
+//! DevOps reviews that don't need the application code.
+
+use crate::Options;
+use std::borrow::Cow;
+use std::path::Path;
+
+/// The hidden files and directories holding configuration, let into the walk
+/// as if given with --include-hidden-pattern.
+const HIDDEN_PATTERNS: &[&str] = &[
+    ".github/",
+    ".gitlab-ci.yml",
+    ".gitlab/",
+    ".circleci/",
+    ".buildkite/",
+    ".travis.yml",
+    ".dockerignore",
+    ".editorconfig",
+    ".env.example",
+    ".tool-versions",
+    ".terraform-version",
+    ".pre-commit-config.yaml",
+    ".*rc",
+    ".*rc.json",
+    ".*rc.yml",
+    ".*rc.yaml",
+];
+
+/// Extensions of configuration formats (YAML covers CI pipelines, Kubernetes
+/// manifests, Compose files and Helm values).
+const EXTENSIONS: &[&str] = &[
+    "yml",
+    "yaml",
+    "tf",
+    "tfvars",
+    "hcl",
+    "dockerfile",
+    "conf",
+    "cfg",
+    "ini",
+];
+
+/// Files that are configuration by name.
+const NAMES: &[&str] = &["Dockerfile", "Containerfile", "Jenkinsfile", "Vagrantfile"];
+
+/// Lets the hidden configuration files into the walk, with --config-only.
+pub fn resolve(options: &mut Cow<Options>) {
+    if options.config_only && !options.include_hidden {
+        options
+            .to_mut()
+            .include_hidden_patterns
+            .extend(HIDDEN_PATTERNS.iter().map(|pattern| (*pattern).to_string()));
+    }
+}
+
+/// Whether the file at `path` is infrastructure or configuration: a
+/// Dockerfile, CI pipeline, Terraform or YAML file, or a dotfile config.
+pub fn is_config(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    // Dockerfile.dev, Jenkinsfile.release...
+    let base = name.split('.').next().unwrap_or(name);
+    let by_extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        });
+    by_extension || NAMES.contains(&base) || name.starts_with('.')
+}
@@ -0,0 +1,1304 @@
+//! The ingestion pipeline: select files, read and format them, write them in order.
+
+use crate::chunk::Chunk;
+use crate::config::Config;
+use crate::filter::{ContentFilter, FilterDecision, FilterSpec};
+use crate::format::{
+    LineNumberStyle, Section, display_path, escape_xml, format_body, header_path, language,
+    output_path, root_index, section_of, write_file_footer, write_file_header,
+    write_section_footer, write_section_header, write_truncation_marker,
+};
+use crate::order::Ranking;
+use crate::owners::CodeOwners;
+use crate::plugin::{self, find_plugin};
+use crate::provider::{FileProvider, FsProvider};
+use crate::selection::Eliminated;
+use crate::source_map::SourceMap;
+use crate::tags::Tagger;
+use crate::throttle::Throttled;
+use crate::timings::{Phase, Timings};
+use crate::units::Units;
+use crate::{AppError, Options};
+use crate::{
+    chunk, cloc, collisions, compare, deps, docs, export, front_matter, images, includes, infra,
+    license, limit, line_count, logs, manifests, mime, mirror, mmap, pack, permissions, pool,
+    provenance, repo_summary, sample, selection, similarity, sources, stream, subset, test_code,
+    workspace,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Number of leading bytes of large files searched for a license.
+const LICENSE_HEAD_BYTES: u64 = 16 * 1024;
+
+/// Concatenates files into a single prompt, as configured by [`Options`].
+///
+/// ```no_run
+/// use clap::Parser;
+/// use files_ingest::{Ingestor, Options};
+///
+/// let options = Options::parse_from(["files-ingest", "--markdown", "src"]);
+/// let prompt = Ingestor::new(options).ingest()?;
+/// # Ok::<(), files_ingest::AppError>(())
+/// ```
+pub struct Ingestor {
+    options: Options,
+    provider: Box<dyn FileProvider>,
+    /// Filters added with [`Ingestor::with_filter`].
+    filters: Vec<Box<dyn ContentFilter>>,
+    /// The command line recorded by --embed-command, if not this process's.
+    command_line: Option<Vec<String>>,
+    /// Set to stop the run early, as set with [`Ingestor::with_interrupt`].
+    interrupt: Option<Arc<AtomicBool>>,
+}
+
+/// One file of the output, as handed to [`Ingestor::ingest_with`] visitors.
+///
+/// Documents are the same whatever the output format; only `text` depends on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Document {
+    /// Position in the output, starting at 1 (the Claude XML document index).
+    pub index: usize,
+    /// The path as shown in the output.
+    pub path: PathBuf,
+    /// The language guessed from the file extension (the Markdown fence tag).
+    pub language: Option<String>,
+    /// The file's content after content transforms (such as `--csv-sample`), but
+    /// without formatting (line numbers, escaping, highlighting).
+    pub content: String,
+    /// Further facts about the file, such as its size (`bytes`), line count (`lines`),
+    /// SPDX license (`license`) and tags from the config file (`tags`), if any.
+    pub metadata: BTreeMap<String, serde_json::Value>,
+    /// The document as it appears in the output, in the selected format.
+    pub text: String,
+}
+
+/// What happened during an ingestion run, beyond the output itself.
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Number of documents written.
+    pub documents: usize,
+    /// Whether `--max-output-bytes` cut the output short.
+    pub truncated: bool,
+    /// Whether the run was interrupted (see [`Ingestor::with_interrupt`]), leaving
+    /// out the remaining files.
+    pub interrupted: bool,
+    /// `MinHash` signatures of the files written, with `--similar-report`.
+    signatures: Vec<(PathBuf, similarity::Signature)>,
+    /// Where the time went, for --timings.
+    timings: Timings,
+}
+
+impl Summary {
+    /// Writes the near-duplicate report requested with `--similar-report`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if writing to `writer` fails.
+    pub fn write_similar_report(&self, writer: &mut dyn Write, threshold: f64) -> io::Result<()> {
+        similarity::write_report(writer, &self.signatures, threshold)
+    }
+
+    /// Writes the report requested with `--timings`: the time spent in each
+    /// phase of the run, and on the slowest files.
+    ///
+    /// # Errors
+    ///
+    /// Fails if writing to `writer` fails.
+    pub fn write_timings(&self, writer: &mut dyn Write) -> io::Result<()> {
+        self.timings.write(writer)
+    }
+}
+
+impl Ingestor {
+    /// Creates an ingestor for files on the local filesystem.
+    #[must_use]
+    pub fn new(options: Options) -> Self {
+        let provider = FsProvider::new(options.mmap_threshold);
+        match options.throttle_mbps.filter(|&rate| rate > 0.0) {
+            Some(rate) => Self::with_provider(options, Throttled::new(provider, rate)),
+            None => Self::with_provider(options, provider),
+        }
+    }
+
+    /// Creates an ingestor reading files from `provider`, such as a [`MemoryFiles`] tree.
+    ///
+    /// [`MemoryFiles`]: crate::MemoryFiles
+    #[must_use]
+    pub fn with_provider(options: Options, provider: impl FileProvider + 'static) -> Self {
+        Self {
+            options,
+            provider: Box::new(provider),
+            filters: Vec::new(),
+            command_line: None,
+            interrupt: None,
+        }
+    }
+
+    /// Sets the command line recorded by `--embed-command` (by default, the arguments
+    /// of the current process), for options that didn't come from it.
+    #[must_use]
+    pub fn with_command_line(mut self, args: Vec<String>) -> Self {
+        self.command_line = Some(args);
+        self
+    }
+
+    /// Stops the run once `interrupt` is set (as by a signal handler): the file
+    /// being written is finished, and the output ends properly with the
+    /// truncation marker, without the remaining files. [`Summary::interrupted`]
+    /// then tells the output is partial.
+    #[must_use]
+    pub fn with_interrupt(mut self, interrupt: Arc<AtomicBool>) -> Self {
+        self.interrupt = Some(interrupt);
+        self
+    }
+
+    /// Adds `filter` to the end of the filter chain, after the filters selected by the options.
+    #[must_use]
+    pub fn with_filter(mut self, filter: impl ContentFilter + 'static) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    #[must_use]
+    pub fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Runs the ingestion and returns the whole output as a string.
+    ///
+    /// # Errors
+    ///
+    /// See [`Ingestor::ingest_to`].
+    pub fn ingest(&self) -> Result<String, AppError> {
+        let mut output = Vec::new();
+        self.ingest_to(&mut output)?;
+        // Every part of the output is built from UTF-8 content
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    /// Runs the ingestion, writing the output to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Fails if an input path doesn't exist, if writing fails, if the output would
+    /// exceed `--max-output-bytes` in abort mode, or on a walk error with
+    /// `--on-walk-error fail`, if the `--format-plugin` fails, or if `--validate-xml`
+    /// finds malformed XML. Files that can't be
+    /// read are skipped with a warning.
+    pub fn ingest_to(&self, writer: &mut dyn Write) -> Result<Summary, AppError> {
+        if self.options.validate_xml {
+            // Nothing reaches the writer until the whole output is known to be well-formed
+            let mut output = Vec::new();
+            let summary = self.run(&self.options, &mut output, None)?;
+            let text = String::from_utf8_lossy(&output);
+            if let Err(e) = roxmltree::Document::parse(&text) {
+                return Err(AppError::InvalidXml(e.to_string()));
+            }
+            writer.write_all(&output)?;
+            writer.flush()?;
+            return Ok(summary);
+        }
+        if let [old, new] = self.options.compare_dirs.as_slice() {
+            return compare::write(self, old, new, writer);
+        }
+        if let Some(format) = self.options.format {
+            return export::write(self, format, writer);
+        }
+        if let Some(dir) = &self.options.output_dir {
+            if !self.options.mirror {
+                return Err(
+                    io::Error::other("--output-dir needs --mirror or --format obsidian").into(),
+                );
+            }
+            return mirror::write(self, dir);
+        }
+        let Some(format_plugin) = &self.options.format_plugin else {
+            return self.run(&self.options, writer, None);
+        };
+        // The plugin formats the whole output, from all the documents at once
+        if find_plugin(format_plugin).is_none() {
+            return Err(plugin::not_found(format_plugin));
+        }
+        let mut documents = Vec::new();
+        let summary = self.ingest_with(|document| {
+            documents.push(document);
+            Ok(())
+        })?;
+        plugin::write_format(format_plugin, &documents, writer)?;
+        writer.flush()?;
+        Ok(summary)
+    }
+
+    /// Runs the ingestion, writing the number of files and of blank, comment and
+    /// code lines of each language to `writer` instead of the output.
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`Ingestor::ingest_to`].
+    pub fn write_language_stats(&self, writer: &mut dyn Write) -> Result<Summary, AppError> {
+        cloc::write(self, writer)
+    }
+
+    /// Runs the ingestion, handing each document to `visitor` instead of writing the output.
+    ///
+    /// This lets consumers send documents elsewhere (a database, a socket, ...)
+    /// without materializing the whole output. Large files that are otherwise
+    /// streamed are held in memory one at a time.
+    ///
+    /// ```no_run
+    /// use files_ingest::{Ingestor, Options};
+    ///
+    /// let mut options = Options::default();
+    /// options.paths = vec!["src".into()];
+    /// Ingestor::new(options).ingest_with(|document| {
+    ///     println!("{}: {} bytes", document.path.display(), document.content.len());
+    ///     Ok(())
+    /// })?;
+    /// # Ok::<(), files_ingest::AppError>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Fails like [`Ingestor::ingest_to`], or with the first error returned by
+    /// `visitor`, which stops the run.
+    pub fn ingest_with(
+        &self,
+        mut visitor: impl FnMut(Document) -> io::Result<()>,
+    ) -> Result<Summary, AppError> {
+        self.run(&self.options, &mut io::sink(), Some(&mut visitor))
+    }
+
+    /// Runs the ingestion of `paths` instead of the input paths, handing each
+    /// document to `visitor`. The documents have their real paths: --alias
+    /// names are left out.
+    pub(crate) fn ingest_paths_with(
+        &self,
+        paths: Vec<PathBuf>,
+        mut visitor: impl FnMut(Document) -> io::Result<()>,
+    ) -> Result<Summary, AppError> {
+        let options = Options {
+            paths,
+            aliases: Vec::new(),
+            ..self.options.clone()
+        };
+        self.run(&options, &mut io::sink(), Some(&mut visitor))
+    }
+
+    /// Applies the filters on the files' tags, owners, types, permissions, size
+    /// and role, recording what each eliminated.
+    fn filter_files(
+        &self,
+        files: &mut Vec<PathBuf>,
+        eliminated: &mut Vec<Eliminated>,
+        options: &Options,
+        tagger: &Tagger,
+        sets: &Tagger,
+    ) -> Result<(), AppError> {
+        let provider = &*self.provider;
+        retain(files, eliminated, "--tag and --exclude-tag", |path| {
+            tagger.selects(path, &options.tags, &options.exclude_tags)
+        });
+        retain(files, eliminated, "--use-set and --skip-set", |path| {
+            sets.selects(path, &options.use_sets, &options.skip_sets)
+        });
+        if !options.owners.is_empty() {
+            let code_owners = CodeOwners::load(provider)?;
+            retain(files, eliminated, "--owner", |path| {
+                code_owners.is_owned_by(path, &options.owners)
+            });
+        }
+        if !options.mime_types.is_empty() {
+            retain(files, eliminated, "--mime", |path| {
+                mime::selects(provider, path, options)
+            });
+        }
+        if permissions::is_filtering(options) {
+            retain(
+                files,
+                eliminated,
+                "--executable-only, --writable-only and --mode",
+                |path| permissions::selects(path, options),
+            );
+        }
+        if line_count::is_filtering(options) {
+            retain(files, eliminated, "--min-lines and --max-lines", |path| {
+                line_count::selects(provider, path, options)
+            });
+        }
+        if let Some(max_size) = options.max_size {
+            // Files that can't be read are kept, for the error to be reported when read
+            retain(files, eliminated, "--max-size", |path| {
+                !provider.len(path).is_ok_and(|len| len > max_size)
+            });
+        }
+        if test_code::is_filtering(options) {
+            retain(files, eliminated, "--no-tests and --tests-only", |path| {
+                test_code::selects(provider, path, options)
+            });
+        }
+        if options.docs {
+            retain(files, eliminated, "--docs", |path| {
+                docs::is_doc(path, options)
+            });
+        }
+        if options.config_only {
+            retain(files, eliminated, "--config-only", |path| {
+                infra::is_config(path)
+            });
+        }
+        Ok(())
+    }
+
+    /// Selects the files to process, in output order.
+    ///
+    /// Fails with [`AppError::NoFilesSelected`] if the filters leave none.
+    fn select_files(
+        &self,
+        options: &Options,
+        tagger: &Tagger,
+        sets: &Tagger,
+        threads: usize,
+        timings: &mut Timings,
+    ) -> Result<Vec<PathBuf>, AppError> {
+        let provider = &*self.provider;
+        let started = Instant::now();
+        let mut files = provider.collect_files(options, threads)?;
+        let walk = started.elapsed();
+        let mut tokenize = Duration::ZERO;
+        let mut eliminated = Vec::new();
+        self.filter_files(&mut files, &mut eliminated, options, tagger, sets)?;
+        if options.follow_includes {
+            let before = files.len();
+            includes::follow(&mut files, provider, options);
+            eliminated.push(Eliminated {
+                filters: "--follow-includes",
+                files: before.saturating_sub(files.len()),
+            });
+        }
+        if options.with_tests || options.with_sources {
+            test_code::add_counterparts(&mut files, provider, options, threads)?;
+        }
+        if let Some(size) = options.sample {
+            let before = files.len();
+            subset::sample(&mut files, size, options.seed);
+            eliminated.push(Eliminated {
+                filters: "--sample",
+                files: before - files.len(),
+            });
+        }
+        let mut ranking = Ranking::new(options)?;
+        ranking.sort(&mut files);
+        if let Some((strategy, max_tokens)) = options.pack.zip(options.max_tokens) {
+            let packing = Instant::now();
+            if let Some(kind) = options.graph {
+                ranking.count_references(kind, &files, provider);
+            }
+            let before = files.len();
+            files = pack::select(files, provider, &ranking, strategy, max_tokens);
+            eliminated.push(Eliminated {
+                filters: "--max-tokens packing",
+                files: before - files.len(),
+            });
+            tokenize = packing.elapsed();
+        }
+        if options.label_roots {
+            files.sort_by_key(|path| root_index(path, options)); // Stable: keeps the order within roots
+        }
+        if options.manifests {
+            // Manifests lead the output, whatever the extension filter says
+            let mut unfiltered = options.clone();
+            unfiltered.extensions.clear();
+            let mut manifests = provider.collect_files(&unfiltered, threads)?;
+            manifests.retain(|path| manifests::is_manifest(path));
+            files.retain(|path| !manifests.contains(path));
+            files.splice(0..0, manifests);
+        }
+        if files.is_empty() {
+            let diagnostic = selection::explain(provider, options, threads, &eliminated)?;
+            return Err(AppError::NoFilesSelected(diagnostic));
+        }
+        timings.add(Phase::Walk, walk);
+        timings.add(Phase::Tokenize, tokenize);
+        timings.add(
+            Phase::Select,
+            started.elapsed().saturating_sub(walk + tokenize),
+        );
+        Ok(files)
+    }
+
+    /// Runs the ingestion, writing the output to `writer` or, with a `visitor`,
+    /// handing each document to it instead (`writer` then only sees what falls
+    /// between documents, such as the truncation marker).
+    fn run(
+        &self,
+        options: &Options,
+        writer: &mut dyn Write,
+        visitor: Option<&mut dyn FnMut(Document) -> io::Result<()>>,
+    ) -> Result<Summary, AppError> {
+        let started = Instant::now();
+        // --workspace-member, --package, --docker, --ssh and package specs (crate:NAME, ...) resolve to input paths
+        let mut options = Cow::Borrowed(options);
+        workspace::resolve(&mut options)?;
+        infra::resolve(&mut options);
+        let _downloads = sources::fetch(&mut options)?; // Removed at the end of the run
+        let provider = &*self.provider;
+
+        // --- Validate input paths ---
+        for path in &options.paths {
+            if !provider.exists(path) {
+                return Err(AppError::PathNotFound(path.clone()));
+            }
+        }
+
+        // --- Select Files ---
+        let threads = pool::thread_count(options.threads);
+        let config = Config::load(options.config.as_deref())?;
+        let tagger = Tagger::new(&config.tags)?;
+        let sets = Tagger::new(&config.sets)?;
+        sets.check_sets(&options)?;
+        let slowest = if options.timings {
+            options.timings_slowest
+        } else {
+            0
+        };
+        let mut timings = Timings::new(threads, slowest);
+        let mut files = self.select_files(&options, &tagger, &sets, threads, &mut timings)?;
+        collisions::disambiguate(&files, &mut options);
+        let options: &Options = &options;
+
+        // --- Process Paths ---
+        let mut run = Run {
+            writer: limit::CountingWriter::new(writer), // Track the output size for --max-output-bytes
+            options,
+            provider,
+            // Shortens the visitor's lifetime to the run's (which may borrow `resolved`)
+            visitor: visitor.map(|visitor| visitor as &mut dyn FnMut(Document) -> io::Result<()>),
+            interrupt: self.interrupt.as_deref(),
+            next_index: 1,
+            open_folders: Vec::new(),
+            open_section: None,
+            seen_content: HashMap::new(),
+            empty_files: Vec::new(),
+            source_map: options.source_map.as_ref().map(|_| SourceMap::default()),
+            licenses: license::Licenses::default(),
+            tagger,
+            summary: Summary {
+                timings,
+                ..Summary::default()
+            },
+        };
+
+        run.write_preamble(&files, self.command_line.as_deref())?;
+
+        // The filter chain: the options' transforms first, then the library user's filters
+        let builtin_filters = builtin_filters(options);
+        let filters: Vec<&dyn ContentFilter> = builtin_filters
+            .iter()
+            .chain(&self.filters)
+            .map(AsRef::as_ref)
+            .collect();
+
+        let writing = Instant::now();
+        if options.repo_summary && !run.write_repo_summary()? {
+            run.summary.truncated = true;
+            files.clear(); // The output limit was reached
+        }
+        run.summary.timings.add(Phase::Write, writing.elapsed());
+
+        run.write_files(&files, threads, &filters)?;
+
+        let writing = Instant::now();
+        if options.deps_summary
+            && !run.summary.truncated
+            && !run.check_interrupt()?
+            && !run.write_deps_summary()?
+        {
+            run.summary.truncated = true;
+        }
+        let mut summary = run.finish()?;
+        summary.timings.add(Phase::Write, writing.elapsed());
+        summary.timings.set_total(started.elapsed());
+        Ok(summary)
+    }
+}
+
+/// The state of a single ingestion run, on the (ordered) writing side.
+struct Run<'a> {
+    writer: limit::CountingWriter<&'a mut dyn Write>,
+    options: &'a Options,
+    provider: &'a dyn FileProvider,
+    /// Receives each document, instead of `writer`.
+    visitor: Option<&'a mut dyn FnMut(Document) -> io::Result<()>>,
+    /// Set to stop the run early.
+    interrupt: Option<&'a AtomicBool>,
+    /// The next Claude XML document index.
+    next_index: usize,
+    /// The `<folder>` elements enclosing the last document, outermost first, with --cxml-group-dirs.
+    open_folders: Vec<PathBuf>,
+    /// The section enclosing the last document, with --label-roots or --manifests.
+    open_section: Option<Section>,
+    /// Content hash -> first path, for --dedupe-content.
+    seen_content: HashMap<[u8; 32], PathBuf>,
+    /// The files left out by --skip-empty, listed at the end.
+    empty_files: Vec<PathBuf>,
+    /// Where the files' lines are in the output, with --source-map.
+    source_map: Option<SourceMap>,
+    licenses: license::Licenses,
+    tagger: Tagger,
+    summary: Summary,
+}
+
+impl Run<'_> {
+    /// Reads and formats the `files` concurrently on `threads` threads, a batch
+    /// at a time, then writes them in order. Batching keeps the number of
+    /// formatted-but-unwritten files bounded.
+    fn write_files(
+        &mut self,
+        files: &[PathBuf],
+        threads: usize,
+        filters: &[&dyn ContentFilter],
+    ) -> Result<(), AppError> {
+        let (options, provider) = (self.options, self.provider);
+        let keep_content = self.visitor.is_some();
+        // Licenses are needed for --license-allow, and for the documents' metadata
+        let need_licenses = keep_content || !options.license_allow.is_empty();
+        for batch in files.chunks(threads * 4) {
+            if self.check_interrupt()? {
+                return Ok(());
+            }
+            let licenses = need_licenses.then_some(&self.licenses);
+            let prepared = pool::map(batch, threads, |path| {
+                let started = Instant::now();
+                let mut read = Duration::ZERO;
+                let prepared = prepare_file(
+                    path,
+                    options,
+                    provider,
+                    filters,
+                    keep_content,
+                    licenses,
+                    &mut read,
+                );
+                (prepared, read, started.elapsed().saturating_sub(read))
+            });
+            for (path, (prepared, read, transform)) in batch.iter().zip(prepared) {
+                if self.check_interrupt()? {
+                    return Ok(());
+                }
+                let writing = Instant::now();
+                let written = self.write_prepared(path, prepared)?;
+                let display_path = output_path(path, options);
+                self.summary
+                    .timings
+                    .add_file(&display_path, read, transform, writing.elapsed());
+                if !written {
+                    self.summary.truncated = true;
+                    return Ok(()); // The output limit was reached
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes what comes before the documents: the opening `<documents>` tag,
+    /// the --embed-command header (recording `command_line`, or this process's)
+    /// and the --cxml-header metadata about `files`.
+    fn write_preamble(
+        &mut self,
+        files: &[PathBuf],
+        command_line: Option<&[String]>,
+    ) -> Result<(), AppError> {
+        let options = self.options;
+        let writing = Instant::now();
+        if options.cxml && self.visitor.is_none() {
+            writeln!(self.writer, "<documents>")?;
+        }
+        if options.embed_command && self.visitor.is_none() {
+            let command_line = match command_line {
+                Some(args) => args.to_vec(),
+                None => env::args().collect(),
+            };
+            provenance::write_header(&mut self.writer, options, &command_line)?;
+        }
+        if options.cxml_header && self.visitor.is_none() {
+            provenance::write_cxml_metadata(&mut self.writer, options, files, self.provider)?;
+        }
+        self.summary.timings.add(Phase::Write, writing.elapsed());
+        Ok(())
+    }
+
+    /// Ends the output after the last document, and writes the --source-map.
+    fn finish(mut self) -> Result<Summary, AppError> {
+        let options = self.options;
+        // Write closing XML tags if needed
+        if options.cxml && self.visitor.is_none() {
+            for _ in self.open_folders.drain(..) {
+                writeln!(self.writer, "</folder>")?;
+            }
+            if let Some(section) = self.open_section {
+                write_section_footer(&mut self.writer, section, options)?;
+            }
+        }
+        if !self.summary.truncated && !self.summary.interrupted && self.visitor.is_none() {
+            self.write_empty_note()?;
+        }
+        if options.cxml && self.visitor.is_none() {
+            writeln!(self.writer, "</documents>")?;
+        }
+
+        // Ensure the buffer is flushed before exiting
+        self.writer.flush()?;
+        if let Some((source_map, path)) = self.source_map.zip(options.source_map.as_ref()) {
+            source_map.write(path)?;
+        }
+
+        Ok(self.summary)
+    }
+
+    /// Writes a prepared file. Returns `Ok(false)` once the output limit was reached.
+    fn write_prepared(&mut self, path: &Path, prepared: PreparedFile) -> Result<bool, AppError> {
+        match prepared {
+            PreparedFile::Formatted {
+                hash: Some(hash), ..
+            }
+            | PreparedFile::Chunked {
+                hash: Some(hash), ..
+            } if self.seen_content.contains_key(&hash) => {
+                let original = self.seen_content[&hash].clone();
+                self.write_duplicate_stub(path, &original)
+            }
+            PreparedFile::Formatted {
+                body,
+                backticks,
+                hash,
+                signature,
+                content,
+                license,
+            } => {
+                let written =
+                    self.write_formatted(path, &body, &backticks, content, license, None)?;
+                if let Some(hash) = hash {
+                    self.seen_content.insert(hash, path.to_path_buf());
+                }
+                if let Some(signature) = signature.filter(|_| written) {
+                    let display_path = output_path(path, self.options).into_owned();
+                    self.summary.signatures.push((display_path, signature));
+                }
+                Ok(written)
+            }
+            PreparedFile::Chunked {
+                chunks,
+                hash,
+                license,
+            } => {
+                if let Some(hash) = hash {
+                    self.seen_content.insert(hash, path.to_path_buf());
+                }
+                for (chunk, body, backticks) in &chunks {
+                    let content = Some(chunk.text.clone());
+                    if !self.write_formatted(
+                        path,
+                        body,
+                        backticks,
+                        content,
+                        license.clone(),
+                        Some(chunk),
+                    )? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            PreparedFile::Large => self.stream_file(path),
+            PreparedFile::Empty => {
+                self.empty_files.push(path.to_path_buf());
+                Ok(true)
+            }
+            PreparedFile::Skipped => Ok(true),
+        }
+    }
+
+    /// Writes a large file in chunks, without holding its content in memory.
+    ///
+    /// Content transforms (truncation, sampling) are not applied to streamed files.
+    fn stream_file(&mut self, path: &Path) -> Result<bool, AppError> {
+        let options = self.options;
+        let stats = self.provider.open(path);
+        let stats = stats.and_then(|mut reader| stream::scan(&mut reader, options.dedupe_content));
+        let stats = match stats {
+            Ok(stats) => stats,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                warn!("Skipping file {} - Not valid UTF-8.", path.display());
+                return Ok(true);
+            }
+            Err(e) => {
+                warn!("Skipping file {} - Error reading: {e}", path.display());
+                return Ok(true);
+            }
+        };
+
+        let license = self.license_of_large_file(path);
+        if let Some(license) = &license
+            && !options.license_allow.is_empty()
+            && !license::is_allowed(license, &options.license_allow)
+        {
+            return Ok(true);
+        }
+
+        if let Some(hash) = stats.sha256 {
+            if let Some(original) = self.seen_content.get(&hash).cloned() {
+                return self.write_duplicate_stub(path, &original);
+            }
+            self.seen_content.insert(hash, path.to_path_buf());
+        }
+
+        let backticks = "`".repeat(stats.longest_backtick_run.max(2) + 1);
+        let body_options = stream::BodyOptions {
+            line_number_width: options
+                .line_numbers
+                .then(|| stats.lines.max(1).to_string().len()),
+            escape_xml: options.cxml,
+            tab_width: options.expand_tabs,
+            wrap: options.wrap,
+        };
+        let header = self.document_header(path, &backticks, None)?;
+        let mut footer = Vec::new();
+        write_file_footer(&mut footer, options, &backticks)?;
+
+        if limit::max_bytes(options).is_some() {
+            // Measure the rendered body with a dry run before committing to it
+            let mut counter = limit::CountingWriter::new(io::sink());
+            stream::write_body(&mut self.provider.open(path)?, &mut counter, body_options)?;
+            let len = header.len() + counter.bytes_written() + footer.len();
+            if !self.check_output_limit(len)? {
+                return Ok(false);
+            }
+        }
+
+        let provider = self.provider;
+        self.write_document(
+            path,
+            &header,
+            &footer,
+            |writer| stream::write_body(&mut provider.open(path)?, writer, body_options),
+            || io::read_to_string(provider.open(path)?),
+            license,
+            None,
+        )?;
+        Ok(true)
+    }
+
+    /// Resolves the license of a large file from its first bytes, if licenses are needed.
+    fn license_of_large_file(&self, path: &Path) -> Option<String> {
+        if self.visitor.is_none() && self.options.license_allow.is_empty() {
+            return None;
+        }
+        let mut head = Vec::new();
+        let _ = (self.provider.open(path))
+            .and_then(|reader| reader.take(LICENSE_HEAD_BYTES).read_to_end(&mut head));
+        let head = String::from_utf8_lossy(&head);
+        self.licenses.of_file(path, Some(&head), self.provider)
+    }
+
+    /// Writes a stub document in place of a file whose content duplicates `original`.
+    fn write_duplicate_stub(&mut self, path: &Path, original: &Path) -> Result<bool, AppError> {
+        let stub = format!(
+            "(same as {})",
+            output_path(original, self.options).display()
+        );
+        let body = if self.options.cxml {
+            escape_xml(&stub)
+        } else {
+            stub.clone()
+        } + "\n";
+        self.write_formatted(path, &body, "```", Some(stub), None, None)
+    }
+
+    /// Writes the one-line note listing the files left out by --skip-empty, if any.
+    fn write_empty_note(&mut self) -> io::Result<()> {
+        if self.empty_files.is_empty() {
+            return Ok(());
+        }
+        let paths = self
+            .empty_files
+            .iter()
+            .map(|path| output_path(path, self.options).display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        if self.options.cxml {
+            writeln!(
+                self.writer,
+                "<empty_files>{}</empty_files>",
+                escape_xml(&paths)
+            )
+        } else {
+            writeln!(self.writer, "Empty files (left out): {paths}")
+        }
+    }
+
+    /// Writes the --repo-summary overview document, unless it would exceed the output limit.
+    fn write_repo_summary(&mut self) -> Result<bool, AppError> {
+        let Some(root) = self.options.paths.first() else {
+            return Ok(true);
+        };
+        let content = repo_summary::summarize(root, &Units::new(self.options));
+        let path = Path::new(repo_summary::PATH);
+        let (body, backticks) = format_body(path, &content, self.options);
+        self.write_formatted(path, &body, &backticks, Some(content), None, None)
+    }
+
+    /// Writes the --deps-summary document, unless it would exceed the output limit.
+    fn write_deps_summary(&mut self) -> Result<bool, AppError> {
+        let Some(root) = self.options.paths.first() else {
+            return Ok(true);
+        };
+        let content = deps::summarize(root);
+        let path = Path::new(deps::PATH);
+        let (body, backticks) = format_body(path, &content, self.options);
+        self.write_formatted(path, &body, &backticks, Some(content), None, None)
+    }
+
+    /// Writes a formatted file (header, body and footer), unless it would exceed the output limit.
+    ///
+    /// `content` is the unformatted content and `license` the file's license, kept
+    /// for visitors, and `chunk` the part of the file written, with --chunk. Returns `Ok(false)` once `--max-output-bytes` stopped the
+    /// output in truncate mode.
+    fn write_formatted(
+        &mut self,
+        path: &Path,
+        body: &str,
+        backticks: &str,
+        content: Option<String>,
+        license: Option<String>,
+        chunk: Option<&Chunk>,
+    ) -> Result<bool, AppError> {
+        // The document index is only claimed once the file is known to fit within the limit
+        let header = self.document_header(path, backticks, chunk)?;
+        let mut footer = Vec::new();
+        write_file_footer(&mut footer, self.options, backticks)?;
+        if !self.check_output_limit(header.len() + body.len() + footer.len())? {
+            return Ok(false);
+        }
+        self.write_document(
+            path,
+            &header,
+            &footer,
+            |writer| writer.write_all(body.as_bytes()),
+            || Ok(content.unwrap_or_default()),
+            license,
+            chunk,
+        )?;
+        Ok(true)
+    }
+
+    /// Renders everything that precedes a document in the output: the file header,
+    /// after the header of its section (--label-roots, --manifests) and the `<folder>`
+    /// tags leading to it with --cxml-group-dirs.
+    fn document_header(
+        &self,
+        path: &Path,
+        backticks: &str,
+        chunk: Option<&Chunk>,
+    ) -> io::Result<Vec<u8>> {
+        let mut header = Vec::new();
+        let section = section_of(path, self.options);
+        let new_section = self.visitor.is_none() && section != self.open_section;
+        let grouped = self.options.cxml_group_dirs && self.visitor.is_none();
+        let folders = if grouped {
+            folders(path, self.options)
+        } else {
+            Vec::new()
+        };
+        // Folders don't span sections
+        let common = if new_section {
+            0
+        } else {
+            self.open_folders
+                .iter()
+                .zip(&folders)
+                .take_while(|(open, folder)| open == folder)
+                .count()
+        };
+        if grouped {
+            for _ in common..self.open_folders.len() {
+                writeln!(header, "</folder>")?;
+            }
+        }
+        if new_section {
+            if let Some(open) = self.open_section {
+                write_section_footer(&mut header, open, self.options)?;
+            }
+            if let Some(section) = section {
+                write_section_header(&mut header, section, self.options)?;
+            }
+        }
+        for folder in &folders[common..] {
+            let folder = escape_xml(&folder.to_string_lossy()).replace('"', "&quot;");
+            writeln!(header, "<folder path=\"{folder}\">")?;
+        }
+        if self.options.front_matter && !self.options.cxml {
+            front_matter::write(&mut header, path, self.provider, self.options)?;
+        }
+        let tags = self.tagger.tags(path);
+        write_file_header(
+            &mut header,
+            path,
+            self.options,
+            backticks,
+            self.next_index,
+            &tags,
+            chunk.map(|chunk| chunk.index),
+        )?;
+        Ok(header)
+    }
+
+    /// Writes a document that fits within the output limit, claiming the next index.
+    ///
+    /// With a visitor, the document is rendered in memory and handed over instead,
+    /// along with its unformatted content from `read_content` and its `license`
+    /// (and where the `chunk` is in the file, with --chunk).
+    #[allow(clippy::too_many_arguments)] // The parts of a document
+    fn write_document(
+        &mut self,
+        path: &Path,
+        header: &[u8],
+        footer: &[u8],
+        write_body: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+        read_content: impl FnOnce() -> io::Result<String>,
+        license: Option<String>,
+        chunk: Option<&Chunk>,
+    ) -> io::Result<()> {
+        let index = self.next_index;
+        self.next_index += 1;
+        self.summary.documents += 1;
+
+        let Some(visitor) = &mut self.visitor else {
+            if self.options.cxml_group_dirs {
+                self.open_folders = folders(path, self.options); // Opened by the header
+            }
+            self.open_section = section_of(path, self.options);
+            self.writer.write_all(header)?;
+            let first_line = self.writer.newlines_written() + 1;
+            write_body(&mut self.writer)?;
+            if let Some(source_map) = &mut self.source_map {
+                source_map.record(
+                    output_path(path, self.options).into_owned(),
+                    first_line,
+                    self.writer.newlines_written() + 1 - first_line,
+                    self.writer.ends_with_blank_line(),
+                );
+            }
+            return self.writer.write_all(footer);
+        };
+        let mut text = header.to_vec();
+        write_body(&mut text)?;
+        text.extend_from_slice(footer);
+        let content = read_content()?;
+        let mut metadata = BTreeMap::from([
+            ("bytes".to_string(), content.len().into()),
+            ("lines".to_string(), content.lines().count().into()),
+        ]);
+        if let Some(license) = license {
+            metadata.insert("license".to_string(), license.into());
+        }
+        let tags = self.tagger.tags(path);
+        if !tags.is_empty() {
+            metadata.insert("tags".to_string(), tags.into());
+        }
+        let mut document_path = output_path(path, self.options).into_owned();
+        if let Some(chunk) = chunk {
+            document_path
+                .as_mut_os_string()
+                .push(format!("#chunk-{}", chunk.index));
+            metadata.insert("chunk".to_string(), chunk.index.into());
+            metadata.insert("start_line".to_string(), chunk.start_line.into());
+            metadata.insert("end_line".to_string(), chunk.end_line.into());
+            if !chunk.headings.is_empty() {
+                metadata.insert("headings".to_string(), chunk.headings.join(" > ").into());
+            }
+        }
+        visitor(Document {
+            index,
+            path: document_path,
+            language: language(path).map(str::to_string),
+            content,
+            metadata,
+            text: String::from_utf8_lossy(&text).into_owned(),
+        })?;
+        self.writer.count(text.len()); // Still counts towards --max-output-bytes
+        Ok(())
+    }
+
+    /// Checks whether `len` more bytes fit within `--max-output-bytes`.
+    ///
+    /// If they don't, either fails (abort mode) or writes the truncation marker and
+    /// returns `Ok(false)` (truncate mode). In truncate mode, room is kept for the
+    /// marker, so that it doesn't take the output over the limit.
+    fn check_output_limit(&mut self, len: usize) -> Result<bool, AppError> {
+        let Some(max_bytes) = limit::max_bytes(self.options) else {
+            return Ok(true);
+        };
+        let mut marker = Vec::new();
+        if self.options.on_overflow == limit::OverflowPolicy::Truncate {
+            let reason = format!("remaining files would exceed {max_bytes} bytes");
+            write_truncation_marker(&mut marker, &reason, self.options)?;
+        }
+        if self.writer.bytes_written() + len + marker.len() <= max_bytes {
+            return Ok(true);
+        }
+        if self.options.on_overflow == limit::OverflowPolicy::Abort {
+            return Err(AppError::OutputLimitExceeded(max_bytes));
+        }
+        if self.writer.bytes_written() + marker.len() <= max_bytes {
+            self.writer.write_all(&marker)?;
+        } else {
+            warn!(
+                "Output truncated at {max_bytes} bytes, with no room left for the truncation marker"
+            );
+        }
+        Ok(false)
+    }
+
+    /// Checks whether the run was interrupted (see [`Ingestor::with_interrupt`]),
+    /// writing the truncation marker the first time it finds so.
+    fn check_interrupt(&mut self) -> Result<bool, AppError> {
+        if self.summary.interrupted {
+            return Ok(true);
+        }
+        if !self
+            .interrupt
+            .is_some_and(|interrupt| interrupt.load(Ordering::Relaxed))
+        {
+            return Ok(false);
+        }
+        self.summary.interrupted = true;
+        if self.visitor.is_none() {
+            write_truncation_marker(&mut self.writer, "interrupted", self.options)?;
+        }
+        Ok(true)
+    }
+}
+
+/// Keeps the `files` that `keep`, recording how many `filters` eliminated.
+fn retain(
+    files: &mut Vec<PathBuf>,
+    eliminated: &mut Vec<Eliminated>,
+    filters: &'static str,
+    keep: impl FnMut(&PathBuf) -> bool,
+) {
+    let before = files.len();
+    files.retain(keep);
+    eliminated.push(Eliminated {
+        filters,
+        files: before - files.len(),
+    });
+}
+
+/// A file read and formatted in memory, ready to be written in output order.
+enum PreparedFile {
+    /// The formatted content, plus the Markdown fence that fits around it, the
+    /// hash of the original content (with --dedupe-content), its `MinHash`
+    /// signature (with --similar-report), and the unformatted content and license
+    /// (for visitors).
+    Formatted {
+        body: String,
+        backticks: String,
+        hash: Option<[u8; 32]>,
+        signature: Option<similarity::Signature>,
+        content: Option<String>,
+        license: Option<String>,
+    },
+    /// Split into chunks by --chunk, each formatted on its own, plus the hash of
+    /// the original content (with --dedupe-content) and the license.
+    Chunked {
+        chunks: Vec<(Chunk, String, String)>,
+        hash: Option<[u8; 32]>,
+        license: Option<String>,
+    },
+    /// Too large to hold in memory: streamed when its turn comes.
+    Large,
+    /// Empty or only whitespace, with --skip-empty: listed in a note instead.
+    Empty,
+    /// Could not be read (a warning was printed), or dropped by a filter or `--license-allow`.
+    Skipped,
+}
+
+/// The filters selected by the options, in the order they run: the built-in
+/// transforms, then those given with --filter.
+fn builtin_filters(options: &Options) -> Vec<Box<dyn ContentFilter>> {
+    (options.normalize.then_some(FilterSpec::Normalize))
+        .into_iter()
+        .chain(options.nfc_content.then_some(FilterSpec::Nfc))
+        .chain(options.strip_ansi.then_some(FilterSpec::StripAnsi))
+        .chain(
+            options
+                .structured_max_depth
+                .map(FilterSpec::StructuredMaxDepth),
+        )
+        .chain(options.csv_sample.map(FilterSpec::CsvSample))
+        .chain(
+            options
+                .logs_mode
+                .then_some(FilterSpec::Logs(options.log_lines)),
+        )
+        .chain(options.sample_large.map(FilterSpec::SampleLarge))
+        .chain(options.filters.iter().cloned())
+        .map(|spec| spec.build())
+        .collect()
+}
+
+/// Reads a single file, runs it through the filter chain and formats its content.
+///
+/// The transformed but unformatted content is kept too if `keep_content` is set,
+/// and the file's license is resolved with `licenses` if given (skipping files
+/// that `--license-allow` excludes). The time spent reading is stored in
+/// `read_time`, for --timings.
+///
+/// Runs on the worker pool; everything that depends on the output order (such
+/// as the Claude XML document index) is left to [`Run::write_formatted`]. Files
+/// that cannot be read (or are not valid UTF-8) are skipped with a warning.
+fn prepare_file(
+    path: &Path,
+    options: &Options,
+    provider: &dyn FileProvider,
+    filters: &[&dyn ContentFilter],
+    keep_content: bool,
+    licenses: Option<&license::Licenses>,
+    read_time: &mut Duration,
+) -> PreparedFile {
+    let image = options.images.zip(images::image_mime_type(path));
+    let is_large = image.is_none()
+        && provider
+            .len(path)
+            .is_ok_and(|len| len > options.stream_threshold);
+    let is_log = options.logs_mode && logs::is_log(path);
+    // The line number gutter is laid out from the whole file, so it is read in memory
+    let gutter = options.line_numbers && options.line_numbers_style == LineNumberStyle::Gutter;
+    if is_large && !is_log && !gutter && options.sample_large.is_none() {
+        return PreparedFile::Large;
+    }
+
+    let reading = Instant::now();
+    let read_result = match (image, options.sample_large) {
+        (Some((mode, mime)), _) => provider
+            .read(path)
+            .map(|bytes| images::render_image(path, &bytes, mime, mode))
+            .map(mmap::FileContent::Owned),
+        // Only the compacted log or the sample of a large file is held in memory
+        (None, _) if is_large && is_log => provider
+            .open(path)
+            .and_then(|reader| logs::compact_reader(reader, options.log_lines))
+            .map(mmap::FileContent::Owned),
+        (None, Some(spec)) if is_large => provider
+            .open(path)
+            .and_then(|reader| sample::sample_reader(reader, spec))
+            .map(mmap::FileContent::Owned),
+        (None, _) => provider.read_text(path),
+    };
+    *read_time = reading.elapsed();
+    match read_result {
+        Ok(content) if options.skip_empty && content.trim().is_empty() => PreparedFile::Empty,
+        Ok(content) => {
+            // Successfully read the file content as UTF-8
+            let license =
+                licenses.and_then(|licenses| licenses.of_file(path, Some(&content), provider));
+            if let Some(license) = &license
+                && !options.license_allow.is_empty()
+                && !license::is_allowed(license, &options.license_allow)
+            {
+                return PreparedFile::Skipped;
+            }
+            let hash = options
+                .dedupe_content
+                .then(|| Sha256::digest(content.as_bytes()).into());
+            let signature = options
+                .similar_report
+                .then(|| similarity::signature(&content));
+            let Some(content) = filter_content(path, &content, filters) else {
+                return PreparedFile::Skipped;
+            };
+            if let Some(size) = chunk::size(options) {
+                let chunks = chunk::split(path, &content, size, options.chunk_overlap)
+                    .into_iter()
+                    .map(|chunk| {
+                        let (body, backticks) = format_body(path, &chunk.text, options);
+                        (chunk, body, backticks)
+                    })
+                    .collect();
+                return PreparedFile::Chunked {
+                    chunks,
+                    hash,
+                    license,
+                };
+            }
+            let (body, backticks) = format_body(path, &content, options);
+            PreparedFile::Formatted {
+                body,
+                backticks,
+                hash,
+                signature,
+                content: keep_content.then(|| content.into_owned()),
+                license,
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            // Handle non-UTF-8 files gracefully
+            warn!("Skipping file {} - Not valid UTF-8.", path.display());
+            PreparedFile::Skipped
+        }
+        Err(e) => {
+            // Handle other file reading errors
+            warn!("Skipping file {} - Error reading: {e}", path.display());
+            PreparedFile::Skipped
+        }
+    }
+}
+
+/// The directories leading to a file as shown in the output, outermost first.
+fn folders(path: &Path, options: &Options) -> Vec<PathBuf> {
+    let mut folders: Vec<_> = header_path(path, options)
+        .ancestors()
+        .skip(1)
+        .filter(|folder| !folder.as_os_str().is_empty() && folder.parent().is_some())
+        .map(Path::to_path_buf)
+        .collect();
+    folders.reverse();
+    folders
+}
+
+/// Runs the filter chain over a file's content.
+///
+/// Returns `None` if a filter dropped the file.
+fn filter_content<'a>(
+    path: &Path,
+    content: &'a str,
+    filters: &[&dyn ContentFilter],
+) -> Option<Cow<'a, str>> {
+    if filters.is_empty() {
+        return Some(Cow::Borrowed(content));
+    }
+    let mut doc = Document {
+        index: 0,
+        path: display_path(path).to_path_buf(),
+        language: language(path).map(str::to_string),
+        content: content.to_string(),
+        metadata: BTreeMap::new(),
+        text: String::new(),
+    };
+    for filter in filters {
+        if filter.apply(&mut doc) == FilterDecision::Skip {
+            return None;
+        }
+    }
+    Some(Cow::Owned(doc.content))
+}
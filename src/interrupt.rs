@@ -0,0 +1,38 @@
+//! Ctrl-C (or SIGTERM) during a run: the run stops after the file being
+//! written, and its partial output is either ended properly or removed
+//! (`--on-interrupt`).
+
+use clap::ValueEnum;
+use signal_hook::consts::TERM_SIGNALS;
+use signal_hook::flag;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use tracing::warn;
+
+/// What becomes of the output of an interrupted run (`--on-interrupt`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum InterruptPolicy {
+    /// End the output after the files written so far, with a truncation marker
+    /// (and closing tags), so that it stays well-formed.
+    #[default]
+    Finalize,
+    /// Don't write the --output file, and don't complete the --post request or the
+    /// S3 upload.
+    Delete,
+}
+
+/// Returns the flag set by the first termination signal. A second one ends the
+/// process at once, with `exit_status`.
+pub fn install(exit_status: i32) -> Arc<AtomicBool> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    for &signal in TERM_SIGNALS {
+        // The shutdown is registered first, so that it only sees the flag set by an earlier signal
+        let registered =
+            flag::register_conditional_shutdown(signal, exit_status, Arc::clone(&interrupted))
+                .and_then(|_| flag::register(signal, Arc::clone(&interrupted)));
+        if let Err(e) = registered {
+            warn!("Could not handle signal {signal} (--on-interrupt): {e}");
+        }
+    }
+    interrupted
+}
@@ -0,0 +1,629 @@
+//! Concatenate a directory full of files into a single prompt for use with LLMs.
+//!
+//! This is the library behind the `files-ingest` command-line tool. [`Options`]
+//! mirrors the command-line options and [`Ingestor`] runs the ingestion:
+//!
+//! ```no_run
+//! use files_ingest::{Ingestor, Options};
+//!
+//! let mut options = Options::default();
+//! options.paths = vec!["src".into()];
+//! options.cxml = true;
+//! let prompt = Ingestor::new(options).ingest()?;
+//! # Ok::<(), files_ingest::AppError>(())
+//! ```
+
+use clap::Parser;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use thiserror::Error; // For custom error types
+
+mod ansi; // ANSI escape sequence removal
+mod chunk; // Overlapping chunks of files
+mod cloc; // Line counts per language
+mod collisions; // Distinct output paths for colliding files
+mod compare; // The --compare-dirs comparison of two trees
+mod config; // The configuration file
+mod csv_sample; // CSV/TSV row sampling
+mod deps; // The --deps-summary dependency summary
+mod docs; // Selection of documentation files
+#[cfg(feature = "embed")]
+mod embed; // Embeddings for --format vectors
+mod export; // Document-oriented output formats (--format)
+#[cfg(feature = "ffi")]
+pub mod ffi; // C-compatible interface
+mod filter; // Content filter chain
+mod format; // Output formats
+mod front_matter; // YAML front matter of Markdown documents
+mod git; // Git metadata of the source tree
+mod graph; // Cross-file reference graphs
+mod highlight; // ANSI syntax highlighting for terminal previews
+mod images; // Image files as base64 data URIs or references
+mod includes; // Following C/C++ includes
+mod infra; // Selection of infrastructure and configuration files
+mod ingest; // The ingestion pipeline
+mod license; // Per-file license detection
+mod limit; // Output size limits
+mod line_count; // Selection by line count
+mod logs; // Log-file compaction
+mod manifests; // Project manifests
+mod mime; // Selection by sniffed MIME type
+mod mirror; // The --mirror sanitized copy of the tree
+mod mmap; // Memory-mapped reads of big files
+#[cfg(feature = "async")]
+mod nonblocking; // Async API
+mod obsidian; // The Obsidian vault export
+mod order; // Output order of the files
+mod owners; // Selection by CODEOWNERS
+mod pack; // Budget packing
+mod permissions; // Selection by filesystem permissions
+mod plugin; // External plugins (files-ingest-* executables)
+mod pool; // Worker pool for reading/formatting files concurrently
+mod provenance; // The --embed-command run header
+mod provider; // File sources: filesystem or in-memory tree
+mod repo_summary; // The --repo-summary overview document
+mod sample; // Head/tail sampling of large files
+mod scratch; // Private temporary directories
+mod selection; // Diagnostic for an empty selection
+mod similarity; // Near-duplicate detection
+mod source_map; // The --source-map sidecar
+mod sources; // Published package sources (crate:, npm:, pypi:) and Docker images as input paths
+mod stream; // Chunked processing of large files
+mod structured; // JSON/YAML depth truncation
+mod subset; // Random sampling of the selected files
+mod symbols; // The --format symbols index
+mod tabs; // Tab expansion
+mod tags; // Per-file tags from path rules
+mod template; // Variables in --system and --prompt
+mod test_code; // Recognizing test code
+mod throttle; // Read throughput limit
+mod timings; // The --timings report
+mod tokens; // Token count estimates
+mod units; // Humanized sizes and counts
+mod walk; // Directory walking and file selection
+mod workspace; // Workspace members as input paths
+mod wrap; // Soft-wrapping of long lines
+
+pub use config::{Config, DEFAULT_CONFIG_FILE, Snapshot};
+pub use export::OutputFormat;
+pub use filter::{ContentFilter, FilterDecision, FilterSpec};
+pub use format::{Alias, LineNumberStyle};
+pub use graph::GraphKind;
+pub use images::ImageMode;
+pub use ingest::{Document, Ingestor, Summary};
+pub use limit::OverflowPolicy;
+pub use mmap::FileContent;
+pub use order::FileOrder;
+pub use pack::PackStrategy;
+pub use permissions::ModeSpec;
+pub use plugin::{PluginFilter, find_plugin, list_plugins};
+pub use provider::{FileProvider, FsProvider, MemoryFiles};
+pub use sample::SampleSpec;
+pub use subset::SampleSize;
+pub use walk::WalkErrorPolicy;
+
+// --- Error Handling ---
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("IO Error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Ignore Error: {0}")] // Errors during the walk, with --on-walk-error fail
+    Ignore(#[from] ignore::Error),
+    #[error("Path does not exist: {0}")]
+    PathNotFound(PathBuf),
+    #[error("Output exceeds the limit of {0} bytes set by --max-output-bytes or --max-tokens")]
+    OutputLimitExceeded(usize),
+    #[error("Plugin Error: {0}")]
+    Plugin(String),
+    #[error("Config Error: {0}")]
+    Config(String),
+    #[error("Output is not well-formed XML: {0}")] // Found by --validate-xml
+    InvalidXml(String),
+    #[error("{0}")] // The diagnostic explains which filters eliminated the files
+    NoFilesSelected(String),
+    #[error("Workspace Error: {0}")] // Resolving --workspace-member or --package
+    Workspace(String),
+    #[error("Download Error: {0}")] // Fetching package specs, --docker, --ssh or s3:// inputs
+    Download(String),
+    // Add a specific error variant if needed for invalid ignore patterns,
+    // though 'ignore' crate often reports these during the walk.
+    // #[error("Invalid ignore pattern: {0}")]
+    // InvalidIgnorePattern(String),
+}
+
+// --- Options ---
+
+/// Options controlling which files are included and how they are formatted.
+///
+/// These are the command-line options of `files-ingest` that don't concern the
+/// output destination; build them with [`Options::parse_from`] or [`Options::default`].
+#[derive(Parser, Debug, Clone)]
+#[command(name = "files-ingest")]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Options {
+    /// Paths to files or directories to process, or package specs such as
+    /// `crate:serde@1.0.200`, `npm:lodash@4.17.21` or `pypi:requests==2.32.0`, or
+    /// `s3://bucket/prefix` (`s3` feature).
+    /// Reads from stdin if empty.
+    #[arg(name = "PATHS")]
+    pub paths: Vec<PathBuf>,
+
+    /// Process the sources of this Cargo workspace member and of the local crates it
+    /// depends on (path dependencies), in addition to PATHS (can be used multiple times).
+    #[arg(long = "workspace-member", value_name = "NAME")]
+    pub workspace_members: Vec<String>,
+
+    /// Process this package of the pnpm, yarn or npm workspace enclosing the current
+    /// directory, in addition to PATHS (can be used multiple times).
+    #[arg(long = "package", value_name = "NAME")]
+    pub packages: Vec<String>,
+
+    /// With --package, also process the workspace packages it depends on.
+    #[arg(long, requires = "packages")]
+    pub package_deps: bool,
+
+    /// Process the files of this Docker image (under PATH, if given, as in
+    /// `nginx:1.25:/etc/nginx`), copied out with the docker CLI (can be used multiple times).
+    #[arg(long, value_name = "IMAGE[:PATH]")]
+    pub docker: Vec<String>,
+
+    /// Process the files of this remote directory, copied over SSH (can be used
+    /// multiple times). The hidden-file and extension filters apply remotely.
+    #[arg(long, value_name = "[USER@]HOST:PATH")]
+    pub ssh: Vec<String>,
+
+    /// Only include files with the specified extension (can be used multiple times).
+    #[arg(short, long = "extension", value_name = "EXT")]
+    pub extensions: Vec<String>,
+
+    /// Only include files of this MIME type, sniffed from their content rather than
+    /// guessed from their extension, as in `text/*` or `image/png` (can be used multiple times).
+    #[arg(long = "mime", value_name = "TYPE")]
+    pub mime_types: Vec<String>,
+
+    /// Include hidden files and directories (starting with '.').
+    #[arg(long)]
+    pub include_hidden: bool,
+
+    /// Include the hidden files and directories matching this pattern (gitignore syntax,
+    /// e.g. `.env.example` or `.github/workflows/*`), but no other (can be used multiple times).
+    #[arg(long = "include-hidden-pattern", value_name = "GLOB")]
+    pub include_hidden_patterns: Vec<String>,
+
+    /// Specify patterns to ignore (files or directories, uses gitignore syntax). Can be used multiple times.
+    #[arg(long = "ignore", value_name = "PATTERN")]
+    pub ignore_patterns: Vec<String>,
+
+    /// Ignore the files matching the patterns of this gitignore-style file (can be used multiple times).
+    #[arg(long = "ignore-file", value_name = "PATH")]
+    pub ignore_files: Vec<PathBuf>,
+
+    /// When set, --ignore patterns only match files, not directories.
+    #[arg(long)]
+    pub ignore_files_only: bool,
+
+    /// Ignore .gitignore files and include all files found.
+    #[arg(long)]
+    pub ignore_gitignore: bool,
+
+    /// Apply the hidden-file, ignore and extension filters to files passed explicitly
+    /// on the command line too (by default they are always included).
+    #[arg(long)]
+    pub no_force_explicit: bool,
+
+    /// Read tag rules and other settings from this TOML file (the binary defaults to .files-ingest.toml in the current directory, if present).
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Only include files carrying one of these tags, as defined in the config file (can be used multiple times).
+    #[arg(long = "tag", value_name = "TAG")]
+    pub tags: Vec<String>,
+
+    /// Exclude files carrying this tag, as defined in the config file (can be used multiple times).
+    #[arg(long = "exclude-tag", value_name = "TAG")]
+    pub exclude_tags: Vec<String>,
+
+    /// Only include files matching one of the patterns of this set, as defined
+    /// in the [sets] of the config file (can be used multiple times).
+    #[arg(long = "use-set", value_name = "NAME")]
+    pub use_sets: Vec<String>,
+
+    /// Exclude files matching one of the patterns of this set, as defined in the
+    /// [sets] of the config file (can be used multiple times).
+    #[arg(long = "skip-set", value_name = "NAME")]
+    pub skip_sets: Vec<String>,
+
+    /// Only include files owned by this team or user (e.g. @org/backend) according to the CODEOWNERS file (can be used multiple times).
+    #[arg(long = "owner", value_name = "OWNER")]
+    pub owners: Vec<String>,
+
+    /// Only include executable files (by anyone; on Windows, by extension).
+    #[arg(long)]
+    pub executable_only: bool,
+
+    /// Only include files that are not read-only.
+    #[arg(long)]
+    pub writable_only: bool,
+
+    /// Only include files with these permissions, in chmod syntax: `u+x` (owner
+    /// can execute), `go-w` (neither group nor others can write), `a+r,o-x`...
+    #[arg(long, value_name = "MODE")]
+    pub mode: Option<ModeSpec>,
+
+    /// Only include files with at least N lines.
+    #[arg(long, value_name = "N")]
+    pub min_lines: Option<usize>,
+
+    /// Only include files with at most N lines (longer ones are left out, not truncated).
+    #[arg(long, value_name = "N")]
+    pub max_lines: Option<usize>,
+
+    /// Only include files of at most this many bytes (larger ones are left out).
+    #[arg(long, value_name = "BYTES")]
+    pub max_size: Option<u64>,
+
+    /// Leave out empty and whitespace-only files, listing them in a one-line note
+    /// at the end instead.
+    #[arg(long)]
+    pub skip_empty: bool,
+
+    /// Leave out test code: files under test, tests, __tests__ or spec directories,
+    /// named like tests (`*_test.go`, `test_*.py`, `*.spec.ts`...) or Rust
+    /// `#![cfg(test)]` modules.
+    #[arg(long, conflicts_with = "tests_only")]
+    pub no_tests: bool,
+
+    /// Only include test code, as recognized by --no-tests.
+    #[arg(long)]
+    pub tests_only: bool,
+
+    /// Also include the tests of the selected source files, found by naming
+    /// convention (`test_parser.py`, `parser_test.go`, `ParserTest.java`, ...).
+    #[arg(long)]
+    pub with_tests: bool,
+
+    /// Also include the sources tested by the selected test files.
+    #[arg(long)]
+    pub with_sources: bool,
+
+    /// Only include documentation: Markdown, reStructuredText, Asciidoc and text
+    /// files, READMEs, and anything under a docs directory.
+    #[arg(long)]
+    pub docs: bool,
+
+    /// Only include infrastructure and configuration: Dockerfiles, CI pipelines,
+    /// Terraform, Kubernetes and other YAML, and dotfile configs (let in even
+    /// though hidden).
+    #[arg(long, conflicts_with = "docs")]
+    pub config_only: bool,
+
+    /// Of the C/C++ headers, only keep those the other files include, directly
+    /// or through other headers (`#include "..."`, looked up next to the
+    /// including file, then under the input directories and their `include`).
+    #[arg(long)]
+    pub follow_includes: bool,
+
+    /// Only include a random sample of the matching files: N files, or N% of them.
+    #[arg(long, value_name = "N[%]")]
+    pub sample: Option<SampleSize>,
+
+    /// Seed for --sample, to pick the same files again (random if not given).
+    #[arg(long, value_name = "S", requires = "sample")]
+    pub seed: Option<u64>,
+
+    /// Order of the files in the output: by path, by git activity (most committed first), or entry points first.
+    #[arg(long, value_name = "ORDER", value_enum, default_value_t)]
+    pub order: order::FileOrder,
+
+    /// How far back --order churn counts commits, in any format git accepts.
+    #[arg(long, value_name = "DATE", default_value = "90 days ago")]
+    pub churn_since: String,
+
+    /// Treat files matching this pattern (gitignore syntax) as project entry points, instead of the usual names like main.rs, app.py or index.ts (can be used multiple times).
+    #[arg(long = "entry-glob", value_name = "GLOB")]
+    pub entry_globs: Vec<String>,
+
+    /// Output in Claude XML format.
+    #[arg(short = 'c', long = "cxml")]
+    pub cxml: bool,
+
+    /// With --cxml, nest documents in <folder path="..."> elements mirroring the directory structure.
+    #[arg(long, requires = "cxml")]
+    pub cxml_group_dirs: bool,
+
+    /// With --cxml, start the output with a <metadata> element holding the time,
+    /// the version and a summary of the selection (inputs, files, size, languages).
+    #[arg(long, requires = "cxml")]
+    pub cxml_header: bool,
+
+    /// Group the files by input path, each group under a section header naming it,
+    /// and show file paths relative to their input path.
+    #[arg(long)]
+    pub label_roots: bool,
+
+    /// Compare two trees instead of ingesting the input paths: pair their files by
+    /// relative path and output the old and new versions of the changed ones,
+    /// after an overview listing the changed, added and removed files.
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        conflicts_with_all = ["PATHS", "format", "format_plugin", "output_dir", "validate_xml"]
+    )]
+    pub compare_dirs: Vec<PathBuf>,
+
+    /// With --compare-dirs, output the unified diff of each changed file instead
+    /// of both versions.
+    #[arg(long, requires = "compare_dirs")]
+    pub compare_diff: bool,
+
+    /// Start the output with a header recording the command line, the version,
+    /// the time and the git commit of the input, to reproduce the bundle later.
+    #[arg(long)]
+    pub embed_command: bool,
+
+    /// Start with an overview document of the repository: git remote, branch and
+    /// commit, build system and top-level entries with their sizes.
+    #[arg(long)]
+    pub repo_summary: bool,
+
+    /// Put the project manifests (Cargo.toml, package.json, go.mod, pyproject.toml,
+    /// pom.xml) in a leading section of their own, even if --extension leaves them out.
+    #[arg(long)]
+    pub manifests: bool,
+
+    /// End with a document summarizing the dependencies, from `cargo metadata`,
+    /// `npm ls` and go.mod.
+    #[arg(long)]
+    pub deps_summary: bool,
+
+    /// Show the paths under DIR as starting with NAME instead, as in
+    /// `--alias /home/me/work/monorepo/services/auth=auth` (can be used multiple times).
+    #[arg(long = "alias", value_name = "DIR=NAME")]
+    pub aliases: Vec<format::Alias>,
+
+    /// The output paths of the files that would be shown under the same path as
+    /// another, set at the start of a run.
+    #[arg(skip)]
+    pub(crate) renamed: HashMap<PathBuf, PathBuf>,
+
+    /// With --cxml, check that the whole output is well-formed XML before writing it (buffers the output in memory).
+    #[arg(long, requires = "cxml", conflicts_with = "format_plugin")]
+    pub validate_xml: bool,
+
+    /// Output as Markdown with fenced code blocks.
+    #[arg(short = 'm', long = "markdown")]
+    pub markdown: bool,
+
+    /// With --markdown, precede each file with a YAML front-matter block (path,
+    /// language, SHA-256 and modification time) instead of its path line, for
+    /// static site generators and note-taking tools.
+    #[arg(long, requires = "markdown")]
+    pub front_matter: bool,
+
+    /// Include line numbers in the output.
+    #[arg(short = 'n', long = "line-numbers")]
+    pub line_numbers: bool,
+
+    /// Where --line-numbers puts the numbers: inline, in the content (inside the
+    /// Markdown code fence), or in a gutter of their own (an HTML table column
+    /// next to the fence in Markdown), keeping the code intact for highlighting.
+    #[arg(long, value_name = "STYLE", value_enum, default_value_t)]
+    pub line_numbers_style: format::LineNumberStyle,
+
+    /// Write a JSON map of where each file's lines are in the output to FILE, to
+    /// trace line numbers quoted from the output back to the sources.
+    #[arg(long, value_name = "FILE")]
+    pub source_map: Option<PathBuf>,
+
+    /// Convert tabs to spaces, with tab stops every N columns.
+    ///
+    /// Stops are counted from the start of each line of the file, before line
+    /// numbers are added, so code stays aligned next to the number column.
+    #[arg(long, value_name = "N")]
+    pub expand_tabs: Option<usize>,
+
+    /// Soft-wrap lines longer than COLS characters, continuing them on the next
+    /// line after a `↪ ` marker.
+    ///
+    /// Line numbers count toward the width; continuation lines have none.
+    #[arg(long, value_name = "COLS")]
+    pub wrap: Option<usize>,
+
+    /// Strip UTF-8 byte order marks and convert CRLF line endings to LF in file contents.
+    #[arg(long)]
+    pub normalize: bool,
+
+    /// Normalize file names in the output to Unicode NFC, so paths from macOS
+    /// (which stores them decomposed, NFD) match those from Linux.
+    #[arg(long)]
+    pub nfc: bool,
+
+    /// Show sizes in SI units (kB, MB: powers of 1000) in summaries such as
+    /// --repo-summary and cloc.
+    #[arg(long, conflicts_with = "binary")]
+    pub si: bool,
+
+    /// Show sizes in binary units (KiB, MiB: powers of 1024), the default.
+    #[arg(long)]
+    pub binary: bool,
+
+    /// With --nfc, also normalize file contents to NFC.
+    #[arg(long, requires = "nfc")]
+    pub nfc_content: bool,
+
+    /// Remove ANSI terminal escape sequences (colors, cursor moves) from file contents.
+    #[arg(long)]
+    pub strip_ansi: bool,
+
+    /// Truncate JSON/YAML values nested deeper than N levels, keeping keys visible.
+    #[arg(long, value_name = "N")]
+    pub structured_max_depth: Option<usize>,
+
+    /// Only include the header plus the first and last N rows of CSV/TSV files.
+    #[arg(long, value_name = "N")]
+    pub csv_sample: Option<usize>,
+
+    /// Compact .log and .txt files: strip ANSI escape codes, collapse repeated lines and keep only the last --log-lines lines (also for files over --stream-threshold).
+    #[arg(long)]
+    pub logs_mode: bool,
+
+    /// Number of lines --logs-mode keeps from the end of each log.
+    #[arg(long, value_name = "N", default_value_t = logs::DEFAULT_LINES)]
+    pub log_lines: usize,
+
+    /// Only include the first N and last M lines of files longer than that, as in head=N,tail=M (also for files over --stream-threshold, which are then no longer streamed whole).
+    #[arg(long, value_name = "SPEC")]
+    pub sample_large: Option<sample::SampleSpec>,
+
+    /// Split each file into chunks of about SIZE estimated tokens, written as
+    /// documents of their own (`path#chunk-i`), for retrieval indexes. Chunks
+    /// end at blank lines before top-level code where they can.
+    #[arg(long, value_name = "SIZE", conflicts_with = "source_map")]
+    pub chunk: Option<usize>,
+
+    /// Start each chunk with up to N estimated tokens of the end of the previous one.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub chunk_overlap: usize,
+
+    /// Apply a content filter: redact=REGEX, strip-comments, strip-ansi, normalize, structured-max-depth=N, csv-sample=N, logs=N, sample-large=head=N,tail=M or plugin=NAME (can be used multiple times, applied in order).
+    #[arg(long = "filter", value_name = "FILTER")]
+    pub filters: Vec<FilterSpec>,
+
+    /// Write file records in another format instead of a prompt.
+    #[arg(long, value_name = "FORMAT", value_enum, conflicts_with_all = ["cxml", "markdown", "format_plugin"])]
+    pub format: Option<export::OutputFormat>,
+
+    /// Build the graph of references between the files: imports, or calls of
+    /// the functions of other files. Written with --format dot or json, and
+    /// favoring the files the others depend on with --pack.
+    #[arg(long, value_name = "KIND", value_enum)]
+    pub graph: Option<graph::GraphKind>,
+
+    /// The directory to write the notes to, with --format obsidian, or the
+    /// processed files to, with --mirror.
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<PathBuf>,
+
+    /// The embedding model for --format vectors (`embed` feature), as named by
+    /// the API of --embed-url.
+    #[arg(long, value_name = "MODEL")]
+    pub embed: Option<String>,
+
+    /// The OpenAI-compatible embeddings endpoint used by --embed. The key in
+    /// `EMBEDDING_API_KEY` (or `OPENAI_API_KEY`), if set, is sent as a bearer token.
+    #[arg(
+        long,
+        value_name = "URL",
+        default_value = "http://localhost:11434/v1/embeddings",
+        requires = "embed"
+    )]
+    pub embed_url: String,
+
+    /// The model of --format anthropic-request, as in `claude-sonnet-4-5`.
+    #[arg(long, value_name = "MODEL")]
+    pub model: Option<String>,
+
+    /// The most tokens the model may generate, for --format anthropic-request.
+    #[arg(long, value_name = "N", default_value_t = 4096)]
+    pub max_output_tokens: u32,
+
+    /// The system prompt of --format anthropic-request. Can refer to the run with
+    /// `{{git_branch}}`, `{{git_commit}}`, `{{date}}`, `{{env.VAR}}` and `{{file_count}}`.
+    #[arg(long, value_name = "TEXT")]
+    pub system: Option<String>,
+
+    /// The question or instruction ending the user turn of --format
+    /// anthropic-request, after the files (with the variables of --system).
+    #[arg(long, value_name = "TEXT")]
+    pub prompt: Option<String>,
+
+    /// Write each processed file (after filters such as redaction or comment
+    /// stripping) to its path under --output-dir instead of concatenating them,
+    /// producing a sanitized copy of the tree.
+    #[arg(long, requires = "output_dir", conflicts_with_all = ["format", "format_plugin"])]
+    pub mirror: bool,
+
+    /// Have the files-ingest-NAME plugin format the output, from the JSON array of all documents.
+    #[arg(long, value_name = "NAME")]
+    pub format_plugin: Option<String>,
+
+    /// Only include files whose detected license is one of these SPDX identifiers (comma-separated); files without a detected license are kept.
+    #[arg(long, value_name = "LICENSES", value_delimiter = ',')]
+    pub license_allow: Vec<String>,
+
+    /// Include image files (png, jpg, gif, webp, svg) as base64 data URIs or as path references with dimensions.
+    #[arg(long, value_name = "MODE")]
+    pub images: Option<images::ImageMode>,
+
+    /// Syntax-highlight the default output format when writing to a terminal.
+    #[arg(long)]
+    pub color: bool,
+
+    /// Stop once the output would exceed N bytes (see --on-overflow).
+    #[arg(long, value_name = "N")]
+    pub max_output_bytes: Option<usize>,
+
+    /// Stop once the output would exceed an estimated N tokens (about 4 bytes each; see --on-overflow and --pack).
+    #[arg(long, value_name = "N")]
+    pub max_tokens: Option<usize>,
+
+    /// Choose the files that fit within --max-tokens by priority (entry points, git activity with --order churn) instead of in output order.
+    #[arg(long, value_name = "STRATEGY", value_enum, requires = "max_tokens")]
+    pub pack: Option<pack::PackStrategy>,
+
+    /// What to do when --max-output-bytes or --max-tokens is exceeded: abort with an error, or truncate with a marker.
+    #[arg(long, value_name = "POLICY", value_enum, default_value_t)]
+    pub on_overflow: limit::OverflowPolicy,
+
+    /// Stream files larger than this many bytes in chunks instead of loading them into memory (content transforms are skipped for them).
+    #[arg(long, value_name = "BYTES", default_value_t = stream::DEFAULT_THRESHOLD)]
+    pub stream_threshold: u64,
+
+    /// Memory-map files larger than this many bytes instead of copying them into memory.
+    #[arg(long, value_name = "BYTES", default_value_t = mmap::DEFAULT_THRESHOLD)]
+    pub mmap_threshold: u64,
+
+    /// Read files at no more than this many megabytes per second, so scheduled runs
+    /// don't saturate the disks of shared machines.
+    #[arg(long, value_name = "MB_PER_SEC")]
+    pub throttle_mbps: Option<f64>,
+
+    /// Number of threads for walking directories and reading/formatting files [default: available parallelism].
+    #[arg(long, value_name = "N")]
+    pub threads: Option<usize>,
+
+    /// What to do with errors while walking directories (e.g. permission denied): warn, skip silently, or fail.
+    #[arg(long, value_name = "POLICY", value_enum, default_value_t)]
+    pub on_walk_error: walk::WalkErrorPolicy,
+
+    /// Include files with identical content only once; later copies become a "same as <path>" stub.
+    #[arg(long)]
+    pub dedupe_content: bool,
+
+    /// After the output, report clusters of near-duplicate files on stderr.
+    #[arg(long)]
+    pub similar_report: bool,
+
+    /// Minimum estimated similarity (0.0 to 1.0) for --similar-report to group files.
+    #[arg(long, value_name = "SCORE", default_value_t = 0.8)]
+    pub similar_threshold: f64,
+
+    /// After the output, report on stderr the time spent walking, selecting,
+    /// tokenizing, reading, transforming and writing, and the slowest files.
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Number of slowest files listed by --timings.
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    pub timings_slowest: usize,
+}
+
+impl Default for Options {
+    /// The options of a plain `files-ingest` invocation, without any paths.
+    fn default() -> Self {
+        Self::parse_from(["files-ingest"])
+    }
+}
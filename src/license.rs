@@ -0,0 +1,214 @@
+//! Per-file license detection and `--license-allow`.
+//!
+//! A file's license is, in order of precedence:
+//!
+//! - the expression of an `SPDX-License-Identifier:` tag near its top, usually
+//!   in a comment (such as `MIT OR Apache-2.0`);
+//! - the license whose text it starts with, for common licenses (LICENSE files,
+//!   or source files with the license in a header comment);
+//! - the license of the nearest enclosing directory with a LICENSE or COPYING
+//!   file, so vendored code carries the license of the project it came from.
+
+use crate::provider::FileProvider;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+
+/// Number of leading lines searched for a tag or a license text.
+const HEADER_LINES: usize = 30;
+
+const TAG: &str = "SPDX-License-Identifier:";
+
+/// Files holding a directory's license. Projects offering a choice of licenses
+/// ship one file per license (LICENSE-MIT, LICENSE-APACHE).
+const LICENSE_FILES: &[&str] = &[
+    "LICENSE",
+    "LICENSE.md",
+    "LICENSE.txt",
+    "LICENCE",
+    "LICENCE.md",
+    "LICENCE.txt",
+    "COPYING",
+    "COPYING.md",
+    "COPYING.txt",
+    "LICENSE-MIT",
+    "LICENSE-APACHE",
+];
+
+/// Common licenses and phrases (all required) identifying their text.
+const LICENSE_TEXTS: &[(&str, &[&str])] = &[
+    (
+        "Apache-2.0",
+        &["Apache License", "Version 2.0, January 2004"],
+    ),
+    (
+        "MIT",
+        &["Permission is hereby granted, free of charge, to any person obtaining a copy"],
+    ),
+    (
+        "ISC",
+        &["Permission to use, copy, modify, and/or distribute this software for any purpose"],
+    ),
+    (
+        "BSD-3-Clause",
+        &[
+            "Redistribution and use in source and binary forms",
+            "Neither the name of",
+        ],
+    ),
+    (
+        "BSD-2-Clause",
+        &["Redistribution and use in source and binary forms"],
+    ),
+    (
+        "AGPL-3.0",
+        &["GNU AFFERO GENERAL PUBLIC LICENSE", "Version 3"],
+    ),
+    (
+        "LGPL-3.0",
+        &["GNU LESSER GENERAL PUBLIC LICENSE", "Version 3"],
+    ),
+    (
+        "LGPL-2.1",
+        &["GNU LESSER GENERAL PUBLIC LICENSE", "Version 2.1"],
+    ),
+    ("GPL-3.0", &["GNU GENERAL PUBLIC LICENSE", "Version 3"]),
+    ("GPL-2.0", &["GNU GENERAL PUBLIC LICENSE", "Version 2"]),
+    ("MPL-2.0", &["Mozilla Public License Version 2.0"]),
+    ("MPL-2.0", &["Mozilla Public License, v. 2.0"]), // The header notice
+    (
+        "Unlicense",
+        &["This is free and unencumbered software released into the public domain"],
+    ),
+];
+
+/// Returns the license `content` declares (with a tag) or starts with, as an SPDX expression.
+#[must_use]
+pub fn detect(content: &str) -> Option<String> {
+    let header = content.lines().take(HEADER_LINES);
+    if let Some(expression) = header.clone().find_map(tag_expression) {
+        return Some(expression);
+    }
+    // License texts are wrapped at various widths
+    let text = header
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ");
+    LICENSE_TEXTS
+        .iter()
+        .find(|(_, phrases)| phrases.iter().all(|phrase| text.contains(phrase)))
+        .map(|(license, _)| (*license).to_string())
+}
+
+/// Returns the expression of the SPDX tag on `line`, if any.
+fn tag_expression(line: &str) -> Option<String> {
+    let (_, expression) = line.split_once(TAG)?;
+    // Drop whatever closes the comment, as in `/* SPDX-License-Identifier: MIT */`
+    let expression = expression
+        .trim()
+        .trim_end_matches("*/")
+        .trim_end_matches("-->")
+        .trim();
+    (!expression.is_empty()).then(|| expression.to_string())
+}
+
+/// Whether the SPDX `expression` only grants licenses from `allowed`: at least
+/// one choice of an `OR` must be allowed, and every part of an `AND`. Operators
+/// are case-insensitive, like identifiers.
+#[must_use]
+pub fn is_allowed(expression: &str, allowed: &[String]) -> bool {
+    let expression = expression.replace(['(', ')'], " ");
+    let words: Vec<&str> = expression.split_whitespace().collect();
+    words
+        .split(|word| word.eq_ignore_ascii_case("OR"))
+        .any(|choice| {
+            choice
+                .split(|word| word.eq_ignore_ascii_case("AND"))
+                .all(|part| {
+                    // `WITH` exceptions only ever relax the license: only the license counts
+                    part.first().is_some_and(|license| {
+                        allowed.iter().any(|allowed| grants(license, allowed))
+                    })
+                })
+        })
+}
+
+/// Whether `license` grants the `allowed` license. A license "or later" grants
+/// its version too, but a license limited to a version (`GPL-2.0-only`, or
+/// `GPL-2.0`) doesn't grant the later ones (`GPL-2.0-or-later`, or `GPL-2.0+`).
+fn grants(license: &str, allowed: &str) -> bool {
+    let (license, or_later) = normalize(license);
+    let (allowed, allowed_or_later) = normalize(allowed);
+    license == allowed && (or_later || !allowed_or_later)
+}
+
+/// Normalizes an SPDX identifier for comparison: case-insensitive, and without
+/// its version qualifier, returning whether it covers later versions.
+fn normalize(license: &str) -> (String, bool) {
+    let license = license.trim();
+    let (license, or_later) = match license
+        .strip_suffix("-or-later")
+        .or_else(|| license.strip_suffix('+'))
+    {
+        Some(license) => (license, true),
+        None => (license.strip_suffix("-only").unwrap_or(license), false),
+    };
+    (license.to_ascii_lowercase(), or_later)
+}
+
+/// Resolves the licenses of files, caching those of directories.
+#[derive(Default)]
+pub struct Licenses {
+    directories: Mutex<HashMap<PathBuf, Option<String>>>,
+}
+
+impl Licenses {
+    /// Returns the license of the file at `path`, from its `content` (if at hand)
+    /// or from the enclosing directories.
+    pub fn of_file(
+        &self,
+        path: &Path,
+        content: Option<&str>,
+        provider: &dyn FileProvider,
+    ) -> Option<String> {
+        content
+            .and_then(detect)
+            .or_else(|| self.of_directory(path.parent()?, provider))
+    }
+
+    /// Returns the license of the nearest directory from `dir` up with a license file.
+    fn of_directory(&self, dir: &Path, provider: &dyn FileProvider) -> Option<String> {
+        if let Some(license) = self
+            .directories
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(dir)
+        {
+            return license.clone();
+        }
+        let mut licenses: Vec<String> = Vec::new();
+        for name in LICENSE_FILES {
+            let path = dir.join(name);
+            if let Some(license) = provider
+                .read_text(&path)
+                .ok()
+                .and_then(|content| detect(&content))
+                && !licenses.contains(&license)
+            {
+                licenses.push(license);
+            }
+        }
+        let license = if licenses.is_empty() {
+            dir.parent()
+                .and_then(|parent| self.of_directory(parent, provider))
+        } else {
+            Some(licenses.join(" OR "))
+        };
+        let mut directories = self
+            .directories
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        directories.insert(dir.to_path_buf(), license.clone());
+        license
+    }
+}
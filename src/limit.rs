@@ -0,0 +1,85 @@
+//! Output size limits (`--max-output-bytes`, `--max-tokens`).
+
+use crate::{Options, tokens};
+use clap::ValueEnum;
+use std::io::{self, Write};
+
+/// What to do once the output would exceed `--max-output-bytes` or `--max-tokens`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Stop with an error.
+    #[default]
+    Abort,
+    /// Stop adding files and end the output with a truncation marker.
+    Truncate,
+}
+
+/// The output limit in bytes, the tighter of `--max-output-bytes` and
+/// `--max-tokens` (estimated tokens are a fixed number of bytes).
+pub fn max_bytes(options: &Options) -> Option<usize> {
+    let token_bytes = options
+        .max_tokens
+        .map(|max_tokens| max_tokens.saturating_mul(tokens::BYTES_PER_TOKEN));
+    match (options.max_output_bytes, token_bytes) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// A writer that keeps track of how many bytes (and lines) have been written through it.
+pub struct CountingWriter<W: Write> {
+    inner: W,
+    written: usize,
+    newlines: usize,
+    /// The last two bytes written.
+    last: [u8; 2],
+}
+
+impl<W: Write> CountingWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            written: 0,
+            newlines: 0,
+            last: [0; 2],
+        }
+    }
+
+    /// Total number of bytes written so far.
+    pub fn bytes_written(&self) -> usize {
+        self.written
+    }
+
+    /// Number of newlines written so far (not counting bytes written elsewhere).
+    pub fn newlines_written(&self) -> usize {
+        self.newlines
+    }
+
+    /// Whether what was written so far ends with an empty line.
+    pub fn ends_with_blank_line(&self) -> bool {
+        self.last == *b"\n\n"
+    }
+
+    /// Counts `n` bytes that were written elsewhere.
+    pub fn count(&mut self, n: usize) {
+        self.written += n;
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.written += n;
+        for &byte in &buf[..n] {
+            if byte == b'\n' {
+                self.newlines += 1;
+            }
+            self.last = [self.last[1], byte];
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
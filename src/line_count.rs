@@ -0,0 +1,50 @@
+//! Selection by line count (`--min-lines`, `--max-lines`), to leave out
+//! trivially small files or huge generated dumps wholesale.
+
+use crate::Options;
+use crate::provider::FileProvider;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Whether the line count filters are in use.
+pub fn is_filtering(options: &Options) -> bool {
+    options.min_lines.is_some() || options.max_lines.is_some()
+}
+
+/// Whether the file at `path` has between --min-lines and --max-lines lines.
+///
+/// Files that can't be read are kept, for the error to be reported when read.
+pub fn selects(provider: &dyn FileProvider, path: &Path, options: &Options) -> bool {
+    let Ok(lines) = count(provider, path, options.max_lines) else {
+        return true;
+    };
+    options.min_lines.is_none_or(|min| lines >= min)
+        && options.max_lines.is_none_or(|max| lines <= max)
+}
+
+/// Counts the lines of the file at `path` (a last line without a newline
+/// included), stopping once past `limit`.
+fn count(provider: &dyn FileProvider, path: &Path, limit: Option<usize>) -> io::Result<usize> {
+    let mut reader = provider.open(path)?;
+    let mut buffer = vec![0; 64 * 1024];
+    let mut lines = 0;
+    let mut last = b'\n';
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for &byte in &buffer[..read] {
+            if byte == b'\n' {
+                lines += 1;
+            }
+        }
+        last = buffer[read - 1];
+        if limit.is_some_and(|limit| lines > limit) {
+            return Ok(lines);
+        }
+    }
+    Ok(lines + usize::from(last != b'\n'))
+}
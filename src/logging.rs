@@ -0,0 +1,71 @@
+//! Diagnostics on stderr. The warnings and messages of the library and of the
+//! subcommands are `tracing` events, written as readable lines or as JSON
+//! objects for log collectors (`--log-format`), and filtered with `RUST_LOG`.
+
+use clap::ValueEnum;
+use std::{env, fmt, io};
+use tracing::{Event, Level, Subscriber, warn};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// What is shown when `RUST_LOG` isn't set: this tool's messages, and the
+/// warnings of the libraries it uses.
+const DEFAULT_FILTER: &str = "warn,files_ingest=info";
+
+/// How diagnostics are written (`--log-format`).
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum LogFormat {
+    /// One line per message, as in "Warning: Skipping file a.bin - Not valid UTF-8."
+    #[default]
+    Pretty,
+    /// One JSON object per message, with its timestamp, level and target.
+    Json,
+}
+
+/// Installs the subscriber writing diagnostics to stderr in `format`.
+pub fn init(format: LogFormat) {
+    let (filter, invalid) = match env::var("RUST_LOG") {
+        Ok(spec) => match EnvFilter::try_new(&spec) {
+            Ok(filter) => (filter, None),
+            Err(e) => (EnvFilter::new(DEFAULT_FILTER), Some(e)),
+        },
+        Err(_) => (EnvFilter::new(DEFAULT_FILTER), None),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr);
+    match format {
+        LogFormat::Pretty => builder.event_format(Plain).init(),
+        LogFormat::Json => builder.json().flatten_event(true).init(),
+    }
+    if let Some(e) = invalid {
+        warn!("Ignoring RUST_LOG - {e}");
+    }
+}
+
+/// The readable format: the message (and any other fields), prefixed with
+/// "Warning:" or "Error:" for those levels.
+struct Plain;
+
+impl<S, N> FormatEvent<S, N> for Plain
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        match *event.metadata().level() {
+            Level::ERROR => write!(writer, "Error: ")?,
+            Level::WARN => write!(writer, "Warning: ")?,
+            _ => {}
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
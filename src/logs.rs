@@ -0,0 +1,90 @@
+//! Log-file compaction (`--logs-mode`).
+//!
+//! Logs are long, repetitive and colored, and the end is what matters: ANSI
+//! escape codes are stripped, runs of identical lines collapse into one line
+//! with a count, and only the last lines are kept.
+
+use crate::ansi;
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+/// Lines kept by default with `--logs-mode`.
+pub const DEFAULT_LINES: usize = 2000;
+
+/// Whether `--logs-mode` applies to the file at `path`.
+pub fn is_log(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("log") || ext.eq_ignore_ascii_case("txt"))
+}
+
+/// Compacts a log, keeping its last `max_lines` lines (after collapsing repeats).
+pub fn compact(content: &str, max_lines: usize) -> String {
+    let mut log = Compactor::new(max_lines);
+    for line in content.lines() {
+        log.push(line);
+    }
+    log.finish()
+}
+
+/// Compacts a log as it is read, holding only the lines it keeps.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the content is not valid UTF-8.
+pub fn compact_reader(reader: impl Read, max_lines: usize) -> io::Result<String> {
+    let mut log = Compactor::new(max_lines);
+    for line in BufReader::new(reader).lines() {
+        log.push(&line?);
+    }
+    Ok(log.finish())
+}
+
+struct Compactor {
+    max_lines: usize,
+    /// The last lines, with how many times each was repeated in a row.
+    kept: VecDeque<(String, usize)>,
+    omitted: usize,
+}
+
+impl Compactor {
+    fn new(max_lines: usize) -> Self {
+        Self {
+            max_lines,
+            kept: VecDeque::new(),
+            omitted: 0,
+        }
+    }
+
+    fn push(&mut self, line: &str) {
+        let line = ansi::strip(line);
+        if let Some((last, count)) = self.kept.back_mut()
+            && *last == line
+        {
+            *count += 1;
+            return;
+        }
+        self.kept.push_back((line.into_owned(), 1));
+        if self.kept.len() > self.max_lines {
+            self.kept.pop_front();
+            self.omitted += 1;
+        }
+    }
+
+    fn finish(mut self) -> String {
+        let mut lines = Vec::with_capacity(self.kept.len());
+        if self.omitted > 0 {
+            // The marker counts as a line, so that compacting again changes nothing
+            self.kept.pop_front();
+            self.omitted += 1;
+            lines.push(format!("[... {} earlier lines omitted ...]", self.omitted));
+        }
+        lines.extend(self.kept.into_iter().map(|(line, count)| {
+            if count > 1 {
+                format!("{line} [repeated {count} times]")
+            } else {
+                line
+            }
+        }));
+        lines.join("\n")
+    }
+}
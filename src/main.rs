@@ -1,64 +1,41 @@
-use clap::Parser;
-use ignore::{DirEntry, WalkBuilder}; // For directory traversal respecting .gitignore etc.
-use std::collections::HashMap;
-use std::fs::{self, File};
+use clap::{Parser, Subcommand};
+use ed25519_dalek::SigningKey;
+use files_ingest::{AppError, DEFAULT_CONFIG_FILE, Ingestor, Options, Summary};
+use interrupt::InterruptPolicy;
+use std::env;
+use std::fs;
 use std::io::{self, BufWriter, Read, Write}; // Standard Input/Output operations
 use std::path::{Path, PathBuf};
-use std::sync::OnceLock;
-use std::sync::atomic::{AtomicUsize, Ordering}; // For the global CXML index
-use thiserror::Error; // For custom error types // Import the atty crate
-
-// --- Configuration & Constants ---
-
-// Static map for file extensions to Markdown language tags
-static EXT_TO_LANG: OnceLock<HashMap<&'static str, &'static str>> = OnceLock::new();
-fn initialize_language_map() -> HashMap<&'static str, &'static str> {
-    let mut m = HashMap::new();
-    m.insert("py", "python");
-    m.insert("rs", "rust");
-    m.insert("c", "c");
-    m.insert("h", "c");
-    m.insert("cpp", "cpp");
-    m.insert("hpp", "cpp");
-    m.insert("java", "java");
-    m.insert("js", "javascript");
-    m.insert("ts", "typescript");
-    m.insert("html", "html");
-    m.insert("css", "css");
-    m.insert("xml", "xml");
-    m.insert("json", "json");
-    m.insert("yaml", "yaml");
-    m.insert("yml", "yaml");
-    m.insert("sh", "bash");
-    m.insert("rb", "ruby");
-    m.insert("md", "markdown");
-    m.insert("toml", "toml");
-    m.insert("go", "go");
-    m.insert("php", "php");
-    m.insert("swift", "swift");
-    m.insert("kt", "kotlin");
-    m.insert("sql", "sql");
-    m
-}
+use std::process;
+use tempfile::NamedTempFile;
+use tracing::{error, warn};
 
-// Global counter for Claude XML document index
-static GLOBAL_INDEX: AtomicUsize = AtomicUsize::new(1);
-
-// --- Error Handling ---
-
-#[derive(Error, Debug)]
-enum AppError {
-    #[error("IO Error: {0}")]
-    Io(#[from] io::Error),
-    #[error("Ignore Error: {0}")] // Keep this for potential errors during walk
-    Ignore(#[from] ignore::Error),
-    #[error("Path does not exist: {0}")]
-    PathNotFound(PathBuf),
-    // Add a specific error variant if needed for invalid ignore patterns,
-    // though 'ignore' crate often reports these during the walk.
-    // #[error("Invalid ignore pattern: {0}")]
-    // InvalidIgnorePattern(String),
-}
+mod apply; // The apply subcommand
+mod argsfile; // @FILE arguments
+mod encrypt; // Encryption of the output
+mod interrupt; // Ctrl-C during a run
+mod logging; // Diagnostics on stderr
+mod pager; // Paging of terminal output
+mod post; // Sending the output in an HTTP request
+mod sign; // Integrity footer of the output
+mod snapshot; // The snapshot subcommand
+mod upload; // Uploading the output to S3
+
+/// Exit status when the filters select no files (errors exit with 1).
+const EXIT_NO_FILES_SELECTED: i32 = 3;
+
+/// Exit status of `snapshot --check` when the snapshot is out of date.
+const EXIT_SNAPSHOT_DRIFT: i32 = 4;
+
+/// Exit status of `verify-signature` when the bundle doesn't match its footer.
+const EXIT_SIGNATURE_INVALID: i32 = 5;
+
+/// Exit status of an interrupted run (128 + SIGINT, as shells report it).
+const EXIT_INTERRUPTED: i32 = 130;
+
+/// Exit status when the reader of stdout went away, as in `| head` (128 + SIGPIPE,
+/// as for the tools killed by the signal).
+const EXIT_BROKEN_PIPE: i32 = 141;
 
 // --- Command Line Argument Parsing ---
 
@@ -67,245 +44,468 @@ enum AppError {
     author,
     version,
     about = "Concatenates files into a single prompt, similar to Python's files-to-prompt.",
-    long_about = "Takes one or more paths to files or directories and outputs the content of each file, recursively. Supports filtering, ignoring files (.gitignore), and various output formats (default, Claude XML, Markdown)."
+    long_about = "Takes one or more paths to files or directories and outputs the content of each file, recursively. Supports filtering, ignoring files (.gitignore), and various output formats (default, Claude XML, Markdown).",
+    args_conflicts_with_subcommands = true,
+    disable_help_subcommand = true
 )]
 #[allow(clippy::struct_excessive_bools)]
 struct Cli {
-    /// Paths to files or directories to process. Reads from stdin if empty.
-    #[arg(name = "PATHS")]
-    paths: Vec<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// Only include files with the specified extension (can be used multiple times).
-    #[arg(short, long = "extension", value_name = "EXT")]
-    extensions: Vec<String>,
+    #[command(flatten)]
+    options: Options,
 
-    /// Include hidden files and directories (starting with '.').
-    #[arg(long)]
-    include_hidden: bool,
-
-    /// Specify patterns to ignore (files or directories, uses gitignore syntax). Can be used multiple times.
-    #[arg(long = "ignore", value_name = "PATTERN")]
-    ignore_patterns: Vec<String>,
-
-    /// When set, --ignore patterns only match files, not directories.
-    #[arg(long)]
-    ignore_files_only: bool,
+    /// Write output to a file instead of stdout, or to an S3 object with an
+    /// `s3://bucket/key` URL (`s3` feature).
+    #[arg(short = 'o', long = "output", value_name = "FILE")]
+    output_file: Option<PathBuf>,
 
-    /// Ignore .gitignore files and include all files found.
-    #[arg(long)]
-    ignore_gitignore: bool,
+    /// Send the output as the body of an HTTP POST request to this URL instead of
+    /// stdout (streamed through curl; the response is printed).
+    #[arg(long, value_name = "URL", conflicts_with = "output_file")]
+    post: Option<String>,
 
-    /// Output in Claude XML format.
-    #[arg(short = 'c', long = "cxml")]
-    cxml: bool,
+    /// An extra header for --post, as in `Authorization: Bearer TOKEN` (can be used multiple times).
+    #[arg(long = "header", value_name = "NAME: VALUE", requires = "post")]
+    headers: Vec<String>,
 
-    /// Output as Markdown with fenced code blocks.
-    #[arg(short = 'm', long = "markdown")]
-    markdown: bool,
+    /// Encrypt the output with age for this recipient, as in `age:age1...` (can be used
+    /// multiple times; `encrypt` feature). Decrypt it with the decrypt subcommand.
+    #[arg(long, value_name = "age:RECIPIENT")]
+    encrypt: Vec<encrypt::Recipient>,
 
-    /// Include line numbers in the output.
-    #[arg(short = 'n', long = "line-numbers")]
-    line_numbers: bool,
+    /// Append a footer with the SHA-256 of the output (and, with --signing-key, an
+    /// ed25519 signature of it), checked by the verify-signature subcommand. Not
+    /// available with --format, whose records or archives a footer line would corrupt.
+    #[arg(long, conflicts_with = "format")]
+    sign: bool,
 
-    /// Write output to a file instead of stdout.
-    #[arg(short = 'o', long = "output", value_name = "FILE")]
-    output_file: Option<PathBuf>,
+    /// The ed25519 private key (PKCS#8 PEM) signing the --sign footer.
+    #[arg(long, value_name = "FILE", requires = "sign")]
+    signing_key: Option<PathBuf>,
 
     /// Use NUL character ('\0') as separator when reading paths from stdin.
     #[arg(short = '0', long = "null")]
     null_separator: bool,
+
+    /// Send output through a pager ($PAGER, or `less`) when writing to a terminal.
+    #[arg(
+        long,
+        value_name = "WHEN",
+        value_enum,
+        default_value_t,
+        num_args = 0..=1,
+        default_missing_value = "always"
+    )]
+    pager: pager::PagerMode,
+
+    /// What to do with the output when the run is interrupted (Ctrl-C or SIGTERM):
+    /// end it properly after the files written so far, or remove the --output
+    /// file. A second Ctrl-C exits at once.
+    #[arg(long, value_name = "POLICY", value_enum, default_value_t)]
+    on_interrupt: InterruptPolicy,
+
+    /// Run with the lowest I/O and CPU priorities (ionice idle class, nice 19), for
+    /// scheduled runs on shared machines.
+    #[arg(long)]
+    nice_io: bool,
+
+    /// List the plugins (files-ingest-* executables) found on PATH and exit.
+    #[arg(long)]
+    list_plugins: bool,
+
+    /// How warnings and messages are written to stderr: readable lines, or one
+    /// JSON object per message (filtered with `RUST_LOG` either way).
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_enum,
+        default_value_t,
+        global = true
+    )]
+    log_format: logging::LogFormat,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Regenerate the bundle recorded in the [snapshot] section of the config file.
+    Snapshot {
+        /// Compare the regenerated bundle to the committed one instead of writing it,
+        /// exiting with status 4 if they differ.
+        #[arg(long)]
+        check: bool,
+
+        /// The config file holding the [snapshot] section.
+        #[arg(long, value_name = "FILE", default_value = DEFAULT_CONFIG_FILE)]
+        config: PathBuf,
+    },
+    /// Decrypt a bundle written with --encrypt to stdout.
+    Decrypt {
+        /// The age identity file holding the recipient's private key.
+        #[arg(short, long, value_name = "FILE")]
+        identity: PathBuf,
+
+        /// The encrypted bundle (stdin if omitted).
+        file: Option<PathBuf>,
+    },
+    /// Check the --sign footer of a bundle, exiting with status 5 if it doesn't match.
+    VerifySignature {
+        /// The ed25519 public key (PEM) to check the signature with; without it,
+        /// only the SHA-256 is checked.
+        #[arg(long, value_name = "FILE")]
+        public_key: Option<PathBuf>,
+
+        /// The signed bundle.
+        file: PathBuf,
+    },
+    /// Write the files of a model's response (in a bundle format, or as unified
+    /// diffs) to the working tree, after showing the changes and asking for confirmation.
+    Apply {
+        /// Apply without asking for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Only show the changes.
+        #[arg(long, conflicts_with = "yes")]
+        dry_run: bool,
+
+        /// The response (stdin if omitted).
+        file: Option<PathBuf>,
+    },
+    /// Count the files and the blank, comment and code lines of each language in
+    /// the selected files (taking the same paths and filters as a normal run).
+    Cloc {
+        #[command(flatten)]
+        options: Box<Options>,
+    },
 }
 
 // --- Main Application Logic ---
 
 fn main() -> Result<(), AppError> {
-    let mut cli = Cli::parse();
+    match run() {
+        // The rest of the output isn't wanted, which is no error
+        Err(AppError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {
+            process::exit(EXIT_BROKEN_PIPE)
+        }
+        result => result,
+    }
+}
+
+/// Runs the command line.
+fn run() -> Result<(), AppError> {
+    let mut cli = Cli::parse_from(argsfile::expand(env::args_os())?);
+    logging::init(cli.log_format);
+
+    if let Some(command) = &cli.command {
+        return run_command(command);
+    }
+
+    if cli.list_plugins {
+        let mut stdout = io::stdout().lock();
+        for (name, path) in files_ingest::list_plugins() {
+            writeln!(stdout, "{name}\t{}", path.display())?;
+        }
+        return Ok(());
+    }
+    if cli.nice_io {
+        lower_priority(); // Before any thread starts: they inherit it
+    }
+    let options = &mut cli.options;
 
     // --- Read paths from stdin if no paths are provided as arguments ---
-    if cli.paths.is_empty() {
-        read_paths_from_stdin(&mut cli.paths, cli.null_separator)?;
-        if cli.paths.is_empty() {
-            eprintln!(
+    if !has_inputs(options) {
+        read_paths_from_stdin(&mut options.paths, cli.null_separator)?;
+        if options.paths.is_empty() {
+            warn!(
                 "No input paths provided either as arguments or via stdin. Use --help for usage."
             );
             return Ok(()); // Exit gracefully if no input
         }
     }
 
-    // --- Validate input paths ---
-    for path in &cli.paths {
-        if !path.exists() {
-            return Err(AppError::PathNotFound(path.clone()));
-        }
+    // Pick up the project's config file, unless another one was given
+    if options.config.is_none() && Path::new(DEFAULT_CONFIG_FILE).is_file() {
+        options.config = Some(PathBuf::from(DEFAULT_CONFIG_FILE));
+    }
+
+    // Highlighting only makes sense for an interactive terminal preview
+    if options.color
+        && (cli.output_file.is_some() || cli.post.is_some() || !atty::is(atty::Stream::Stdout))
+    {
+        options.color = false;
     }
 
     // --- Setup Output Writer ---
     // Determine where to write the output: stdout or a file.
     // Use BufWriter for potentially better performance, especially with large outputs.
-    let writer: Box<dyn Write> = if let Some(output_path) = &cli.output_file {
-        Box::new(BufWriter::new(File::create(output_path)?))
+    // When paging or posting, output goes to the pager's (or curl's) stdin instead of stdout.
+    // An S3 destination (or --output file) is staged in a local file, uploaded
+    // (or renamed) once the run succeeded.
+    let Output {
+        mut writer,
+        pager: pager_process,
+        curl: mut post_process,
+        s3: s3_destination,
+        staged: staged_output,
+    } = open_output(&cli)?;
+
+    // --- Process Paths ---
+    let signing = match &cli.signing_key {
+        Some(path) => Some(Some(sign::load_key(path)?)),
+        None => cli.sign.then_some(None),
+    };
+    let interrupt = interrupt::install(EXIT_INTERRUPTED);
+    let ingestor = Ingestor::new(cli.options).with_interrupt(interrupt);
+    let result = if cli.encrypt.is_empty() {
+        ingest(&ingestor, &mut writer, signing.as_ref())
     } else {
-        Box::new(BufWriter::new(io::stdout()))
+        let mut encrypted = encrypt::Writer::new(writer, &cli.encrypt)?;
+        let result = ingest(&ingestor, &mut encrypted, signing.as_ref());
+        writer = encrypted.finish()?;
+        result
     };
-    let mut writer = writer; // Make it mutable
 
-    // --- Process Paths ---
-    // Write initial XML tag if needed
-    if cli.cxml {
-        writeln!(writer, "<documents>")?;
+    // Don't post an incomplete bundle: abort the request before ending its body
+    let discard = cli.on_interrupt == InterruptPolicy::Delete
+        && result.as_ref().is_ok_and(|summary| summary.interrupted);
+    if let Some(request) = &mut post_process
+        && (result.is_err() || discard)
+    {
+        let _ = request.curl.kill();
     }
 
-    // Create the directory walker builder
-    // Handle the case where cli.paths might be empty after attempting stdin read
-    if cli.paths.is_empty() {
-        // This case should ideally be caught earlier, but double-check
-        eprintln!("No valid paths found to process.");
-        return Ok(());
+    // Close the pager's stdin and let the user finish reading
+    drop(writer);
+    if let Some(mut child) = pager_process {
+        child.wait()?;
+        if is_broken_pipe(&result) {
+            return Ok(()); // The pager was quit before the end of the output
+        }
     }
-    let mut walker_builder = WalkBuilder::new(&cli.paths[0]); // Start with the first path
-
-    walker_builder
-        .hidden(!cli.include_hidden) // Respect --include-hidden flag
-        .git_ignore(!cli.ignore_gitignore) // Respect --ignore-gitignore flag
-        .git_global(!cli.ignore_gitignore)
-        .git_exclude(!cli.ignore_gitignore)
-        .require_git(false) // Don't require a git repo to exist
-        .ignore(!cli.ignore_gitignore); // Also respect .ignore files
-
-    // Add custom ignore patterns
-    for pattern in &cli.ignore_patterns {
-        // The add_ignore method returns &mut WalkBuilder and doesn't return a Result/Option.
-        // Invalid patterns usually cause errors during the .build() or the walk itself.
-        walker_builder.add_ignore(pattern); // REMOVED '?' and incorrect error handling
-    }
-
-    // Add remaining paths to the walker
-    for path in cli.paths.iter().skip(1) {
-        walker_builder.add(path);
+    // A broken pipe to curl means it failed: report that instead
+    if let Some(request) = post_process
+        && (result.is_ok() && !discard || is_broken_pipe(&result))
+    {
+        post::finish(request)?;
     }
+    let keep = result.is_ok() && !discard;
+    finish_file(
+        s3_destination,
+        staged_output,
+        cli.output_file.as_deref(),
+        keep,
+    )?;
+    let summary = match result {
+        Err(AppError::NoFilesSelected(diagnostic)) => {
+            error!("{diagnostic}");
+            process::exit(EXIT_NO_FILES_SELECTED);
+        }
+        result => result?,
+    };
 
-    // Iterate through the files found by the walker
-    // The build() method itself can return an error (e.g., invalid pattern)
-    for result in walker_builder.build() {
-        // Errors from build() are handled here
-        match result {
-            Ok(entry) => {
-                if !should_process_entry(&entry, &cli) {
-                    continue; // Skip if it's a directory, doesn't match extension, or ignored by --ignore file pattern
-                }
-                let path = entry.path();
-                match fs::read_to_string(path) {
-                    Ok(content) => {
-                        // Successfully read the file content as UTF-8
-                        print_file(
-                            &mut writer,
-                            path,
-                            &content,
-                            cli.cxml,
-                            cli.markdown,
-                            cli.line_numbers,
-                        )?;
-                    }
-                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {
-                        // Handle non-UTF-8 files gracefully
-                        eprintln!(
-                            "{}",
-                            format_args!(
-                                "Warning: Skipping file {} - Not valid UTF-8.",
-                                path.display()
-                            )
-                        );
-                    }
-                    Err(e) => {
-                        // Handle other file reading errors
-                        eprintln!(
-                            "{}",
-                            format_args!(
-                                "Warning: Skipping file {} - Error reading: {}",
-                                path.display(),
-                                e
-                            )
-                        );
-                    }
-                }
-            }
-            Err(err) => {
-                // Handle errors during the walk (could be permission issues, invalid patterns, etc.)
-                eprintln!("Warning: Error during directory walk: {err}");
-            }
+    write_reports(&summary, ingestor.options())?;
+    if summary.interrupted {
+        if discard {
+            warn!("Interrupted; the partial output was discarded");
+        } else {
+            warn!(
+                "Interrupted; the output ends after {} documents",
+                summary.documents
+            );
         }
+        process::exit(EXIT_INTERRUPTED);
     }
+    Ok(())
+}
 
-    // Write closing XML tag if needed
-    if cli.cxml {
-        writeln!(writer, "</documents>")?;
-    }
+// --- Helper Functions ---
 
-    // Ensure the buffer is flushed before exiting
-    writer.flush()?;
+/// Where the output goes: `writer`, and the pager or the --post request reading it, if any.
+struct Output {
+    writer: Box<dyn Write>,
+    pager: Option<process::Child>,
+    curl: Option<post::Request>,
+    /// The S3 destination of the output, staged in a local file.
+    s3: Option<upload::Destination>,
+    /// The file the --output file is written to, renamed into place once the run succeeded.
+    staged: Option<NamedTempFile>,
+}
 
+/// Opens the output: the staging file of an S3 destination or of the --output
+/// file, the stdin of curl (--post) or of the pager, or stdout.
+fn open_output(cli: &Cli) -> Result<Output, AppError> {
+    let mut output = Output {
+        writer: Box::new(BufWriter::new(io::stdout())),
+        pager: None,
+        curl: None,
+        s3: match &cli.output_file {
+            Some(output_path) => upload::destination(output_path)?,
+            None => None,
+        },
+        staged: None,
+    };
+    if let Some(destination) = &output.s3 {
+        output.writer = Box::new(BufWriter::new(destination.staging.reopen()?));
+    } else if let Some(output_path) = &cli.output_file {
+        let staged = stage_output(output_path)?;
+        output.writer = Box::new(BufWriter::new(staged.reopen()?));
+        output.staged = Some(staged);
+    } else if let Some(url) = &cli.post {
+        let mut request = post::spawn(url, &cli.headers)?;
+        let stdin = request
+            .curl
+            .stdin
+            .take()
+            .ok_or_else(|| io::Error::other("no stdin for curl"))?;
+        output.writer = Box::new(BufWriter::new(stdin));
+        output.curl = Some(request);
+    } else if let Some(mut child) = pager::spawn(cli.pager)
+        && let Some(stdin) = child.stdin.take()
+    {
+        output.writer = Box::new(BufWriter::new(stdin));
+        output.pager = Some(child);
+    }
+    Ok(output)
+}
+
+/// Uploads the output staged for `s3_destination`, or renames `staged` to the
+/// --output file, if `keep`. Otherwise the staging file is removed, leaving no
+/// partial output behind.
+fn finish_file(
+    s3_destination: Option<upload::Destination>,
+    staged: Option<NamedTempFile>,
+    output_path: Option<&Path>,
+    keep: bool,
+) -> Result<(), AppError> {
+    if let Some(destination) = s3_destination {
+        upload::finish(destination, keep)?;
+    } else if let Some(staged) = staged
+        && let Some(output_path) = output_path
+        && keep
+    {
+        staged.persist(output_path).map_err(|e| e.error)?;
+    }
     Ok(())
 }
 
-// --- Helper Functions ---
+/// Creates the file staging the --output file at `path`: next to it, so that it
+/// can be renamed into place.
+fn stage_output(path: &Path) -> io::Result<NamedTempFile> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".files-ingest-").suffix(".tmp");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        // The permissions File::create gives, rather than the private ones of temporary files
+        builder.permissions(fs::Permissions::from_mode(0o666));
+    }
+    builder.tempfile_in(dir)
+}
+
+/// Whether `result` failed because the reader of the output went away.
+fn is_broken_pipe<T>(result: &Result<T, AppError>) -> bool {
+    matches!(result, Err(AppError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe)
+}
 
-/// Checks if a directory entry should be processed based on CLI options.
-fn should_process_entry(entry: &DirEntry, cli: &Cli) -> bool {
-    // Only process files
-    if !entry.file_type().is_some_and(|ft| ft.is_file()) {
-        return false;
+/// Writes the reports requested about the run to stderr, after the output.
+fn write_reports(summary: &Summary, options: &Options) -> io::Result<()> {
+    if options.similar_report {
+        summary.write_similar_report(&mut io::stderr(), options.similar_threshold)?;
+    }
+    if options.timings {
+        summary.write_timings(&mut io::stderr())?;
     }
+    Ok(())
+}
 
-    let path = entry.path();
-
-    // Filter by extension if specified
-    if !cli.extensions.is_empty() {
-        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-            if !cli
-                .extensions
-                .iter()
-                .any(|allowed_ext| ext.eq_ignore_ascii_case(allowed_ext))
-            {
-                // Case-insensitive compare
-                return false; // Extension doesn't match
+/// Runs the ingestion into `writer`, ending the output with the --sign footer
+/// if `signing` (signed with the key, if there is one).
+fn ingest(
+    ingestor: &Ingestor,
+    writer: &mut dyn Write,
+    signing: Option<&Option<SigningKey>>,
+) -> Result<Summary, AppError> {
+    let Some(key) = signing else {
+        return ingestor.ingest_to(writer);
+    };
+    let mut signed = sign::Writer::new(writer);
+    let summary = ingestor.ingest_to(&mut signed)?;
+    signed.finish(key.as_ref(), ingestor.options())?;
+    Ok(summary)
+}
+
+/// Runs a subcommand.
+fn run_command(command: &Command) -> Result<(), AppError> {
+    match command {
+        Command::Snapshot { check, config } => {
+            if !snapshot::run(config, *check)? {
+                process::exit(EXIT_SNAPSHOT_DRIFT);
+            }
+            Ok(())
+        }
+        Command::Decrypt { identity, file } => {
+            encrypt::decrypt(identity, file.as_deref(), &mut io::stdout().lock())
+        }
+        Command::VerifySignature { public_key, file } => {
+            if !sign::verify(file, public_key.as_deref())? {
+                process::exit(EXIT_SIGNATURE_INVALID);
+            }
+            Ok(())
+        }
+        Command::Apply { yes, dry_run, file } => apply::run(file.as_deref(), *yes, *dry_run),
+        Command::Cloc { options } => {
+            let mut options = Options::clone(options);
+            if !has_inputs(&options) {
+                options.paths.push(PathBuf::from("."));
+            }
+            if options.config.is_none() && Path::new(DEFAULT_CONFIG_FILE).is_file() {
+                options.config = Some(PathBuf::from(DEFAULT_CONFIG_FILE));
+            }
+            let ingestor = Ingestor::new(options);
+            match ingestor.write_language_stats(&mut io::stdout().lock()) {
+                Err(AppError::NoFilesSelected(diagnostic)) => {
+                    error!("{diagnostic}");
+                    process::exit(EXIT_NO_FILES_SELECTED);
+                }
+                result => result.map(|_| ()),
             }
-        } else {
-            return false; // No extension or invalid UTF-8 extension
         }
     }
+}
 
-    // Apply --ignore patterns specifically to files if --ignore-files-only is set
-    // Note: The `ignore` crate handles directory ignoring based on patterns automatically
-    // unless overridden. This check is mainly for the --ignore-files-only case where
-    // we might want to ignore a file *within* a directory that isn't itself ignored.
-    // The `ignore` crate's standard matching should cover most cases, but this adds
-    // an explicit file-only check if the flag is set.
-    if cli.ignore_files_only && !cli.ignore_patterns.is_empty() {
-        if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-            // We need a simple glob matcher here. The `ignore` crate doesn't directly
-            // expose its matcher easily for this specific file-only check after traversal.
-            // Using a basic `contains` or `starts_with`/`ends_with` might be sufficient
-            // for simple patterns, or pull in a glob crate if needed.
-            // For simplicity, let's just check if the filename *contains* any ignore pattern.
-            // A proper implementation would use glob matching.
-            // Example using `glob` crate (add `glob = "0.3"` to Cargo.toml):
-            // use glob::Pattern;
-            // if cli.ignore_patterns.iter().any(|pattern| Pattern::new(pattern).map_or(false, |p| p.matches(file_name))) {
-            //     return false;
-            // }
-            // Using contains as a placeholder:
-            if cli
-                .ignore_patterns
-                .iter()
-                .any(|pattern| file_name.contains(pattern))
-            {
-                // This is a basic check, consider using a proper glob matcher
-                // return false; // Uncomment if using contains is sufficient, or replace with glob logic
-            }
+/// Lowers the I/O and CPU priorities of the process with `ionice` and `renice`,
+/// warning where that isn't supported.
+fn lower_priority() {
+    let pid = process::id().to_string();
+    let commands: [(&str, &[&str]); 2] = [
+        ("ionice", &["-c", "3", "-p", &pid]),
+        ("renice", &["-n", "19", "-p", &pid]),
+    ];
+    for (program, args) in commands {
+        let status = process::Command::new(program)
+            .args(args)
+            .stdout(process::Stdio::null())
+            .status();
+        if !status.is_ok_and(|status| status.success()) {
+            warn!("Could not lower the priority with {program} (--nice-io)");
         }
     }
+}
 
-    true // Process this entry
+/// Whether inputs were given as arguments: paths, or options adding some.
+fn has_inputs(options: &Options) -> bool {
+    !options.paths.is_empty()
+        || !options.compare_dirs.is_empty()
+        || !options.workspace_members.is_empty()
+        || !options.packages.is_empty()
+        || !options.docker.is_empty()
+        || !options.ssh.is_empty()
 }
 
 /// Reads paths from standard input.
@@ -331,99 +531,3 @@ fn read_paths_from_stdin(paths: &mut Vec<PathBuf>, null_separator: bool) -> io::
     }
     Ok(())
 }
-
-/// Adds line numbers to the content string.
-fn add_line_numbers(content: &str) -> String {
-    let lines: Vec<&str> = content.lines().collect();
-    let num_lines = lines.len();
-    // Calculate padding needed for line numbers (e.g., 1, 10, 100)
-    let padding = if num_lines == 0 {
-        1
-    } else {
-        num_lines.to_string().len()
-    };
-
-    lines
-        .into_iter()
-        .enumerate()
-        .map(|(i, line)| format!("{:<width$}  {}", i + 1, line, width = padding))
-        .collect::<Vec<String>>()
-        .join("\n")
-}
-
-/// Prints a single file's content in the specified format.
-fn print_file(
-    writer: &mut dyn Write,
-    path: &Path,
-    content: &str,
-    cxml: bool,
-    markdown: bool,
-    line_numbers: bool,
-) -> io::Result<()> {
-    // Use relative path if possible for cleaner output, fallback to absolute
-    let display_path = path.strip_prefix(".").unwrap_or(path).display();
-
-    // Apply line numbers if requested *before* formatting
-    let processed_content = if line_numbers {
-        add_line_numbers(content)
-    } else {
-        content.to_string() // Keep original content if no line numbers
-    };
-
-    // --- Select Output Format ---
-    if cxml {
-        // Claude XML Format
-        let index = GLOBAL_INDEX.fetch_add(1, Ordering::SeqCst); // Increment and get previous value
-        writeln!(writer, "<document index=\"{index}\">")?;
-        writeln!(writer, "<source>{display_path}</source>")?; // Use relative path
-        writeln!(writer, "<document_content>")?;
-        // Basic XML escaping for content - replace '&', '<', '>'
-        let escaped_content = processed_content
-            .replace('&', "&amp;")
-            .replace('<', "&lt;")
-            .replace('>', "&gt;");
-        writeln!(writer, "{escaped_content}")?; // Write potentially line-numbered and escaped content
-        writeln!(writer, "</document_content>")?;
-        writeln!(writer, "</document>")?;
-    } else if markdown {
-        // Markdown Format
-        let lang = path
-            .extension()
-            .and_then(|ext| ext.to_str()) // Get extension as &str
-            .and_then(|ext_str| {
-                // Access the OnceLock, initializing it if this is the first time
-                EXT_TO_LANG
-                    .get_or_init(|| {
-                        // This closure runs only once to initialize the map
-                        // eprintln!("Initializing language map..."); // Optional debug print
-                        initialize_language_map() // Call the initializer function
-                        // Alternatively, put the HashMap creation logic directly here:
-                        // let mut m = HashMap::new(); /* ... inserts ... */ m
-                    })
-                    .get(ext_str.to_lowercase().as_str()) // Now get from the initialized HashMap
-            })
-            .unwrap_or(&""); // Get language tag or empty string
-
-        // Determine necessary backtick count (handle content with backticks)
-        let mut backticks = "```".to_string();
-        while processed_content.contains(&backticks) {
-            backticks.push('`');
-        }
-
-        writeln!(writer, "{display_path}")?; // File path (relative)
-        writeln!(writer, "{backticks}{lang}")?; // Opening fence with language tag
-        writeln!(writer, "{processed_content}")?; // File content (potentially line-numbered)
-        writeln!(writer, "{backticks}")?; // Closing fence
-        writeln!(writer)?; // Add a blank line for separation
-    } else {
-        // Default Format
-        writeln!(writer, "{display_path}")?; // File path (relative)
-        writeln!(writer, "---")?;
-        writeln!(writer, "{processed_content}")?; // File content (potentially line-numbered)
-        // writeln!(writer)?; // Original python version adds blank line here - removed for closer match
-        writeln!(writer, "---")?;
-        writeln!(writer)?; // Add blank line after the closing separator
-    }
-
-    Ok(())
-}
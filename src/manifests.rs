@@ -0,0 +1,19 @@
+//! Project manifests: the files declaring a project and its dependencies.
+
+use std::path::Path;
+
+/// File names of the recognized manifests.
+pub const MANIFESTS: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "go.mod",
+    "pyproject.toml",
+    "pom.xml",
+];
+
+/// Whether the file at `path` is a recognized project manifest.
+pub fn is_manifest(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| MANIFESTS.contains(&name))
+}
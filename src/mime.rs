@@ -0,0 +1,60 @@
+//! Selection by MIME type (`--mime`), sniffed from the content rather than
+//! guessed from the extension.
+//!
+//! Binary formats are recognized by their magic bytes; other files are
+//! `text/plain` if their beginning is valid UTF-8 without NUL bytes, and
+//! `application/octet-stream` otherwise.
+
+use crate::Options;
+use crate::provider::FileProvider;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// How much of a file is sniffed.
+const SNIFF_BYTES: u64 = 8192;
+
+/// Whether the file at `path` has a MIME type matching one of the --mime
+/// patterns (`text/plain`, `text/*`, `*/*`). Files that can't be read are
+/// kept, for the error to be reported when read.
+pub fn selects(provider: &dyn FileProvider, path: &Path, options: &Options) -> bool {
+    let Ok(mime) = sniff(provider, path) else {
+        return true;
+    };
+    options
+        .mime_types
+        .iter()
+        .any(|pattern| matches(pattern, mime))
+}
+
+/// The MIME type of the file at `path`, from its first bytes.
+pub fn sniff(provider: &dyn FileProvider, path: &Path) -> io::Result<&'static str> {
+    let mut head = Vec::new();
+    provider
+        .open(path)?
+        .take(SNIFF_BYTES)
+        .read_to_end(&mut head)?;
+    if let Some(kind) = infer::get(&head) {
+        return Ok(kind.mime_type());
+    }
+    let text = match std::str::from_utf8(&head) {
+        Ok(text) => !text.contains('\0'),
+        // Cut in the middle of a character by the sniffing limit
+        Err(e) => e.error_len().is_none() && !head[..e.valid_up_to()].contains(&0),
+    };
+    Ok(if text {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    })
+}
+
+/// Whether `mime` matches `pattern`, whose type or subtype may be `*`.
+fn matches(pattern: &str, mime: &str) -> bool {
+    let (Some((kind, subtype)), Some((mime_kind, mime_subtype))) =
+        (pattern.split_once('/'), mime.split_once('/'))
+    else {
+        return pattern.eq_ignore_ascii_case(mime);
+    };
+    (kind == "*" || kind.eq_ignore_ascii_case(mime_kind))
+        && (subtype == "*" || subtype.eq_ignore_ascii_case(mime_subtype))
+}
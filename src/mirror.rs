@@ -0,0 +1,22 @@
+//! The `--mirror` output: each processed file written to its path under
+//! `--output-dir`, to produce a sanitized copy of a tree with the filters.
+
+use crate::format::contained_path;
+use crate::{AppError, Ingestor, Summary};
+use std::fs;
+use std::path::Path;
+use tracing::info;
+
+/// Runs the ingestion, writing each document's content (filtered, but not
+/// formatted) to its path under `dir`.
+pub fn write(ingestor: &Ingestor, dir: &Path) -> Result<Summary, AppError> {
+    let summary = ingestor.ingest_with(|document| {
+        let path = dir.join(contained_path(&document.path));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, document.content)
+    })?;
+    info!("Wrote {} files to {}", summary.documents, dir.display());
+    Ok(summary)
+}
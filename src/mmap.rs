@@ -0,0 +1,59 @@
+//! Memory-mapped reads for big files.
+//!
+//! Mapping a file avoids copying its content into a heap buffer, which matters
+//! for files that are large but still formatted in memory.
+
+use memmap2::Mmap;
+use std::fs::{self, File};
+use std::io;
+use std::ops::Deref;
+use std::path::Path;
+
+/// Default size (in bytes) above which files are memory-mapped: 1 MiB.
+pub const DEFAULT_THRESHOLD: u64 = 1024 * 1024;
+
+/// The UTF-8 content of a file, either mapped into memory or read into a `String`.
+pub enum FileContent {
+    Mapped(Mmap),
+    Owned(String),
+}
+
+impl Deref for FileContent {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            // SAFETY: validated as UTF-8 in `read` when the map was created.
+            FileContent::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+            FileContent::Owned(content) => content,
+        }
+    }
+}
+
+/// Reads `path` as UTF-8, memory-mapping it if it is larger than `threshold` bytes.
+///
+/// Falls back to a regular read if the file cannot be mapped (e.g. on some
+/// network filesystems). Like `fs::read_to_string`, fails with
+/// [`io::ErrorKind::InvalidData`] if the content is not valid UTF-8.
+pub fn read(path: &Path, threshold: u64) -> io::Result<FileContent> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() > threshold
+        && let Some(map) = map_file(&file)
+    {
+        if std::str::from_utf8(&map).is_err() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "stream did not contain valid UTF-8",
+            ));
+        }
+        return Ok(FileContent::Mapped(map));
+    }
+    fs::read_to_string(path).map(FileContent::Owned)
+}
+
+fn map_file(file: &File) -> Option<Mmap> {
+    // SAFETY: the map is only read. If another process truncates the file while
+    // it is mapped, reads may fault; this is the accepted trade-off of mmap and
+    // the reason it is only used above a (configurable) size threshold.
+    unsafe { Mmap::map(file) }.ok()
+}
@@ -0,0 +1,97 @@
+//! Async API (`async` feature), for services running on a tokio runtime.
+//!
+//! Walking directories and reading files are blocking operations, so the
+//! ingestion runs on tokio's blocking thread pool and its results are handed
+//! back through channels: the async runtime itself is never blocked.
+
+use crate::{AppError, Document, Ingestor, Summary};
+use std::io::{self, Write};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Number of documents (or output chunks) buffered ahead of the consumer.
+const AHEAD: usize = 16;
+/// Size of the output chunks passed to async writers.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+impl Ingestor {
+    /// Runs the ingestion in the background, yielding each document once it is formatted.
+    ///
+    /// The stream ends after the last document, or with the error that stopped
+    /// the run. Dropping the stream cancels the run. Must be called from within
+    /// a tokio runtime.
+    pub fn stream(self) -> impl Stream<Item = Result<Document, AppError>> {
+        let (sender, receiver) = mpsc::channel(AHEAD);
+        tokio::task::spawn_blocking(move || {
+            let mut send = |document| {
+                sender
+                    .blocking_send(Ok(document))
+                    .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "stream was dropped"))
+            };
+            if let Err(e) = self.ingest_with(&mut send) {
+                let _ = sender.blocking_send(Err(e)); // Nobody is listening if the stream was dropped
+            }
+        });
+        ReceiverStream::new(receiver)
+    }
+
+    /// Runs the ingestion in the background, writing the output to an async `writer`.
+    ///
+    /// # Errors
+    ///
+    /// See [`Ingestor::ingest_to`].
+    pub async fn ingest_to_async<W>(self, writer: &mut W) -> Result<Summary, AppError>
+    where
+        W: AsyncWrite + Unpin + ?Sized,
+    {
+        let (sender, mut receiver) = mpsc::channel(AHEAD);
+        let run = tokio::task::spawn_blocking(move || {
+            let mut chunks = ChunkSender {
+                buffer: Vec::new(),
+                sender,
+            };
+            self.ingest_to(&mut chunks)
+        });
+
+        while let Some(chunk) = receiver.recv().await {
+            writer.write_all(&chunk).await?;
+        }
+        writer.flush().await?;
+
+        run.await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+}
+
+/// A writer passing its output to the async side in chunks.
+struct ChunkSender {
+    buffer: Vec<u8>,
+    sender: mpsc::Sender<Vec<u8>>,
+}
+
+impl ChunkSender {
+    fn send_buffer(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        self.sender
+            .blocking_send(std::mem::take(&mut self.buffer))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "writer was dropped"))
+    }
+}
+
+impl Write for ChunkSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buffer()
+    }
+}
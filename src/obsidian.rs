@@ -0,0 +1,85 @@
+//! The Obsidian vault export (`--format obsidian`): one Markdown note per file,
+//! with wiki-links to the files it references, so a repository can be browsed
+//! as a knowledge base (graph view, backlinks).
+
+use crate::format::contained_path;
+use crate::{AppError, Document, Ingestor, Summary, graph};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// Runs the ingestion, writing a note for each document under `dir`.
+///
+/// The notes need all the documents to link them, so these are held in memory.
+pub fn write(ingestor: &Ingestor, dir: &Path) -> Result<Summary, AppError> {
+    let mut documents = Vec::new();
+    let summary = ingestor.ingest_with(|document| {
+        documents.push(document);
+        Ok(())
+    })?;
+    for document in &documents {
+        let note = dir.join(note_path(&document.path));
+        if let Some(parent) = note.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&note, render(document, &documents))?;
+    }
+    info!("Wrote {} notes to {}", documents.len(), dir.display());
+    Ok(summary)
+}
+
+/// Where the note of the file at `path` goes in the vault: its path plus `.md`.
+fn note_path(path: &Path) -> PathBuf {
+    let mut note = contained_path(path);
+    note.as_mut_os_string().push(".md");
+    note
+}
+
+/// The note of `document`, with links to the other `documents` it references.
+fn render(document: &Document, documents: &[Document]) -> String {
+    let path = document.path.to_string_lossy();
+    let lang = document.language.as_deref().unwrap_or("");
+    let mut fence = "```".to_string();
+    while document.content.contains(&fence) {
+        fence.push('`');
+    }
+
+    let mut note = String::new();
+    let _ = writeln!(note, "---");
+    // JSON strings are valid YAML scalars, whatever characters the path holds
+    let _ = writeln!(note, "path: {}", serde_json::Value::from(path.as_ref()));
+    if !lang.is_empty() {
+        let _ = writeln!(note, "lang: {lang}");
+    }
+    let _ = writeln!(note, "---");
+    let _ = writeln!(note);
+    let _ = writeln!(note, "{fence}{lang}");
+    note.push_str(&document.content);
+    if !document.content.ends_with('\n') {
+        note.push('\n');
+    }
+    let _ = writeln!(note, "{fence}");
+
+    let links: Vec<&Document> = documents
+        .iter()
+        .filter(|other| {
+            other.path != document.path && graph::references(&document.content, &other.path)
+        })
+        .collect();
+    if !links.is_empty() {
+        let _ = writeln!(note);
+        let _ = writeln!(note, "## Links");
+        let _ = writeln!(note);
+        for other in links {
+            let target = note_path(&other.path);
+            let _ = writeln!(
+                note,
+                "- [[{}|{}]]",
+                target.to_string_lossy().replace('\\', "/"),
+                other.path.to_string_lossy()
+            );
+        }
+    }
+    note
+}
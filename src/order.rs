@@ -0,0 +1,219 @@
+//! Output order of the selected files (`--order`), and project entry points.
+
+use crate::format::display_path;
+use crate::graph::{self, GraphKind};
+use crate::provider::FileProvider;
+use crate::{AppError, Options};
+use clap::ValueEnum;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tracing::warn;
+
+/// How files are ordered in the output.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileOrder {
+    /// By path.
+    #[default]
+    Path,
+    /// Most frequently committed first, from the git history since `--churn-since`
+    /// (then by path), so that the most active files make it under an output limit.
+    Churn,
+    /// Project entry points first (see `--entry-glob`), then by path: the files
+    /// a reader would open first.
+    Smart,
+}
+
+/// Where projects usually start (gitignore syntax), unless `--entry-glob` is given.
+const DEFAULT_ENTRY_POINTS: &[&str] = &[
+    "main.rs",
+    "lib.rs",
+    "src/bin/",
+    "main.py",
+    "__main__.py",
+    "app.py",
+    "manage.py",
+    "index.ts",
+    "index.tsx",
+    "index.js",
+    "index.jsx",
+    "main.ts",
+    "main.js",
+    "server.ts",
+    "server.js",
+    "main.go",
+    "cmd/",
+    "Main.java",
+    "Program.cs",
+    "main.c",
+    "main.cpp",
+];
+
+/// Matches project entry points: `--entry-glob` patterns, or the usual file names.
+pub struct EntryPoints(Gitignore);
+
+impl EntryPoints {
+    /// Compiles the `--entry-glob` patterns, or the default ones.
+    pub fn new(options: &Options) -> Result<Self, AppError> {
+        let mut builder = GitignoreBuilder::new("");
+        if options.entry_globs.is_empty() {
+            for pattern in DEFAULT_ENTRY_POINTS {
+                builder.add_line(None, pattern)?;
+            }
+        }
+        for pattern in &options.entry_globs {
+            builder.add_line(None, pattern)?;
+        }
+        Ok(Self(builder.build()?))
+    }
+
+    /// Whether the file at `path` is an entry point.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.0
+            .matched_path_or_any_parents(display_path(path), false)
+            .is_ignore()
+    }
+}
+
+/// Extra priority of entry points, for packing (every file has a priority of 1).
+const ENTRY_POINT_PRIORITY: f64 = 4.0;
+
+/// Extra priority of a file per file referencing it with `--graph`, for packing.
+const REFERENCE_PRIORITY: f64 = 1.0;
+
+/// What files are ranked by: entry points, git activity with `--order churn`,
+/// and references from other files with `--graph`.
+pub struct Ranking {
+    order: FileOrder,
+    entry_points: EntryPoints,
+    commits: Option<Commits>,
+    /// Number of files referencing each file, with `--graph`.
+    references: HashMap<PathBuf, usize>,
+}
+
+impl Ranking {
+    /// Gathers what `--order` and `--pack` need.
+    pub fn new(options: &Options) -> Result<Self, AppError> {
+        Ok(Self {
+            order: options.order,
+            entry_points: EntryPoints::new(options)?,
+            commits: (options.order == FileOrder::Churn)
+                .then(|| Commits::load(&options.churn_since))
+                .flatten(),
+            references: HashMap::new(),
+        })
+    }
+
+    /// Counts the references between `files` in the graph of `kind`, so that
+    /// packing favors the files the others depend on.
+    pub fn count_references(
+        &mut self,
+        kind: GraphKind,
+        files: &[PathBuf],
+        provider: &dyn FileProvider,
+    ) {
+        let contents: Vec<(PathBuf, String)> = files
+            .iter()
+            .filter_map(|path| Some((path.clone(), provider.read_text(path).ok()?.to_string())))
+            .collect();
+        self.references = graph::in_degrees(kind, &contents);
+    }
+
+    /// Puts `files` (sorted by path) in the order selected by `--order`.
+    pub fn sort(&self, files: &mut [PathBuf]) {
+        match self.order {
+            FileOrder::Path => {}
+            FileOrder::Churn => {
+                if let Some(commits) = &self.commits {
+                    // Stable: files with as many commits stay in path order
+                    files.sort_by_cached_key(|path| std::cmp::Reverse(commits.of(path)));
+                }
+            }
+            FileOrder::Smart => {
+                files.sort_by_cached_key(|path| !self.entry_points.contains(path));
+            }
+        }
+    }
+
+    /// The priority of each file, for `--pack`: 1, plus a bonus for entry
+    /// points, with `--order churn`, the number of commits and, with `--graph`,
+    /// the number of files referencing it.
+    pub fn priorities(&self, files: &[PathBuf]) -> Vec<f64> {
+        files
+            .iter()
+            .map(|path| {
+                let mut priority = 1.0;
+                if self.entry_points.contains(path) {
+                    priority += ENTRY_POINT_PRIORITY;
+                }
+                if let Some(commits) = &self.commits {
+                    #[allow(clippy::cast_precision_loss)] // Commit counts are small
+                    let count = commits.of(path) as f64;
+                    priority += count;
+                }
+                if let Some(&count) = self.references.get(path) {
+                    #[allow(clippy::cast_precision_loss)] // File counts are small
+                    let count = count as f64;
+                    priority += REFERENCE_PRIORITY * count;
+                }
+                priority
+            })
+            .collect()
+    }
+}
+
+/// Commit counts per file, from the git history of the current directory.
+struct Commits {
+    /// Paths relative to the current directory -> number of commits.
+    counts: HashMap<PathBuf, usize>,
+    cwd: PathBuf,
+}
+
+impl Commits {
+    /// Counts the commits touching each file since `since`.
+    ///
+    /// Returns `None`, with a warning, if git is not available or this is not a repository.
+    fn load(since: &str) -> Option<Self> {
+        let output = Command::new("git")
+            .args([
+                "log",
+                "--format=",
+                "--name-only",
+                "--relative",
+                "--no-renames",
+            ])
+            .arg(format!("--since={since}"))
+            .output();
+        let output = match output {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                let error = String::from_utf8_lossy(&output.stderr);
+                warn!("Ignoring --order churn - git log failed: {}", error.trim());
+                return None;
+            }
+            Err(e) => {
+                warn!("Ignoring --order churn - could not run git: {e}");
+                return None;
+            }
+        };
+        let mut counts = HashMap::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if !line.is_empty() {
+                *counts.entry(Path::new(line).to_path_buf()).or_insert(0) += 1;
+            }
+        }
+        Some(Self {
+            counts,
+            cwd: env::current_dir().unwrap_or_default(),
+        })
+    }
+
+    /// The number of commits touching the file at `path`.
+    fn of(&self, path: &Path) -> usize {
+        let path = display_path(path);
+        let path = path.strip_prefix(&self.cwd).unwrap_or(path);
+        self.counts.get(path).copied().unwrap_or(0)
+    }
+}
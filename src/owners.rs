@@ -0,0 +1,74 @@
+//! Selection by code owner (`--owner`), from the repository's CODEOWNERS file.
+
+use crate::AppError;
+use crate::format::display_path;
+use crate::provider::FileProvider;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::Path;
+
+/// Where GitHub and GitLab look for the CODEOWNERS file, in order, relative to
+/// the repository root (the current directory).
+const LOCATIONS: &[&str] = &["CODEOWNERS", ".github/CODEOWNERS", "docs/CODEOWNERS"];
+
+/// The rules of a CODEOWNERS file: a path pattern and its owners.
+pub struct CodeOwners {
+    rules: Vec<(Gitignore, Vec<String>)>,
+}
+
+impl CodeOwners {
+    /// Reads the CODEOWNERS file from its usual locations.
+    ///
+    /// Fails if there is none, or if a pattern is invalid.
+    pub fn load(provider: &dyn FileProvider) -> Result<Self, AppError> {
+        let Some(content) = LOCATIONS
+            .iter()
+            .find_map(|location| provider.read_text(Path::new(location)).ok())
+        else {
+            return Err(AppError::Config(format!(
+                "--owner needs a CODEOWNERS file (looked for {})",
+                LOCATIONS.join(", ")
+            )));
+        };
+
+        let mut rules = Vec::new();
+        for line in content.lines() {
+            let line = line.trim();
+            // Skip comments and GitLab section headers
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let Some(pattern) = fields.next() else {
+                continue;
+            };
+            let mut builder = GitignoreBuilder::new("");
+            builder.add_line(None, pattern)?;
+            let owners = fields
+                .take_while(|field| !field.starts_with('#'))
+                .map(str::to_string)
+                .collect();
+            rules.push((builder.build()?, owners));
+        }
+        Ok(Self { rules })
+    }
+
+    /// The owners of the file at `path`: those of the last matching rule.
+    pub fn owners(&self, path: &Path) -> &[String] {
+        let path = display_path(path);
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern.matched_path_or_any_parents(path, false).is_ignore())
+            .map_or(&[], |(_, owners)| owners)
+    }
+
+    /// Whether the file at `path` is owned by one of `owners` (handles and
+    /// emails are case-insensitive).
+    pub fn is_owned_by(&self, path: &Path, owners: &[String]) -> bool {
+        self.owners(path).iter().any(|owner| {
+            owners
+                .iter()
+                .any(|wanted| wanted.eq_ignore_ascii_case(owner))
+        })
+    }
+}
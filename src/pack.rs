@@ -0,0 +1,111 @@
+//! Budget packing (`--pack`): choosing which files fit within `--max-tokens`.
+//!
+//! Without packing, files are added in output order until the budget runs out.
+//! Packing instead selects, before anything is read, the subset of files with
+//! the highest total priority (see [`Ranking::priorities`]) whose estimated size
+//! fits the budget; the selected files keep their output order.
+
+use crate::format::display_path;
+use crate::order::Ranking;
+use crate::provider::FileProvider;
+use crate::tokens;
+use clap::ValueEnum;
+use std::path::PathBuf;
+
+/// How `--pack` selects files.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackStrategy {
+    /// Take files by priority per token, as long as they fit: fast, usually close to the best.
+    Greedy,
+    /// Find the best selection (0/1 knapsack). Sizes are rounded on large inputs
+    /// to bound the work, which can leave a little of the budget unused.
+    Optimal,
+}
+
+/// Estimated framing around each file (path, separators or tags), in bytes.
+const FILE_OVERHEAD: usize = 64;
+
+/// Upper bound on the knapsack table (files × budget steps), to keep `optimal` fast.
+const MAX_TABLE_SIZE: usize = 20_000_000;
+
+/// Keeps the files of `files` that `--pack` selects within `--max-tokens`.
+pub fn select(
+    files: Vec<PathBuf>,
+    provider: &dyn FileProvider,
+    ranking: &Ranking,
+    strategy: PackStrategy,
+    budget: usize,
+) -> Vec<PathBuf> {
+    let costs: Vec<usize> = files
+        .iter()
+        .map(|path| {
+            // Files that can't be read are skipped later on, at no cost
+            let len = provider
+                .len(path)
+                .map_or(0, |len| usize::try_from(len).unwrap_or(usize::MAX));
+            let framing = display_path(path).as_os_str().len() + FILE_OVERHEAD;
+            len.saturating_add(framing)
+                .div_ceil(tokens::BYTES_PER_TOKEN)
+        })
+        .collect();
+    let priorities = ranking.priorities(&files);
+
+    let selected = match strategy {
+        PackStrategy::Greedy => greedy(&costs, &priorities, budget),
+        PackStrategy::Optimal => optimal(&costs, &priorities, budget),
+    };
+    files
+        .into_iter()
+        .zip(selected)
+        .filter_map(|(path, selected)| selected.then_some(path))
+        .collect()
+}
+
+fn greedy(costs: &[usize], priorities: &[f64], budget: usize) -> Vec<bool> {
+    let mut by_density: Vec<usize> = (0..costs.len()).collect();
+    #[allow(clippy::cast_precision_loss)] // Only a ranking
+    let density = |i: usize| priorities[i] / costs[i].max(1) as f64;
+    by_density.sort_by(|&a, &b| density(b).total_cmp(&density(a)));
+
+    let mut selected = vec![false; costs.len()];
+    let mut left = budget;
+    for i in by_density {
+        if costs[i] <= left {
+            left -= costs[i];
+            selected[i] = true;
+        }
+    }
+    selected
+}
+
+fn optimal(costs: &[usize], priorities: &[f64], budget: usize) -> Vec<bool> {
+    let n = costs.len();
+    // Round sizes up to a coarser unit if the table would be too large: the
+    // selection still fits, but may not be the very best one
+    let unit = (n.saturating_mul(budget + 1) / MAX_TABLE_SIZE).max(1);
+    let capacity = budget / unit;
+    let costs: Vec<usize> = costs.iter().map(|cost| cost.div_ceil(unit)).collect();
+
+    // best[c]: highest total priority within c units; taken[i] records the choices
+    let mut best = vec![0.0_f64; capacity + 1];
+    let mut taken = vec![vec![false; capacity + 1]; n];
+    for i in 0..n {
+        for c in (costs[i]..=capacity).rev() {
+            let with = best[c - costs[i]] + priorities[i];
+            if with > best[c] {
+                best[c] = with;
+                taken[i][c] = true;
+            }
+        }
+    }
+
+    let mut selected = vec![false; n];
+    let mut c = capacity;
+    for i in (0..n).rev() {
+        if taken[i][c] {
+            selected[i] = true;
+            c -= costs[i];
+        }
+    }
+    selected
+}
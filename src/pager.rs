@@ -0,0 +1,63 @@
+//! Pager integration, so large previews don't flood the terminal.
+
+use clap::ValueEnum;
+use std::env;
+use std::process::{Child, Command, Stdio};
+use tracing::warn;
+
+/// When to send the output through a pager.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PagerMode {
+    /// Page when writing to a terminal, quitting immediately if the output fits on one screen.
+    #[default]
+    Auto,
+    /// Always page when writing to a terminal.
+    Always,
+    /// Never page.
+    Never,
+}
+
+/// Spawns the pager named by `$PAGER` (falling back to `less`) with a piped stdin.
+///
+/// Like git, `$PAGER` is a shell command on unix, so it may quote its arguments;
+/// elsewhere it is split on whitespace.
+///
+/// Returns `None` if no pager should be used or it could not be started, in
+/// which case output should go straight to stdout.
+pub fn spawn(mode: PagerMode) -> Option<Child> {
+    if mode == PagerMode::Never || !atty::is(atty::Stream::Stdout) {
+        return None;
+    }
+
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    let mut words = pager.split_whitespace();
+    let program = words.next()?; // An empty $PAGER disables paging
+    if pager.trim() == "cat" {
+        return None;
+    }
+
+    let mut command = if cfg!(unix) {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&pager);
+        command
+    } else {
+        let mut command = Command::new(program);
+        command.args(words);
+        command
+    };
+    command.stdin(Stdio::piped());
+    // Like git, give `less` sensible defaults unless the user configured it:
+    // -R passes colors through, -X keeps the output on screen after quitting,
+    // and in auto mode -F exits straight away if everything fits on one screen.
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", if mode == PagerMode::Auto { "FRX" } else { "RX" });
+    }
+
+    match command.spawn() {
+        Ok(child) => Some(child),
+        Err(e) => {
+            warn!("Could not start pager '{pager}': {e}");
+            None
+        }
+    }
+}
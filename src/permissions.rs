@@ -0,0 +1,112 @@
+//! Selection by filesystem permissions (`--executable-only`, `--writable-only`
+//! and `--mode`), to pick out scripts or deployable artifacts.
+//!
+//! Outside of Unix there are no permission bits: files are readable, writable
+//! unless read-only, and executable if their extension says so (`.exe`, `.bat`, ...).
+
+use crate::Options;
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Permission requirements, as in `u+x` or `go-w,a+r` (all must hold).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModeSpec {
+    /// Mode bits that must all be set.
+    set: u32,
+    /// Mode bits that must all be clear.
+    clear: u32,
+}
+
+impl FromStr for ModeSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut mode = ModeSpec { set: 0, clear: 0 };
+        for clause in spec.split(',') {
+            let invalid = || format!("expected a mode like u+x or go-w, found '{clause}'");
+            let operator = clause.find(['+', '-']).ok_or_else(invalid)?;
+            let (who, permissions) = (&clause[..operator], &clause[operator + 1..]);
+            if permissions.is_empty() {
+                return Err(invalid());
+            }
+            // The classes' shifts: user bits are 0o700, group 0o070, other 0o007
+            let mut shifts = Vec::new();
+            for class in who.chars() {
+                match class {
+                    'u' => shifts.push(6),
+                    'g' => shifts.push(3),
+                    'o' => shifts.push(0),
+                    'a' => shifts.extend([6, 3, 0]),
+                    _ => return Err(invalid()),
+                }
+            }
+            if shifts.is_empty() {
+                shifts.extend([6, 3, 0]); // As with chmod, no class means all of them
+            }
+            let mut bits = 0;
+            for permission in permissions.chars() {
+                let bit = match permission {
+                    'r' => 0o4,
+                    'w' => 0o2,
+                    'x' => 0o1,
+                    _ => return Err(invalid()),
+                };
+                bits |= shifts.iter().fold(0, |bits, shift| bits | bit << shift);
+            }
+            if clause.as_bytes()[operator] == b'+' {
+                mode.set |= bits;
+            } else {
+                mode.clear |= bits;
+            }
+        }
+        Ok(mode)
+    }
+}
+
+/// Whether the permission filters are in use.
+pub fn is_filtering(options: &Options) -> bool {
+    options.executable_only || options.writable_only || options.mode.is_some()
+}
+
+/// Whether the file at `path` passes the permission filters. Files whose
+/// permissions can't be read (such as in-memory ones) don't.
+pub fn selects(path: &Path, options: &Options) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    let mode = mode_of(path, &metadata);
+    (!options.executable_only || mode & 0o111 != 0)
+        && (!options.writable_only || mode & 0o222 != 0)
+        && options
+            .mode
+            .as_ref()
+            .is_none_or(|spec| mode & spec.set == spec.set && mode & spec.clear == 0)
+}
+
+#[cfg(unix)]
+fn mode_of(_path: &Path, metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode()
+}
+
+#[cfg(not(unix))]
+fn mode_of(path: &Path, metadata: &Metadata) -> u32 {
+    const EXECUTABLE: &[&str] = &["exe", "com", "bat", "cmd", "ps1"];
+    let mut mode = 0o444;
+    if !metadata.permissions().readonly() {
+        mode |= 0o222;
+    }
+    let executable = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            EXECUTABLE
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known))
+        });
+    if executable {
+        mode |= 0o111;
+    }
+    mode
+}
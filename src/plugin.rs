@@ -0,0 +1,158 @@
+//! External plugins: `files-ingest-<name>` executables on `PATH`, like cargo subcommands.
+//!
+//! Plugins talk JSON over stdin/stdout and are invoked in one of two ways:
+//!
+//! - `files-ingest-<name> filter`, once per file (`--filter plugin=<name>`):
+//!   receives a [`Document`] and answers with the (possibly changed) document,
+//!   or `null` to leave the file out.
+//! - `files-ingest-<name> format`, once per run (`--format-plugin <name>`):
+//!   receives the array of all documents and writes the whole output.
+
+use crate::filter::{ContentFilter, FilterDecision};
+use crate::{AppError, Document};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use tracing::warn;
+
+/// Prefix of plugin executable names.
+const PREFIX: &str = "files-ingest-";
+
+/// Finds the executable of plugin `name` on `PATH`.
+#[must_use]
+pub fn find_plugin(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{PREFIX}{name}{}", env::consts::EXE_SUFFIX);
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| is_executable(path))
+}
+
+/// Lists the plugins found on `PATH`, by name, with their executables.
+///
+/// When several directories provide the same plugin, the first one wins, as when running it.
+#[must_use]
+pub fn list_plugins() -> Vec<(String, PathBuf)> {
+    let mut plugins: Vec<(String, PathBuf)> = Vec::new();
+    for dir in env::split_paths(&env::var_os("PATH").unwrap_or_default()) {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut found: Vec<_> = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let file_name = path.file_name()?.to_str()?;
+                let name = file_name
+                    .strip_prefix(PREFIX)?
+                    .strip_suffix(env::consts::EXE_SUFFIX)?
+                    .to_string();
+                (!name.is_empty() && is_executable(&path)).then_some((name, path))
+            })
+            .filter(|(name, _)| !plugins.iter().any(|(known, _)| known == name))
+            .collect();
+        plugins.append(&mut found);
+    }
+    plugins.sort_by(|a, b| a.0.cmp(&b.0));
+    plugins
+}
+
+/// A filter running each document through a plugin.
+///
+/// If the plugin fails, a warning is printed and the document is kept as it was.
+#[derive(Debug, Clone)]
+pub struct PluginFilter {
+    name: String,
+    program: PathBuf,
+}
+
+impl PluginFilter {
+    /// Creates a filter for plugin `name`, if it can be found on `PATH`.
+    #[must_use]
+    pub fn new(name: &str) -> Option<Self> {
+        Some(Self {
+            name: name.to_string(),
+            program: find_plugin(name)?,
+        })
+    }
+}
+
+impl ContentFilter for PluginFilter {
+    fn apply(&self, doc: &mut Document) -> FilterDecision {
+        let result = serde_json::to_vec(&*doc)
+            .map_err(io::Error::from)
+            .and_then(|input| run(&self.program, "filter", &input))
+            .and_then(|output| {
+                serde_json::from_slice::<Option<Document>>(&output).map_err(io::Error::from)
+            });
+        match result {
+            Ok(Some(filtered)) => {
+                *doc = filtered;
+                FilterDecision::Keep
+            }
+            Ok(None) => FilterDecision::Skip,
+            Err(e) => {
+                warn!(
+                    "Plugin {} failed on {} - {e}",
+                    self.name,
+                    doc.path.display()
+                );
+                FilterDecision::Keep
+            }
+        }
+    }
+}
+
+/// Has plugin `name` format `documents`, writing its output to `writer`.
+pub(crate) fn write_format(
+    name: &str,
+    documents: &[Document],
+    writer: &mut dyn Write,
+) -> Result<(), AppError> {
+    let program = find_plugin(name).ok_or_else(|| not_found(name))?;
+    let input = serde_json::to_vec(documents).map_err(io::Error::from)?;
+    let output =
+        run(&program, "format", &input).map_err(|e| AppError::Plugin(format!("{name}: {e}")))?;
+    writer.write_all(&output)?;
+    Ok(())
+}
+
+/// The error for a plugin missing from `PATH`.
+pub(crate) fn not_found(name: &str) -> AppError {
+    AppError::Plugin(format!("no {PREFIX}{name} executable found on PATH"))
+}
+
+/// Runs `program` with `input` on stdin, returning its stdout.
+fn run(program: &Path, mode: &str, input: &[u8]) -> io::Result<Vec<u8>> {
+    let mut child = Command::new(program)
+        .arg(mode)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // Write from another thread: the plugin may start answering before it has read everything
+    let output = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || stdin.write_all(input));
+        let output = child.wait_with_output();
+        match writer.join() {
+            Ok(Err(e)) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+            _ => output,
+        }
+    })?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("exited with {}", output.status)));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
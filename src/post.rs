@@ -0,0 +1,71 @@
+//! HTTP delivery of the output (`--post`), streamed as a request body through `curl`.
+
+use files_ingest::AppError;
+use std::io::{self, Write};
+use std::process::{Child, Command, Stdio};
+use tempfile::NamedTempFile;
+
+/// A request in progress.
+pub struct Request {
+    /// The `curl` process sending the request, reading the body from its stdin.
+    pub curl: Child,
+    /// The private file holding the extra headers, kept until the request is over.
+    _headers: Option<NamedTempFile>,
+}
+
+/// Spawns `curl` to POST its stdin to `url` with the extra `headers` (`Name: value`).
+///
+/// The body is sent as it is written (chunked), with a `text/plain` content
+/// type unless `headers` set another; the response goes to stdout. The headers
+/// (which may hold credentials) are passed in a file only the current user can
+/// read, rather than on curl's command line where any user could see them.
+pub fn spawn(url: &str, headers: &[String]) -> Result<Request, AppError> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--fail-with-body", "--location"])
+        .args(["--request", "POST", "--upload-file", "-"]);
+    let has_content_type = headers.iter().any(|header| {
+        header
+            .split_once(':')
+            .is_some_and(|(name, _)| name.trim().eq_ignore_ascii_case("content-type"))
+    });
+    if !has_content_type {
+        command.args(["--header", "Content-Type: text/plain; charset=utf-8"]);
+    }
+    let headers_file = if headers.is_empty() {
+        None
+    } else {
+        let mut file = tempfile::Builder::new()
+            .prefix("files-ingest-")
+            .suffix(".headers")
+            .tempfile()?;
+        for header in headers {
+            writeln!(file, "{header}")?;
+        }
+        file.flush()?;
+        let mut argument = std::ffi::OsString::from("@");
+        argument.push(file.path());
+        command.arg("--header").arg(argument);
+        Some(file)
+    };
+    let curl = command
+        .arg("--")
+        .arg(url)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| io::Error::other(format!("could not run curl for --post: {e}")))?;
+    Ok(Request {
+        curl,
+        _headers: headers_file,
+    })
+}
+
+/// Waits for the request to complete, failing if it did not succeed.
+pub fn finish(mut request: Request) -> Result<(), AppError> {
+    let status = request.curl.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("--post failed: curl exited with {status}")).into())
+    }
+}
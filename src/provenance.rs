@@ -0,0 +1,164 @@
+//! The run headers recording how a bundle was made: that of `--embed-command`,
+//! and the `<metadata>` element of `--cxml-header`.
+
+use crate::format::{display_path, escape_xml, language};
+use crate::provider::FileProvider;
+use crate::{Options, git};
+use std::collections::BTreeMap;
+use std::env;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Writes the header recording `command_line`, the version, the time and the
+/// commit of the first input path's repository.
+///
+/// Claude XML gets a `<run>` element (XML comments can't hold the `--` of
+/// options), Markdown an HTML comment and the default format `#` lines.
+pub fn write_header(
+    writer: &mut dyn Write,
+    options: &Options,
+    command_line: &[String],
+) -> io::Result<()> {
+    let dir = options.paths.first().map(|path| git::dir_of(path));
+    let commit = dir.and_then(|dir| {
+        let commit = git::commit(dir)?;
+        Some(if git::is_dirty(dir) {
+            format!("{commit} (with uncommitted changes)")
+        } else {
+            commit
+        })
+    });
+    let fields = [
+        ("version", Some(env!("CARGO_PKG_VERSION").to_string())),
+        ("command", Some(quote_command_line(command_line))),
+        ("generated", Some(timestamp())),
+        ("commit", commit),
+    ];
+    let fields = fields
+        .iter()
+        .filter_map(|(name, value)| Some((*name, value.as_deref()?)));
+
+    if options.cxml {
+        writeln!(writer, "<run>")?;
+        for (name, value) in fields {
+            writeln!(writer, "<{name}>{}</{name}>", escape_xml(value))?;
+        }
+        writeln!(writer, "</run>")
+    } else if options.markdown {
+        writeln!(writer, "<!--")?;
+        for (name, value) in fields {
+            writeln!(writer, "{name}: {value}")?;
+        }
+        writeln!(writer, "-->")?;
+        writeln!(writer)
+    } else {
+        for (name, value) in fields {
+            writeln!(writer, "# {name}: {value}")?;
+        }
+        writeln!(writer)
+    }
+}
+
+/// Writes the `<metadata>` element of --cxml-header: the time, the version, and
+/// a summary of the selection (input paths, number of files, their size and
+/// their languages).
+pub fn write_cxml_metadata(
+    writer: &mut dyn Write,
+    options: &Options,
+    files: &[PathBuf],
+    provider: &dyn FileProvider,
+) -> io::Result<()> {
+    let bytes: u64 = files
+        .iter()
+        .filter_map(|path| provider.len(path).ok())
+        .sum();
+    let mut languages: BTreeMap<&str, usize> = BTreeMap::new();
+    for path in files {
+        *languages
+            .entry(language(path).unwrap_or("other"))
+            .or_default() += 1;
+    }
+    let languages = languages
+        .iter()
+        .map(|(language, count)| format!("{language}: {count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    writeln!(writer, "<metadata>")?;
+    writeln!(writer, "<generated>{}</generated>", timestamp())?;
+    writeln!(writer, "<version>{}</version>", env!("CARGO_PKG_VERSION"))?;
+    for path in &options.paths {
+        let shown = display_path(path);
+        let path = if shown.as_os_str().is_empty() {
+            path.to_string_lossy() // `.`
+        } else {
+            shown.to_string_lossy()
+        };
+        writeln!(writer, "<input>{}</input>", escape_xml(&path))?;
+    }
+    writeln!(writer, "<files>{}</files>", files.len())?;
+    writeln!(writer, "<bytes>{bytes}</bytes>")?;
+    writeln!(writer, "<languages>{languages}</languages>")?;
+    writeln!(writer, "</metadata>")
+}
+
+/// Joins the arguments of a command line, quoting those the shell would split or expand.
+fn quote_command_line(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            let plain = !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c));
+            if plain {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The current UTC time as `YYYY-MM-DDTHH:MM:SSZ`, or that of `SOURCE_DATE_EPOCH`
+/// for reproducible output.
+pub fn timestamp() -> String {
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs())
+        });
+    format_timestamp(seconds)
+}
+
+/// Formats `seconds` since the Unix epoch as `YYYY-MM-DDTHH:MM:SSZ`.
+pub fn format_timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86_400, seconds % 86_400);
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Converts days since 1970-01-01 to a (year, month, day) date, after Howard
+/// Hinnant's `civil_from_days`.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
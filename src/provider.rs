@@ -0,0 +1,237 @@
+//! Where files come from: the filesystem, or an in-memory file tree.
+//!
+//! The pipeline only touches files through [`FileProvider`], so it also runs
+//! where there is no filesystem (e.g. `wasm32-unknown-unknown` in a browser)
+//! against a [`MemoryFiles`] tree.
+
+use crate::mmap::{self, FileContent};
+use crate::{AppError, Options, walk};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// A source of files to ingest.
+pub trait FileProvider: Send + Sync {
+    /// Whether `path` exists, as a file or a directory.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Lists the files selected by `options` under `options.paths`, in output order.
+    ///
+    /// # Errors
+    ///
+    /// Fails on invalid `--ignore` patterns, or on walk errors when asked to.
+    fn collect_files(&self, options: &Options, threads: usize) -> Result<Vec<PathBuf>, AppError>;
+
+    /// Size of a file in bytes.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be accessed.
+    fn len(&self, path: &Path) -> io::Result<u64>;
+
+    /// Reads a whole file.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be read.
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>>;
+
+    /// Reads a whole file as UTF-8 text.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be read, with [`io::ErrorKind::InvalidData`] if
+    /// it is not valid UTF-8.
+    fn read_text(&self, path: &Path) -> io::Result<FileContent>;
+
+    /// Opens a file for reading in chunks.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the file cannot be opened.
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>>;
+}
+
+/// Files on the local filesystem, selected with the `ignore` crate's walker.
+#[derive(Debug, Clone, Copy)]
+pub struct FsProvider {
+    mmap_threshold: u64,
+}
+
+impl FsProvider {
+    /// Files larger than `mmap_threshold` bytes are memory-mapped when read.
+    #[must_use]
+    pub fn new(mmap_threshold: u64) -> Self {
+        Self { mmap_threshold }
+    }
+}
+
+impl Default for FsProvider {
+    fn default() -> Self {
+        Self::new(mmap::DEFAULT_THRESHOLD)
+    }
+}
+
+impl FileProvider for FsProvider {
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn collect_files(&self, options: &Options, threads: usize) -> Result<Vec<PathBuf>, AppError> {
+        Ok(walk::collect_files(options, threads)?)
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        fs::metadata(path).map(|metadata| metadata.len())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        fs::read(path).map(Cow::Owned)
+    }
+
+    fn read_text(&self, path: &Path) -> io::Result<FileContent> {
+        mmap::read(path, self.mmap_threshold)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(File::open(path)?))
+    }
+}
+
+/// An in-memory file tree.
+///
+/// Files are selected like on disk: hidden files, `--extension`, `--ignore` and
+/// `.gitignore` files in the tree are honored. Unlike git, a `.gitignore` cannot
+/// re-include (`!pattern`) a file ignored by a `.gitignore` in a parent directory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFiles {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryFiles {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file, replacing any file already at `path`.
+    pub fn insert(&mut self, path: impl Into<PathBuf>, content: impl Into<Vec<u8>>) {
+        self.files.insert(path.into(), content.into());
+    }
+
+    fn get(&self, path: &Path) -> io::Result<&[u8]> {
+        self.files
+            .get(path)
+            .map(Vec::as_slice)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file not found"))
+    }
+
+    /// The paths under `root` (a file or a directory), in order.
+    fn paths_under<'a>(&'a self, root: &'a Path) -> impl Iterator<Item = &'a PathBuf> {
+        let root = tree_path(root);
+        self.files.keys().filter(move |path| path.starts_with(root))
+    }
+
+    /// Builds a matcher for each `.gitignore` file in the tree.
+    fn gitignores(&self) -> Result<Vec<Gitignore>, ignore::Error> {
+        let mut gitignores = Vec::new();
+        for (path, content) in &self.files {
+            if path.file_name().is_some_and(|name| name == ".gitignore") {
+                let mut builder = GitignoreBuilder::new(path.parent().unwrap_or(Path::new("")));
+                for line in String::from_utf8_lossy(content).lines() {
+                    builder.add_line(Some(path.clone()), line)?;
+                }
+                gitignores.push(builder.build()?);
+            }
+        }
+        Ok(gitignores)
+    }
+}
+
+impl FileProvider for MemoryFiles {
+    fn exists(&self, path: &Path) -> bool {
+        self.paths_under(path).next().is_some()
+    }
+
+    fn collect_files(&self, options: &Options, _threads: usize) -> Result<Vec<PathBuf>, AppError> {
+        let gitignores = if options.ignore_gitignore {
+            Vec::new()
+        } else {
+            self.gitignores()?
+        };
+
+        let mut files = Vec::new();
+        for root in &options.paths {
+            let mut patterns = GitignoreBuilder::new(tree_path(root));
+            for pattern in &options.ignore_patterns {
+                patterns.add_line(None, pattern)?;
+            }
+            let patterns = patterns.build()?;
+            let mut ignore_files = GitignoreBuilder::new(tree_path(root));
+            for path in &options.ignore_files {
+                if let Some(err) = ignore_files.add(path) {
+                    return Err(err.into());
+                }
+            }
+            let ignore_files = ignore_files.build()?;
+            let hidden_allowlist = walk::HiddenAllowlist::new(
+                &options.include_hidden_patterns,
+                &[tree_path(root).to_path_buf()],
+            )?;
+
+            for path in self.paths_under(root) {
+                if path == tree_path(root) && !options.no_force_explicit {
+                    files.push(path.clone()); // A file passed explicitly
+                    continue;
+                }
+                let ignored = |gitignore: &Gitignore| {
+                    path.starts_with(gitignore.path())
+                        && gitignore
+                            .matched_path_or_any_parents(path, false)
+                            .is_ignore()
+                };
+                let ignored_by_patterns = if options.ignore_files_only {
+                    patterns.matched(path, false).is_ignore()
+                } else {
+                    ignored(&patterns)
+                };
+                if (!options.include_hidden && !hidden_allowlist.allows(path, false))
+                    || ignored_by_patterns
+                    || ignored(&ignore_files)
+                    || gitignores.iter().any(ignored)
+                    || !walk::has_included_extension(path, options)
+                {
+                    continue;
+                }
+                files.push(path.clone());
+            }
+        }
+        Ok(files)
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        self.get(path).map(|content| content.len() as u64)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        self.get(path).map(Cow::Borrowed)
+    }
+
+    fn read_text(&self, path: &Path) -> io::Result<FileContent> {
+        let content = String::from_utf8(self.get(path)?.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(FileContent::Owned(content))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(self.get(path)?))
+    }
+}
+
+/// Returns `path` as stored in the tree: "." is the top of the tree.
+fn tree_path(path: &Path) -> &Path {
+    path.strip_prefix(".").unwrap_or(path)
+}
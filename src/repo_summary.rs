@@ -0,0 +1,117 @@
+//! The overview document of `--repo-summary`, giving orientation before the files.
+
+use crate::git;
+use crate::units::Units;
+use ignore::WalkBuilder;
+use std::fmt::Write;
+use std::path::Path;
+
+/// The path the overview document is shown under.
+pub const PATH: &str = "(repository summary)";
+
+/// Files identifying a build system, with its name.
+const BUILD_FILES: &[(&str, &str)] = &[
+    ("Cargo.toml", "Cargo"),
+    ("package.json", "npm"),
+    ("go.mod", "Go modules"),
+    ("pyproject.toml", "Python (pyproject.toml)"),
+    ("setup.py", "Python (setuptools)"),
+    ("requirements.txt", "Python (pip)"),
+    ("pom.xml", "Maven"),
+    ("build.gradle", "Gradle"),
+    ("build.gradle.kts", "Gradle"),
+    ("CMakeLists.txt", "CMake"),
+    ("meson.build", "Meson"),
+    ("MODULE.bazel", "Bazel"),
+    ("WORKSPACE", "Bazel"),
+    ("Makefile", "Make"),
+    ("Gemfile", "Bundler"),
+    ("composer.json", "Composer"),
+    ("mix.exs", "Mix"),
+    ("Package.swift", "Swift Package Manager"),
+    ("deno.json", "Deno"),
+];
+
+/// Writes the overview of the repository holding `path` (or of `path` itself
+/// outside of a repository): git remote, branch and commit, build systems and
+/// top-level entries with their sizes.
+pub fn summarize(path: &Path, units: &Units) -> String {
+    let dir = git::dir_of(path);
+    let top = git::toplevel(dir).map_or_else(|| dir.to_path_buf(), Into::into);
+    let mut summary = String::new();
+    let _ = writeln!(summary, "Directory: {}", top.display());
+    if let Some(remote) = git::remote_url(dir) {
+        let _ = writeln!(summary, "Remote: {remote}");
+    }
+    if let Some(branch) = git::branch(dir) {
+        let _ = writeln!(summary, "Branch: {branch}");
+    }
+    if let Some(commit) = git::commit(dir) {
+        let status = if git::is_dirty(dir) {
+            "uncommitted changes"
+        } else {
+            "clean"
+        };
+        let _ = writeln!(summary, "Commit: {commit} ({status})");
+    }
+
+    let entries: Vec<_> = WalkBuilder::new(&top)
+        .max_depth(Some(1))
+        .sort_by_file_name(Ord::cmp)
+        .build()
+        .flatten()
+        .filter(|entry| entry.depth() == 1)
+        .collect();
+    let build_systems: Vec<String> = BUILD_FILES
+        .iter()
+        .filter(|(file, _)| entries.iter().any(|entry| entry.file_name() == *file))
+        .map(|(file, name)| format!("{name} ({file})"))
+        .collect();
+    if !build_systems.is_empty() {
+        let _ = writeln!(summary, "Build system: {}", build_systems.join(", "));
+    }
+
+    let _ = writeln!(summary, "\nTop-level entries:");
+    let names: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            match entry.file_type() {
+                Some(ft) if ft.is_dir() => format!("{name}/"),
+                _ => name.into_owned(),
+            }
+        })
+        .collect();
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (entry, name) in entries.iter().zip(&names) {
+        if entry.file_type().is_some_and(|ft| ft.is_dir()) {
+            let (files, bytes) = tree_size(entry.path());
+            let plural = if files == 1 { "" } else { "s" };
+            let (size, files_count) = (units.size(bytes), units.count(files as u64));
+            let _ = writeln!(
+                summary,
+                "  {name:<width$}  {size} in {files_count} file{plural}"
+            );
+        } else {
+            let size = units.size(entry.metadata().map_or(0, |metadata| metadata.len()));
+            let _ = writeln!(summary, "  {name:<width$}  {size}");
+        }
+    }
+    summary
+}
+
+/// Counts the files under `dir` (skipping hidden and ignored ones) and their total size.
+fn tree_size(dir: &Path) -> (usize, u64) {
+    WalkBuilder::new(dir)
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|ft| ft.is_file()))
+        .fold((0, 0), |(files, bytes), entry| {
+            let len = entry.metadata().map_or(0, |metadata| metadata.len());
+            (files + 1, bytes + len)
+        })
+}
@@ -0,0 +1,84 @@
+//! Head/tail sampling of large files (`--sample-large`).
+//!
+//! The beginning of a file (imports, declarations) and its end (exports, the
+//! latest log lines) usually matter most, so oversized files keep those and
+//! lose the middle, with a marker saying how much was left out.
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Read};
+use std::str::FromStr;
+
+/// How many lines to keep from each end, as in `head=N,tail=M`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleSpec {
+    pub head: usize,
+    pub tail: usize,
+}
+
+impl FromStr for SampleSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut sample = SampleSpec { head: 0, tail: 0 };
+        for part in spec.split(',') {
+            let (name, count) = part
+                .split_once('=')
+                .ok_or_else(|| format!("expected head=N,tail=M, found '{part}'"))?;
+            let count = count
+                .parse()
+                .map_err(|_| format!("'{name}' needs a number of lines, found '{count}'"))?;
+            match name {
+                "head" => sample.head = count,
+                "tail" => sample.tail = count,
+                _ => return Err(format!("unknown part '{name}' (expected head or tail)")),
+            }
+        }
+        Ok(sample)
+    }
+}
+
+/// Samples `content` down to its first and last lines, if it has more than that.
+pub fn sample_lines(content: &str, spec: SampleSpec) -> Option<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    // Replacing a single line with the marker would save nothing (and this way,
+    // an already sampled file stays as it is)
+    if lines.len() <= spec.head + spec.tail + 1 {
+        return None;
+    }
+    let omitted = lines.len() - spec.head - spec.tail;
+    let mut sampled = lines[..spec.head].to_vec();
+    let marker = marker(omitted);
+    sampled.push(&marker);
+    sampled.extend_from_slice(&lines[lines.len() - spec.tail..]);
+    Some(sampled.join("\n"))
+}
+
+/// Samples a file as it is read, holding only the lines it keeps.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the content is not valid UTF-8.
+pub fn sample_reader(reader: impl Read, spec: SampleSpec) -> io::Result<String> {
+    let mut head = Vec::with_capacity(spec.head);
+    let mut tail = VecDeque::with_capacity(spec.tail + 1);
+    let mut omitted = 0;
+    for line in BufReader::new(reader).lines() {
+        let line = line?;
+        if head.len() < spec.head {
+            head.push(line);
+            continue;
+        }
+        tail.push_back(line);
+        if tail.len() > spec.tail {
+            tail.pop_front();
+            omitted += 1;
+        }
+    }
+    if omitted > 0 {
+        head.push(marker(omitted));
+    }
+    head.extend(tail);
+    Ok(head.join("\n"))
+}
+
+fn marker(omitted: usize) -> String {
+    format!("[... {omitted} lines omitted ...]")
+}
@@ -0,0 +1,30 @@
+//! Private scratch directories: random names (so they can't be guessed or
+//! collide), created only if they don't exist yet, readable by the current
+//! user alone, and removed when dropped.
+
+use std::io;
+use tempfile::TempDir;
+
+/// Creates a scratch directory whose name starts with `files-ingest-` and `label`.
+pub fn dir(label: &str) -> io::Result<TempDir> {
+    let label: String = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let prefix = format!("files-ingest-{label}-");
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix);
+    #[cfg(unix)]
+    {
+        use std::fs::Permissions;
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(Permissions::from_mode(0o700));
+    }
+    builder.tempdir()
+}
@@ -0,0 +1,81 @@
+//! The diagnostic for a run selecting no files.
+//!
+//! The walk applies its filters together, so their share is found by walking
+//! again: once with none of them, for the candidate files, then once with each
+//! alone. This only happens when nothing was selected, so the extra walks are
+//! cheap next to the user's confusion.
+
+use crate::provider::FileProvider;
+use crate::{AppError, Options};
+use std::fmt::Write;
+
+/// Files eliminated after the walk by a stage of the selection (tags, owners...).
+pub struct Eliminated {
+    pub filters: &'static str,
+    pub files: usize,
+}
+
+/// Explains why no files were selected: how many candidates each filter eliminated.
+///
+/// `eliminated` lists what the stages after the walk eliminated, in order.
+pub fn explain(
+    provider: &dyn FileProvider,
+    options: &Options,
+    threads: usize,
+    eliminated: &[Eliminated],
+) -> Result<String, AppError> {
+    let mut unfiltered = options.clone();
+    unfiltered.include_hidden = true;
+    unfiltered.ignore_gitignore = true;
+    unfiltered.ignore_patterns.clear();
+    unfiltered.ignore_files.clear();
+    unfiltered.extensions.clear();
+    let candidates = provider.collect_files(&unfiltered, threads)?.len();
+    if candidates == 0 {
+        return Ok("No files selected: there are no files under the given paths".to_string());
+    }
+
+    // Each walk filter alone, if it is in use
+    let mut filters: Vec<(&str, Options)> = Vec::new();
+    if !options.include_hidden {
+        let mut only = unfiltered.clone();
+        only.include_hidden = false;
+        only.include_hidden_patterns
+            .clone_from(&options.include_hidden_patterns);
+        filters.push((
+            "hidden files (--include-hidden, --include-hidden-pattern)",
+            only,
+        ));
+    }
+    if !options.ignore_gitignore {
+        let mut only = unfiltered.clone();
+        only.ignore_gitignore = false;
+        filters.push(("gitignored files (--ignore-gitignore)", only));
+    }
+    if !options.ignore_patterns.is_empty() || !options.ignore_files.is_empty() {
+        let mut only = unfiltered.clone();
+        only.ignore_patterns.clone_from(&options.ignore_patterns);
+        only.ignore_files.clone_from(&options.ignore_files);
+        filters.push(("--ignore and --ignore-file", only));
+    }
+    if !options.extensions.is_empty() {
+        let mut only = unfiltered.clone();
+        only.extensions.clone_from(&options.extensions);
+        filters.push(("--extension", only));
+    }
+
+    let mut diagnostic = format!(
+        "No files selected: the filters eliminated all {candidates} candidate files. \
+         Files eliminated by each filter (a file may be counted under several):"
+    );
+    for (filters, only) in filters {
+        let files = candidates - provider.collect_files(&only, threads)?.len();
+        if files > 0 {
+            let _ = write!(diagnostic, "\n  - {filters}: {files}");
+        }
+    }
+    for stage in eliminated.iter().filter(|stage| stage.files > 0) {
+        let _ = write!(diagnostic, "\n  - {}: {}", stage.filters, stage.files);
+    }
+    Ok(diagnostic)
+}
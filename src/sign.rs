@@ -0,0 +1,139 @@
+//! Integrity footer of the output (`--sign`), and the `verify-signature`
+//! subcommand checking it.
+//!
+//! The footer is the last line of the bundle: the SHA-256 of everything before
+//! it and, with `--signing-key`, an ed25519 signature of that digest. It is an
+//! HTML/XML comment in the Claude XML and Markdown formats, a `#` line otherwise.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use files_ingest::{AppError, Options};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use tracing::{error, info};
+
+const MARKER: &str = "files-ingest signature";
+
+/// Hashes what is written through it, for the footer written by [`Writer::finish`].
+pub struct Writer<'a> {
+    inner: &'a mut dyn Write,
+    hasher: Sha256,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(inner: &'a mut dyn Write) -> Self {
+        Writer {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Writes the footer, signing the digest with `key` if given.
+    pub fn finish(self, key: Option<&SigningKey>, options: &Options) -> io::Result<()> {
+        let digest = self.hasher.finalize();
+        let mut fields = format!("sha256={}", hex(&digest));
+        if let Some(key) = key {
+            let signature = key.sign(&digest);
+            let _ = write!(fields, " ed25519={}", BASE64.encode(signature.to_bytes()));
+        }
+        if options.cxml || options.markdown {
+            writeln!(self.inner, "<!-- {MARKER} {fields} -->")
+        } else {
+            writeln!(self.inner, "# {MARKER} {fields}")
+        }
+    }
+}
+
+impl Write for Writer<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads an ed25519 private key (PKCS#8 PEM, as written by
+/// `openssl genpkey -algorithm ed25519`).
+pub fn load_key(path: &Path) -> Result<SigningKey, AppError> {
+    let pem = fs::read_to_string(path)?;
+    SigningKey::from_pkcs8_pem(&pem)
+        .map_err(|e| AppError::Config(format!("{}: {e}", path.display())))
+}
+
+/// Checks the footer of the bundle at `path`: its digest and, with a
+/// `public_key` (PEM), its signature. Prints the outcome and returns whether
+/// the bundle is intact.
+pub fn verify(path: &Path, public_key: Option<&Path>) -> Result<bool, AppError> {
+    let bundle = fs::read(path)?;
+    let Some((body, fields)) = split_footer(&bundle) else {
+        error!("{}: no signature footer", path.display());
+        return Ok(false);
+    };
+    let field = |name: &str| {
+        fields
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+    };
+
+    let digest = Sha256::digest(body);
+    if field("sha256") != Some(hex(&digest).as_str()) {
+        error!(
+            "{}: SHA-256 mismatch, the bundle was modified",
+            path.display()
+        );
+        return Ok(false);
+    }
+    let Some(public_key) = public_key else {
+        info!("{}: SHA-256 matches", path.display());
+        return Ok(true);
+    };
+
+    let pem = fs::read_to_string(public_key)?;
+    let key = VerifyingKey::from_public_key_pem(&pem)
+        .map_err(|e| AppError::Config(format!("{}: {e}", public_key.display())))?;
+    let signature = field("ed25519")
+        .and_then(|signature| BASE64.decode(signature).ok())
+        .and_then(|bytes| Signature::from_slice(&bytes).ok());
+    let Some(signature) = signature else {
+        error!("{}: not signed with a key", path.display());
+        return Ok(false);
+    };
+    if key.verify(&digest, &signature).is_err() {
+        error!("{}: invalid signature", path.display());
+        return Ok(false);
+    }
+    info!("{}: SHA-256 matches, signature valid", path.display());
+    Ok(true)
+}
+
+/// Splits the bundle into its body and the fields of its footer line.
+fn split_footer(bundle: &[u8]) -> Option<(&[u8], &str)> {
+    let without_newline = bundle.strip_suffix(b"\n")?;
+    let start = without_newline
+        .iter()
+        .rposition(|&byte| byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    let line = std::str::from_utf8(&without_newline[start..]).ok()?;
+    let fields = line
+        .strip_prefix("<!-- ")
+        .and_then(|line| line.strip_suffix(" -->"))
+        .or_else(|| line.strip_prefix("# "))?
+        .strip_prefix(MARKER)?;
+    Some((&bundle[..start], fields))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{byte:02x}");
+        hex
+    })
+}
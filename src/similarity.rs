@@ -0,0 +1,117 @@
+//! Near-duplicate detection (`--similar-report`).
+//!
+//! Each file is summarized by a `MinHash` signature over word shingles. Comparing
+//! signatures estimates the Jaccard similarity of the files' shingle sets, which
+//! is cheap enough to do for every pair of included files.
+
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Number of hash functions in a signature.
+const SIGNATURE_LEN: usize = 64;
+/// Number of consecutive words forming a shingle.
+const SHINGLE_WORDS: usize = 5;
+
+/// A `MinHash` signature of a file's content.
+pub type Signature = Vec<u64>;
+
+/// Computes the `MinHash` signature of `content`.
+pub fn signature(content: &str) -> Signature {
+    let words: Vec<&str> = content.split_whitespace().collect();
+    let mut signature = vec![u64::MAX; SIGNATURE_LEN];
+    // Short files still get one (whole-content) shingle
+    for shingle in words.windows(SHINGLE_WORDS.min(words.len()).max(1)) {
+        let mut hasher = DefaultHasher::new();
+        shingle.hash(&mut hasher);
+        let shingle_hash = hasher.finish();
+        for (i, min) in signature.iter_mut().enumerate() {
+            *min = (*min).min(mix(
+                shingle_hash ^ (i as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ));
+        }
+    }
+    signature
+}
+
+/// Estimates the Jaccard similarity of two files from their signatures.
+#[allow(clippy::cast_precision_loss)] // Counts are at most SIGNATURE_LEN
+pub fn similarity(a: &Signature, b: &Signature) -> f64 {
+    let matching = a.iter().zip(b).filter(|(x, y)| x == y).count();
+    matching as f64 / SIGNATURE_LEN as f64
+}
+
+/// Groups files whose similarity to another file in the group reaches `threshold`.
+///
+/// Returns only groups with at least two files, in order of their first file.
+pub fn clusters(files: &[(PathBuf, Signature)], threshold: f64) -> Vec<Vec<usize>> {
+    // Union-find over file indices
+    let mut parent: Vec<usize> = (0..files.len()).collect();
+
+    for i in 0..files.len() {
+        for j in i + 1..files.len() {
+            if similarity(&files[i].1, &files[j].1) >= threshold {
+                let (root_i, root_j) = (root(&mut parent, i), root(&mut parent, j));
+                parent[root_j.max(root_i)] = root_j.min(root_i);
+            }
+        }
+    }
+
+    let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for i in 0..files.len() {
+        let group_root = root(&mut parent, i);
+        groups.entry(group_root).or_default().push(i);
+    }
+    groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect()
+}
+
+/// Writes the near-duplicate report for `files`.
+pub fn write_report(
+    writer: &mut dyn Write,
+    files: &[(PathBuf, Signature)],
+    threshold: f64,
+) -> io::Result<()> {
+    let clusters = clusters(files, threshold);
+    if clusters.is_empty() {
+        return writeln!(
+            writer,
+            "No near-duplicate files found (similarity >= {threshold:.2})."
+        );
+    }
+
+    writeln!(
+        writer,
+        "Near-duplicate files (estimated similarity >= {threshold:.2}):"
+    )?;
+    for (n, cluster) in clusters.iter().enumerate() {
+        writeln!(writer, "  Cluster {}:", n + 1)?;
+        let (first_path, first_signature) = &files[cluster[0]];
+        writeln!(writer, "    {}", first_path.display())?;
+        for &i in &cluster[1..] {
+            let (path, signature) = &files[i];
+            let score = similarity(first_signature, signature);
+            writeln!(writer, "    {} ({score:.2})", path.display())?;
+        }
+    }
+    Ok(())
+}
+
+/// Finds the representative of `i`'s group in a union-find forest.
+fn root(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]]; // Path halving
+        i = parent[i];
+    }
+    i
+}
+
+/// `SplitMix64` finalizer, deriving independent hash functions from one shingle hash.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
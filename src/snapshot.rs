@@ -0,0 +1,54 @@
+//! The `snapshot` subcommand: keeps a committed bundle in sync with the code.
+//!
+//! The bundle's file and the arguments generating it are recorded in the
+//! `[snapshot]` section of the configuration file, so CI can regenerate it with
+//! `files-ingest snapshot --check` and fail when it has drifted.
+
+use clap::Parser;
+use files_ingest::{AppError, Config, Ingestor, Options};
+use std::fs;
+use std::iter;
+use std::path::Path;
+use tracing::warn;
+
+/// Regenerates the bundle recorded in the configuration file at `config_path`.
+///
+/// With `check`, compares it to the committed file instead of writing it and
+/// returns whether they match (printing where they differ if not).
+pub fn run(config_path: &Path, check: bool) -> Result<bool, AppError> {
+    let config = Config::load(Some(config_path))?;
+    let Some(snapshot) = config.snapshot else {
+        return Err(AppError::Config(format!(
+            "{}: no [snapshot] section",
+            config_path.display()
+        )));
+    };
+    let args: Vec<String> = iter::once("files-ingest".to_string())
+        .chain(snapshot.args)
+        .collect();
+    let mut options = Options::try_parse_from(&args)
+        .map_err(|e| AppError::Config(format!("[snapshot] args: {e}")))?;
+    if options.config.is_none() {
+        options.config = Some(config_path.to_path_buf());
+    }
+    options.color = false; // Never highlight a file
+    let bundle = Ingestor::new(options).with_command_line(args).ingest()?;
+
+    if !check {
+        fs::write(&snapshot.file, bundle)?;
+        return Ok(true);
+    }
+    let committed = fs::read_to_string(&snapshot.file).unwrap_or_default();
+    if committed == bundle {
+        return Ok(true);
+    }
+    let line = iter::zip(committed.lines(), bundle.lines())
+        .take_while(|(committed, current)| committed == current)
+        .count()
+        + 1;
+    warn!(
+        "Snapshot {} is out of date (first difference at line {line}); run `files-ingest snapshot` to update it.",
+        snapshot.file.display()
+    );
+    Ok(false)
+}
@@ -0,0 +1,51 @@
+//! The `--source-map` sidecar: where the lines of each file are in the bundle,
+//! so line numbers quoted back from the bundle can be traced to the sources.
+//!
+//! Spans assume the body reproduces the file line for line. Content transforms
+//! (sampling, log compaction, filters), `--wrap` and Markdown line number
+//! gutters shift lines, so spans are approximate with them.
+
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The spans recorded so far, in bundle order.
+#[derive(Debug, Default, Serialize)]
+pub struct SourceMap {
+    files: Vec<Span>,
+}
+
+/// The lines of a file in the bundle (1-based, inclusive).
+#[derive(Debug, Serialize)]
+struct Span {
+    path: PathBuf,
+    /// First and last line of the file's content in the bundle.
+    bundle_lines: [usize; 2],
+    /// The matching lines of the file.
+    source_lines: [usize; 2],
+}
+
+impl SourceMap {
+    /// Records a file whose body spans `lines` lines of the bundle from
+    /// `first_line`. Bodies end with an extra newline, so one ending with an
+    /// empty line has one line fewer from the file.
+    pub fn record(&mut self, path: PathBuf, first_line: usize, lines: usize, blank_end: bool) {
+        let source_lines = lines.saturating_sub(usize::from(blank_end));
+        if source_lines == 0 {
+            return;
+        }
+        self.files.push(Span {
+            path,
+            bundle_lines: [first_line, first_line + source_lines - 1],
+            source_lines: [1, source_lines],
+        });
+    }
+
+    /// Writes the map to `path`, as JSON.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_vec_pretty(self)?;
+        json.push(b'\n');
+        fs::write(path, json)
+    }
+}
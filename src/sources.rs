@@ -0,0 +1,528 @@
+//! Published package sources as input paths, such as `crate:serde@1.0.200`,
+//! `npm:lodash@4.17.21` or `pypi:requests==2.32.0`.
+//!
+//! The package is downloaded with `curl` and unpacked with `tar` (or `unzip`) into a temporary
+//! directory, which is shown in the output under the package's name (as with
+//! --alias) and removed once the run is over. The filesystems of Docker images
+//! (`--docker`) are copied out with the `docker` CLI the same way, remote
+//! directories (`--ssh`) with `ssh` and `tar`, and the
+//! objects under an `s3://bucket/prefix` input downloaded with the AWS SDK
+//! (`s3` feature).
+
+use crate::format::Alias;
+use crate::{AppError, Options, scratch};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use tempfile::TempDir;
+
+/// The temporary directories of the downloaded packages, removed when dropped.
+#[derive(Default)]
+pub struct Downloads {
+    dirs: Vec<TempDir>,
+}
+
+/// A published package, as given on the command line.
+struct Spec<'a> {
+    registry: Registry,
+    name: &'a str,
+    version: Option<&'a str>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Registry {
+    /// `crate:NAME[@VERSION]`, from crates.io.
+    Crates,
+    /// `npm:NAME[@VERSION]` (NAME may be scoped, as in `@types/node`), from the npm registry.
+    Npm,
+    /// `pypi:NAME[==VERSION]`, from pypi.org (the source distribution if there is one, else a wheel).
+    PyPi,
+}
+
+impl<'a> Spec<'a> {
+    /// Parses a package spec, or returns `None` if `input` isn't one.
+    fn parse(input: &'a str) -> Option<Self> {
+        let (prefix, rest) = input.split_once(':')?;
+        let (registry, split) = match prefix {
+            "crate" => (Registry::Crates, rest.split_once('@')),
+            // The version follows the last `@` that doesn't start a scope
+            "npm" => (
+                Registry::Npm,
+                rest.rfind('@')
+                    .filter(|&at| at > 0)
+                    .map(|at| (&rest[..at], &rest[at + 1..])),
+            ),
+            "pypi" => (Registry::PyPi, rest.split_once("==")),
+            _ => return None,
+        };
+        let (name, version) = match split {
+            Some((name, version)) => (name, Some(version)),
+            None => (rest, None),
+        };
+        Some(Spec {
+            registry,
+            name,
+            version,
+        })
+    }
+
+    /// Checks that the spec can safely go into a URL and a file name.
+    fn validate(&self, input: &str) -> Result<(), AppError> {
+        let valid = |text: &str| {
+            !text.is_empty()
+                && !text.starts_with('.')
+                && text
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+        };
+        let name_valid = match self.name.strip_prefix('@') {
+            Some(scoped) if self.registry == Registry::Npm => scoped
+                .split_once('/')
+                .is_some_and(|(scope, name)| valid(scope) && valid(name)),
+            _ => valid(self.name),
+        };
+        if name_valid && self.version.is_none_or(valid) {
+            Ok(())
+        } else {
+            Err(AppError::Download(format!(
+                "invalid package spec '{input}'"
+            )))
+        }
+    }
+}
+
+/// A published version of a package: where to download it from.
+struct Release {
+    version: String,
+    url: String,
+}
+
+/// Replaces the package specs among the input paths of `options` with the
+/// directories they were downloaded to. Paths that exist are never specs.
+pub fn fetch(options: &mut Cow<Options>) -> Result<Downloads, AppError> {
+    let mut downloads = Downloads::default();
+    for i in 0..options.paths.len() {
+        let input = options.paths[i].to_string_lossy().into_owned();
+        if let Some(location) = input.strip_prefix("s3://") {
+            let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+            let dir = downloads.temp_dir(&input)?;
+            s3::download(bucket, prefix, &dir, options)?;
+
+            let options = options.to_mut();
+            options.paths[i].clone_from(&dir);
+            options.aliases.push(Alias {
+                dir,
+                name: format!("s3://{bucket}"), // The bucket's objects keep their keys
+            });
+            continue;
+        }
+        let Some(spec) = Spec::parse(&input).filter(|_| !options.paths[i].exists()) else {
+            continue;
+        };
+        spec.validate(&input)?;
+        let release = release(&spec)?;
+        let label = format!("{}-{}", spec.name, release.version);
+        let dir = downloads.temp_dir(&label)?;
+        let root = download(&release.url, &dir)?;
+
+        let options = options.to_mut();
+        options.paths[i].clone_from(&root);
+        options.aliases.push(Alias {
+            dir: root,
+            name: label,
+        });
+    }
+
+    for i in 0..options.docker.len() {
+        let spec = options.docker[i].clone();
+        // The path is absolute, so `:/` can't be the port or tag of the image
+        let (image, path) = match spec.split_once(":/") {
+            Some((image, path)) => (image, format!("/{path}")),
+            None => (spec.as_str(), "/".to_string()),
+        };
+        let dir = downloads.temp_dir(image)?;
+        let root = docker_copy(image, &path, &dir)?;
+
+        let options = options.to_mut();
+        options.paths.push(root.clone());
+        options.aliases.push(Alias {
+            dir: root,
+            name: format!("{image}{}", path.trim_end_matches('/')),
+        });
+    }
+
+    for i in 0..options.ssh.len() {
+        let spec = options.ssh[i].clone();
+        let dir = downloads.temp_dir(&spec)?;
+        ssh_copy(&spec, &dir, options)?;
+
+        let options = options.to_mut();
+        options.paths.push(dir.clone());
+        options.aliases.push(Alias {
+            dir,
+            name: spec.trim_end_matches('/').to_string(),
+        });
+    }
+    Ok(downloads)
+}
+
+impl Downloads {
+    /// Creates a new private temporary directory for `label`, to be removed with the others.
+    fn temp_dir(&mut self, label: &str) -> io::Result<PathBuf> {
+        let dir = scratch::dir(label)?;
+        let path = dir.path().to_path_buf();
+        self.dirs.push(dir);
+        Ok(path)
+    }
+}
+
+/// Copies `path` out of the filesystem of the Docker `image` into `dir`, returning
+/// where it ended up.
+fn docker_copy(image: &str, path: &str, dir: &Path) -> Result<PathBuf, AppError> {
+    fs::create_dir_all(dir)?;
+    let output = run(Command::new("docker").args(["create", image]))?;
+    let container = String::from_utf8_lossy(&output).trim().to_string();
+    let destination = dir.join("fs");
+    let copied = if path == "/" {
+        // Export the whole filesystem, which `docker cp` handles poorly (special files)
+        let archive = dir.join("fs.tar");
+        fs::create_dir_all(&destination)?;
+        run(Command::new("docker")
+            .arg("export")
+            .arg("--output")
+            .arg(&archive)
+            .arg(&container))
+        .and_then(|_| {
+            run(Command::new("tar")
+                .arg("-xf")
+                .arg(&archive)
+                .arg("-C")
+                .arg(&destination))
+        })
+    } else {
+        run(Command::new("docker")
+            .arg("cp")
+            .arg(format!("{container}:{path}"))
+            .arg(&destination))
+    };
+    let _ = run(Command::new("docker").args(["rm", &container])); // Even if the copy failed
+    copied?;
+    Ok(destination)
+}
+
+/// Looks up the version of the package to download (the latest if none was
+/// given) and its archive's URL.
+fn release(spec: &Spec) -> Result<Release, AppError> {
+    let missing = || {
+        AppError::Download(format!(
+            "no published version of {}{}",
+            spec.name,
+            spec.version.map(|v| format!(" {v}")).unwrap_or_default()
+        ))
+    };
+    match spec.registry {
+        Registry::Crates => {
+            let version = if let Some(version) = spec.version {
+                version.to_string()
+            } else {
+                let url = format!("https://crates.io/api/v1/crates/{}", spec.name);
+                let metadata = get_json(&url)?;
+                let krate = &metadata["crate"];
+                krate["max_stable_version"]
+                    .as_str()
+                    .or_else(|| krate["max_version"].as_str())
+                    .ok_or_else(missing)?
+                    .to_string()
+            };
+            let url = format!(
+                "https://static.crates.io/crates/{0}/{0}-{version}.crate",
+                spec.name
+            );
+            Ok(Release { version, url })
+        }
+        Registry::Npm => {
+            let url = format!(
+                "https://registry.npmjs.org/{}/{}",
+                spec.name.replace('/', "%2F"),
+                spec.version.unwrap_or("latest")
+            );
+            let metadata = get_json(&url)?;
+            match (
+                metadata["version"].as_str(),
+                metadata["dist"]["tarball"].as_str(),
+            ) {
+                (Some(version), Some(url)) => Ok(Release {
+                    version: version.to_string(),
+                    url: url.to_string(),
+                }),
+                _ => Err(missing()),
+            }
+        }
+        Registry::PyPi => {
+            let url = match spec.version {
+                Some(version) => format!("https://pypi.org/pypi/{}/{version}/json", spec.name),
+                None => format!("https://pypi.org/pypi/{}/json", spec.name),
+            };
+            let metadata = get_json(&url)?;
+            let files = metadata["urls"].as_array().ok_or_else(missing)?;
+            let file = ["sdist", "bdist_wheel"]
+                .iter()
+                .find_map(|kind| {
+                    files
+                        .iter()
+                        .find(|file| file["packagetype"].as_str() == Some(kind))
+                })
+                .ok_or_else(missing)?;
+            match (metadata["info"]["version"].as_str(), file["url"].as_str()) {
+                (Some(version), Some(url)) => Ok(Release {
+                    version: version.to_string(),
+                    url: url.to_string(),
+                }),
+                _ => Err(missing()),
+            }
+        }
+    }
+}
+
+/// Downloads and unpacks the archive at `url` (a gzipped tarball, or a zip file
+/// such as a wheel) into `dir`, returning the directory holding its files.
+fn download(url: &str, dir: &Path) -> Result<PathBuf, AppError> {
+    fs::create_dir_all(dir)?;
+    let archive = dir.join("package.archive");
+    run(curl().arg("--output").arg(&archive).arg(url))?;
+    let unpacked = dir.join("package");
+    fs::create_dir_all(&unpacked)?;
+    let is_zip = [".zip", ".whl"].iter().any(|ext| url.ends_with(ext));
+    if is_zip {
+        run(Command::new("unzip")
+            .arg("-q")
+            .arg(&archive)
+            .arg("-d")
+            .arg(&unpacked))?;
+    } else {
+        run(Command::new("tar")
+            .arg("-xzf")
+            .arg(&archive)
+            .arg("-C")
+            .arg(&unpacked))?;
+    }
+    fs::remove_file(&archive)?;
+    single_dir(&unpacked)
+}
+
+/// Copies the files of the remote directory `spec` (`[USER@]HOST:PATH`) into `dir`.
+///
+/// The hidden-file, --extension and --max-size filters are applied remotely by
+/// `find`, so only the files that may be selected are transferred (as a tarball
+/// over `ssh`). The remote errors are collected while the tarball is extracted.
+fn ssh_copy(spec: &str, dir: &Path, options: &Options) -> Result<(), AppError> {
+    let invalid = || AppError::Download(format!("expected [USER@]HOST:PATH, found '{spec}'"));
+    let (host, path) = spec.split_once(':').ok_or_else(invalid)?;
+    if host.is_empty() || host.starts_with('-') || path.is_empty() {
+        return Err(invalid());
+    }
+
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "'\\''"));
+    let mut find = vec!["find . -type f".to_string()];
+    if !options.include_hidden {
+        find.push("! -path '*/.*'".to_string());
+    }
+    if !options.extensions.is_empty() {
+        let names: Vec<String> = options
+            .extensions
+            .iter()
+            .map(|ext| format!("-iname {}", quote(&format!("*.{ext}"))))
+            .collect();
+        find.push(format!("\\( {} \\)", names.join(" -o ")));
+    }
+    if let Some(max_size) = options.max_size {
+        find.push(format!("! -size +{max_size}c"));
+    }
+    let remote = format!(
+        "cd {} && {} -print0 | tar -cf - --null -T -",
+        quote(path),
+        find.join(" ")
+    );
+
+    fs::create_dir_all(dir)?;
+    let mut ssh = Command::new("ssh")
+        .args(["-o", "BatchMode=yes", "--", host, &remote])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| AppError::Download(format!("could not run ssh: {e}")))?;
+    // Read on a thread of its own: ssh would block once the pipe is full
+    let mut stderr = ssh.stderr.take();
+    let errors = thread::spawn(move || {
+        let mut errors = Vec::new();
+        if let Some(stderr) = &mut stderr {
+            let _ = stderr.read_to_end(&mut errors);
+        }
+        errors
+    });
+    let archive = ssh.stdout.take().map_or_else(Stdio::null, Stdio::from);
+    let extracted = run(Command::new("tar")
+        .arg("-xf")
+        .arg("-")
+        .arg("-C")
+        .arg(dir)
+        .stdin(archive));
+    let status = ssh.wait()?;
+    let errors = errors.join().unwrap_or_default();
+    if !status.success() {
+        let error = String::from_utf8_lossy(&errors);
+        return Err(AppError::Download(format!(
+            "ssh {host} failed: {}",
+            error.trim()
+        )));
+    }
+    extracted.map(drop)
+}
+
+/// Returns the only entry of `dir` if it is a directory (archives usually hold
+/// a `NAME-VERSION/` directory), else `dir` itself.
+fn single_dir(dir: &Path) -> Result<PathBuf, AppError> {
+    let entries: Vec<_> = fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+    match entries.as_slice() {
+        [entry] if entry.path().is_dir() => Ok(entry.path()),
+        _ => Ok(dir.to_path_buf()),
+    }
+}
+
+/// Fetches `url` and parses it as JSON.
+fn get_json(url: &str) -> Result<Value, AppError> {
+    let output = run(curl().arg(url))?;
+    serde_json::from_slice(&output).map_err(|e| AppError::Download(format!("{url}: {e}")))
+}
+
+/// A `curl` command failing on HTTP errors and following redirects.
+fn curl() -> Command {
+    let mut command = Command::new("curl");
+    command
+        .args(["--fail", "--silent", "--show-error", "--location"])
+        .args([
+            "--user-agent",
+            concat!("files-ingest/", env!("CARGO_PKG_VERSION")),
+        ]);
+    command
+}
+
+/// Runs `command`, returning its output if it succeeds.
+fn run(command: &mut Command) -> Result<Vec<u8>, AppError> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|e| AppError::Download(format!("could not run {program}: {e}")))?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Download(format!(
+            "{program} failed: {}",
+            error.trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(not(feature = "s3"))]
+mod s3 {
+    use crate::{AppError, Options};
+    use std::path::Path;
+
+    pub(super) fn download(
+        bucket: &str,
+        _prefix: &str,
+        _dir: &Path,
+        _options: &Options,
+    ) -> Result<(), AppError> {
+        Err(AppError::Download(format!(
+            "s3://{bucket}: s3:// inputs need files-ingest built with the `s3` feature"
+        )))
+    }
+}
+
+#[cfg(feature = "s3")]
+mod s3 {
+    use crate::walk::has_included_extension;
+    use crate::{AppError, Options};
+    use aws_sdk_s3::Client;
+    use aws_sdk_s3::error::DisplayErrorContext;
+    use std::fs;
+    use std::path::{Component, Path};
+
+    /// Downloads the objects of `bucket` under `prefix` that pass the --extension
+    /// and --max-size filters into `dir`, at their keys.
+    ///
+    /// Credentials, region and endpoint come from the usual AWS configuration
+    /// (environment, profiles, instance metadata).
+    pub(super) fn download(
+        bucket: &str,
+        prefix: &str,
+        dir: &Path,
+        options: &Options,
+    ) -> Result<(), AppError> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            let client = Client::new(&config);
+            let mut pages = client
+                .list_objects_v2()
+                .bucket(bucket)
+                .prefix(prefix)
+                .into_paginator()
+                .send();
+            while let Some(page) = pages.next().await {
+                let page = page.map_err(|e| to_error(bucket, &e))?;
+                for object in page.contents() {
+                    let Some(key) = object.key() else {
+                        continue;
+                    };
+                    let path = Path::new(key);
+                    // Keys are untrusted paths: keep them inside `dir`
+                    let safe = path
+                        .components()
+                        .all(|component| matches!(component, Component::Normal(_)));
+                    if !safe || key.ends_with('/') || !has_included_extension(path, options) {
+                        continue;
+                    }
+                    // The listing has the sizes: large objects aren't even fetched
+                    let size = object.size().and_then(|size| u64::try_from(size).ok());
+                    if let (Some(max_size), Some(size)) = (options.max_size, size)
+                        && size > max_size
+                    {
+                        continue;
+                    }
+                    let object = client
+                        .get_object()
+                        .bucket(bucket)
+                        .key(key)
+                        .send()
+                        .await
+                        .map_err(|e| to_error(bucket, &e))?;
+                    let bytes = object
+                        .body
+                        .collect()
+                        .await
+                        .map_err(|e| AppError::Download(format!("s3://{bucket}/{key}: {e}")))?;
+                    let local = dir.join(path);
+                    if let Some(parent) = local.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(local, bytes.into_bytes())?;
+                }
+            }
+            fs::create_dir_all(dir)?; // Even if there was no object
+            Ok(())
+        })
+    }
+
+    fn to_error(bucket: &str, error: &impl std::error::Error) -> AppError {
+        AppError::Download(format!("s3://{bucket}: {}", DisplayErrorContext(error)))
+    }
+}
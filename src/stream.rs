@@ -0,0 +1,266 @@
+//! Streaming output of large files.
+//!
+//! Files above `--stream-threshold` are never loaded into memory as a whole.
+//! They are read in fixed-size chunks, once to gather what the formatters need
+//! up front (UTF-8 validity, line count, longest run of backticks) and once more
+//! to write them, applying tab expansion, line numbering and XML escaping on the fly. The
+//! result is byte-for-byte what the in-memory path would have produced. Files
+//! numbered in a gutter (`--line-numbers-style gutter`) are not streamed.
+
+use crate::tabs::ExpandTabs;
+use crate::wrap;
+use sha2::{Digest, Sha256};
+use std::io::{self, Read, Write};
+
+/// Default size (in bytes) above which files are streamed: 16 MiB.
+pub const DEFAULT_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// What the formatters need to know about a file before writing it.
+pub struct StreamStats {
+    /// Number of lines, counted like `str::lines` does.
+    pub lines: usize,
+    /// Length of the longest run of consecutive backticks (for Markdown fences).
+    pub longest_backtick_run: usize,
+    /// SHA-256 of the content, if requested.
+    pub sha256: Option<[u8; 32]>,
+}
+
+/// How the body of a streamed file is rendered.
+#[derive(Clone, Copy)]
+pub struct BodyOptions {
+    /// Width of the line number column, if line numbers are enabled.
+    pub line_number_width: Option<usize>,
+    /// Whether to escape `&`, `<` and `>` for XML output.
+    pub escape_xml: bool,
+    /// Tab stop width, if tabs are expanded to spaces.
+    pub tab_width: Option<usize>,
+    /// Column limit, if long lines are wrapped.
+    pub wrap: Option<usize>,
+}
+
+/// Reads `reader` in chunks, validating UTF-8 and collecting [`StreamStats`].
+///
+/// The content is hashed as well if `hash` is set.
+///
+/// Fails with [`io::ErrorKind::InvalidData`] if the file is not valid UTF-8,
+/// matching the error `fs::read_to_string` reports.
+pub fn scan(reader: &mut dyn Read, hash: bool) -> io::Result<StreamStats> {
+    let mut stats = StreamStats {
+        lines: 0,
+        longest_backtick_run: 0,
+        sha256: None,
+    };
+    let mut hasher = hash.then(Sha256::new);
+    let mut backtick_run = 0;
+    let mut last_byte = None;
+    for_each_chunk(reader, |chunk| {
+        for &byte in chunk {
+            if byte == b'`' {
+                backtick_run += 1;
+                stats.longest_backtick_run = stats.longest_backtick_run.max(backtick_run);
+            } else {
+                backtick_run = 0;
+            }
+            if byte == b'\n' {
+                stats.lines += 1;
+            }
+        }
+        last_byte = chunk.last().copied().or(last_byte);
+        if let Some(hasher) = &mut hasher {
+            hasher.update(chunk);
+        }
+        Ok(())
+    })?;
+    stats.sha256 = hasher.map(|hasher| hasher.finalize().into());
+    // A final line without a trailing newline still counts
+    if last_byte.is_some_and(|byte| byte != b'\n') {
+        stats.lines += 1;
+    }
+    Ok(stats)
+}
+
+/// Writes the content of `reader` to `writer`, followed by a newline.
+pub fn write_body(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    options: BodyOptions,
+) -> io::Result<()> {
+    let mut expanded;
+    let reader: &mut dyn Read = match options.tab_width {
+        Some(width) => {
+            expanded = ExpandTabs::new(reader, width);
+            &mut expanded
+        }
+        None => reader,
+    };
+    // Wrapping goes between line numbering and escaping, as in the in-memory path
+    let mut wrapping;
+    let (writer, escape_xml): (&mut dyn Write, bool) = match options.wrap {
+        Some(columns) => {
+            wrapping = Wrapping {
+                writer,
+                columns,
+                column: 0,
+                escape_xml: options.escape_xml,
+                buffer: Vec::new(),
+            };
+            (&mut wrapping, false)
+        }
+        None => (writer, options.escape_xml),
+    };
+    let mut line_number = 0;
+    let mut at_line_start = true;
+    // Line endings are held back until the next byte shows whether another line
+    // follows: `str::lines` drops the final newline and the `\r` of `\r\n`.
+    let mut pending_cr = false;
+    let mut pending_newline = false;
+
+    for_each_chunk(reader, |chunk| {
+        let Some(width) = options.line_number_width else {
+            return write_escaped(writer, chunk, escape_xml);
+        };
+
+        let mut start = 0;
+        for (i, &byte) in chunk.iter().enumerate() {
+            if byte != b'\n' && byte != b'\r' && !pending_cr && !at_line_start {
+                continue; // Fast path: an ordinary byte in the middle of a line
+            }
+            write_escaped(writer, &chunk[start..i], escape_xml)?;
+            start = i + 1;
+
+            if pending_cr && byte != b'\n' {
+                // A lone '\r' is part of the line content
+                if at_line_start {
+                    write_line_number(writer, &mut line_number, width, &mut pending_newline)?;
+                    at_line_start = false;
+                }
+                write_escaped(writer, b"\r", escape_xml)?;
+            }
+            pending_cr = false;
+
+            match byte {
+                b'\r' => pending_cr = true,
+                b'\n' => {
+                    if at_line_start {
+                        write_line_number(writer, &mut line_number, width, &mut pending_newline)?;
+                    }
+                    pending_newline = true;
+                    at_line_start = true;
+                }
+                _ => {
+                    if at_line_start {
+                        write_line_number(writer, &mut line_number, width, &mut pending_newline)?;
+                        at_line_start = false;
+                    }
+                    start = i; // The byte itself is line content
+                }
+            }
+        }
+        write_escaped(writer, &chunk[start..], escape_xml)
+    })?;
+
+    if pending_cr {
+        // The file ended with a lone '\r'
+        if at_line_start && let Some(width) = options.line_number_width {
+            write_line_number(writer, &mut line_number, width, &mut pending_newline)?;
+        }
+        write_escaped(writer, b"\r", escape_xml)?;
+    }
+    writeln!(writer)
+}
+
+/// A writer wrapping long lines, then escaping XML special characters if requested.
+struct Wrapping<'a> {
+    writer: &'a mut dyn Write,
+    columns: usize,
+    column: usize,
+    escape_xml: bool,
+    buffer: Vec<u8>,
+}
+
+impl Write for Wrapping<'_> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        wrap::wrap(bytes, self.columns, &mut self.column, &mut self.buffer);
+        write_escaped(self.writer, &self.buffer, self.escape_xml)?;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes the separator for the previous line (if any) and the next line number.
+fn write_line_number(
+    writer: &mut dyn Write,
+    line_number: &mut usize,
+    width: usize,
+    pending_newline: &mut bool,
+) -> io::Result<()> {
+    if std::mem::take(pending_newline) {
+        writer.write_all(b"\n")?;
+    }
+    *line_number += 1;
+    write!(writer, "{:<width$}  ", *line_number)
+}
+
+/// Writes `bytes`, escaping XML special characters if requested.
+fn write_escaped(writer: &mut dyn Write, bytes: &[u8], escape_xml: bool) -> io::Result<()> {
+    if !escape_xml {
+        return writer.write_all(bytes);
+    }
+    let mut start = 0;
+    for (i, &byte) in bytes.iter().enumerate() {
+        let escaped: &[u8] = match byte {
+            b'&' => b"&amp;",
+            b'<' => b"&lt;",
+            b'>' => b"&gt;",
+            _ => continue,
+        };
+        writer.write_all(&bytes[start..i])?;
+        writer.write_all(escaped)?;
+        start = i + 1;
+    }
+    writer.write_all(&bytes[start..])
+}
+
+/// Calls `f` with successive chunks of `reader`, each ending on a UTF-8 character boundary.
+fn for_each_chunk(
+    reader: &mut dyn Read,
+    mut f: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut carried = 0; // Bytes of an incomplete character carried over from the previous chunk
+
+    loop {
+        let read = reader.read(&mut buffer[carried..])?;
+        let filled = carried + read;
+        if read == 0 {
+            if carried > 0 {
+                return Err(invalid_utf8()); // The file ends in the middle of a character
+            }
+            return Ok(());
+        }
+
+        let valid = match std::str::from_utf8(&buffer[..filled]) {
+            Ok(_) => filled,
+            // `error_len` is `None` when the chunk merely ends mid-character
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(invalid_utf8()),
+        };
+        f(&buffer[..valid])?;
+
+        buffer.copy_within(valid..filled, 0);
+        carried = filled - valid;
+    }
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "stream did not contain valid UTF-8",
+    )
+}
@@ -0,0 +1,107 @@
+//! Depth truncation for structured data files (JSON / YAML).
+//!
+//! Large data files mostly matter for their *shape*. Truncating nested values
+//! beyond a given depth keeps keys (the schema) visible while dropping the bulk.
+
+use std::path::Path;
+use tracing::warn;
+
+/// Key and value used for the placeholder that replaces truncated containers.
+const PLACEHOLDER_KEY: &str = "...";
+const PLACEHOLDER_VALUE: &str = "…";
+
+/// Truncates `content` to `max_depth` levels of nesting if `path` is a JSON or YAML file.
+///
+/// Returns `None` when the file is not structured data or could not be parsed,
+/// in which case the caller should use the original content unchanged.
+pub fn truncate_depth(path: &Path, content: &str, max_depth: usize) -> Option<String> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    match ext.as_str() {
+        "json" => truncate_json(path, content, max_depth),
+        "yaml" | "yml" => truncate_yaml(path, content, max_depth),
+        _ => None,
+    }
+}
+
+fn truncate_json(path: &Path, content: &str, max_depth: usize) -> Option<String> {
+    let mut value: serde_json::Value = match serde_json::from_str(content) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("Not truncating {} - Invalid JSON: {e}", path.display());
+            return None;
+        }
+    };
+    truncate_json_value(&mut value, 0, max_depth);
+    serde_json::to_string_pretty(&value).ok()
+}
+
+fn truncate_json_value(value: &mut serde_json::Value, depth: usize, max_depth: usize) {
+    use serde_json::Value;
+
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            if depth >= max_depth {
+                map.clear();
+                map.insert(PLACEHOLDER_KEY.into(), PLACEHOLDER_VALUE.into());
+            } else {
+                for child in map.values_mut() {
+                    truncate_json_value(child, depth + 1, max_depth);
+                }
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            if depth >= max_depth {
+                items.clear();
+                items.push(PLACEHOLDER_VALUE.into());
+            } else {
+                for child in items {
+                    truncate_json_value(child, depth + 1, max_depth);
+                }
+            }
+        }
+        _ => {} // Scalars and empty containers are always kept as-is
+    }
+}
+
+fn truncate_yaml(path: &Path, content: &str, max_depth: usize) -> Option<String> {
+    let mut value: serde_yaml::Value = match serde_yaml::from_str(content) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("Not truncating {} - Invalid YAML: {e}", path.display());
+            return None;
+        }
+    };
+    truncate_yaml_value(&mut value, 0, max_depth);
+    serde_yaml::to_string(&value)
+        .ok()
+        .map(|s| s.trim_end().to_string())
+}
+
+fn truncate_yaml_value(value: &mut serde_yaml::Value, depth: usize, max_depth: usize) {
+    use serde_yaml::Value;
+
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            if depth >= max_depth {
+                map.clear();
+                map.insert(PLACEHOLDER_KEY.into(), PLACEHOLDER_VALUE.into());
+            } else {
+                for (_, child) in map.iter_mut() {
+                    truncate_yaml_value(child, depth + 1, max_depth);
+                }
+            }
+        }
+        Value::Sequence(items) if !items.is_empty() => {
+            if depth >= max_depth {
+                items.clear();
+                items.push(PLACEHOLDER_VALUE.into());
+            } else {
+                for child in items {
+                    truncate_yaml_value(child, depth + 1, max_depth);
+                }
+            }
+        }
+        Value::Tagged(tagged) => truncate_yaml_value(&mut tagged.value, depth, max_depth),
+        _ => {}
+    }
+}
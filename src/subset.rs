@@ -0,0 +1,87 @@
+//! Random subsets of the selected files (`--sample`, `--seed`), for building
+//! representative subsets of large corpora.
+//!
+//! The generator is a small `SplitMix64`, so a seed picks the same files on every
+//! platform and release.
+
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many files to keep: a number, or a percentage as in `10%`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleSize {
+    Files(usize),
+    Percent(f64),
+}
+
+impl FromStr for SampleSize {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        if let Some(percent) = spec.strip_suffix('%') {
+            match percent.trim().parse::<f64>() {
+                Ok(percent) if (0.0..=100.0).contains(&percent) => Ok(SampleSize::Percent(percent)),
+                _ => Err(format!(
+                    "expected a percentage from 0 to 100, found '{spec}'"
+                )),
+            }
+        } else {
+            spec.trim()
+                .parse()
+                .map(SampleSize::Files)
+                .map_err(|_| format!("expected a number of files or a percentage, found '{spec}'"))
+        }
+    }
+}
+
+impl SampleSize {
+    /// The number of files to keep out of `total`.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )] // Rounded percentage of a file count, within 0..=total
+    fn of(self, total: usize) -> usize {
+        match self {
+            SampleSize::Files(files) => files.min(total),
+            SampleSize::Percent(percent) => (total as f64 * percent / 100.0).round() as usize,
+        }
+    }
+}
+
+/// Keeps a random sample of `files`, in their original order.
+///
+/// Without a `seed`, one is taken from the clock.
+pub fn sample(files: &mut Vec<PathBuf>, size: SampleSize, seed: Option<u64>) {
+    let keep = size.of(files.len());
+    let mut state = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| {
+                elapsed.as_secs() ^ u64::from(elapsed.subsec_nanos())
+            })
+    });
+    // A partial Fisher-Yates shuffle of the indices picks `keep` of them
+    let mut indices: Vec<usize> = (0..files.len()).collect();
+    for i in 0..keep {
+        let range = (indices.len() - i) as u64;
+        let j = i + usize::try_from(next(&mut state) % range).unwrap_or(0);
+        indices.swap(i, j);
+    }
+    let mut chosen = vec![false; files.len()];
+    for &index in &indices[..keep] {
+        chosen[index] = true;
+    }
+    let mut chosen = chosen.into_iter();
+    files.retain(|_| chosen.next().unwrap_or(false));
+}
+
+/// `SplitMix64`: advances `state` and returns the next number.
+fn next(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,105 @@
+//! The symbol index (`--format symbols`): the definitions found by
+//! universal-ctags in the documents, one JSON line each, as a compact map of
+//! the code.
+//!
+//! ctags reads the documents as they would appear in the output (after the
+//! filters, from any provider), written to a temporary directory.
+
+use crate::format::contained_path;
+use crate::{AppError, Ingestor, Summary, scratch};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs the ingestion, writing a record for each symbol defined in the
+/// documents to `writer`: its name, kind, file, line, enclosing scope (if any)
+/// and signature (the line defining it).
+pub fn write(ingestor: &Ingestor, writer: &mut dyn Write) -> Result<Summary, AppError> {
+    let dir = scratch::dir("symbols")?; // Removed when dropped
+    write_in(ingestor, writer, dir.path())
+}
+
+fn write_in(ingestor: &Ingestor, writer: &mut dyn Write, dir: &Path) -> Result<Summary, AppError> {
+    // Where each document went in `dir` -> its path and content
+    let mut documents: HashMap<PathBuf, (PathBuf, String)> = HashMap::new();
+    let summary = ingestor.ingest_with(|document| {
+        let copy = contained_path(&document.path);
+        let path = dir.join(&copy);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, &document.content)?;
+        documents.insert(copy, (document.path, document.content));
+        Ok(())
+    })?;
+    if documents.is_empty() {
+        return Ok(summary);
+    }
+
+    let output = Command::new("ctags")
+        .args([
+            "--output-format=json",
+            "--fields=+nKS",
+            "--recurse",
+            "-f",
+            "-",
+        ])
+        .current_dir(dir)
+        .output()
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("--format symbols needs universal-ctags (ctags): {e}"),
+            )
+        })?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "ctags failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+
+    let mut symbols = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let Ok(mut tag) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if tag["_type"] != "tag" {
+            continue;
+        }
+        let copy = PathBuf::from(tag["path"].as_str().unwrap_or_default());
+        let Some((path, content)) = documents.get(copy.strip_prefix(".").unwrap_or(&copy)) else {
+            continue;
+        };
+        let line = tag["line"].as_u64().unwrap_or(0);
+        let signature = usize::try_from(line)
+            .ok()
+            .and_then(|line| content.lines().nth(line.checked_sub(1)?))
+            .map_or("", str::trim);
+        let mut symbol = json!({
+            "name": mem::take(&mut tag["name"]),
+            "kind": mem::take(&mut tag["kind"]),
+            "path": path.to_string_lossy(),
+            "line": line,
+        });
+        if let Some(scope) = tag["scope"].as_str() {
+            symbol["scope"] = scope.into();
+        }
+        symbol["signature"] = signature.into();
+        symbols.push(symbol);
+    }
+    symbols.sort_by(|a, b| {
+        (a["path"].as_str(), a["line"].as_u64()).cmp(&(b["path"].as_str(), b["line"].as_u64()))
+    });
+    for symbol in symbols {
+        serde_json::to_writer(&mut *writer, &symbol).map_err(io::Error::from)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(summary)
+}
@@ -0,0 +1,87 @@
+//! Tab expansion (`--expand-tabs`).
+//!
+//! Tabs become spaces up to the next tab stop, counted from the start of the
+//! file's own line: line numbers added afterwards don't shift the stops, so code
+//! stays aligned however it is rendered.
+
+use std::io::{self, Read};
+
+/// Expands the tabs of `content` with tab stops every `width` columns.
+pub fn expand_tabs(content: &str, width: usize) -> String {
+    if !content.contains('\t') {
+        return content.to_string();
+    }
+    let mut expanded = Vec::with_capacity(content.len());
+    expand(content.as_bytes(), width, &mut 0, &mut expanded);
+    String::from_utf8(expanded).expect("only ASCII tabs were replaced")
+}
+
+/// A reader expanding the tabs of another, for streamed files.
+pub struct ExpandTabs<R> {
+    inner: R,
+    width: usize,
+    column: usize,
+    /// Expanded bytes not read yet, from `position` on.
+    expanded: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> ExpandTabs<R> {
+    pub fn new(inner: R, width: usize) -> Self {
+        Self {
+            inner,
+            width,
+            column: 0,
+            expanded: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for ExpandTabs<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.expanded.len() {
+            let mut chunk = [0; 8 * 1024];
+            let read = self.inner.read(&mut chunk)?;
+            self.expanded.clear();
+            self.position = 0;
+            expand(
+                &chunk[..read],
+                self.width,
+                &mut self.column,
+                &mut self.expanded,
+            );
+        }
+        let available = &self.expanded[self.position..];
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.position += n;
+        Ok(n)
+    }
+}
+
+/// Expands the tabs of `bytes` into `out`, `column` being the current column.
+///
+/// Columns count characters: UTF-8 continuation bytes don't advance them, so
+/// `bytes` may start or end in the middle of a character.
+fn expand(bytes: &[u8], width: usize, column: &mut usize, out: &mut Vec<u8>) {
+    let width = width.max(1);
+    for &byte in bytes {
+        match byte {
+            b'\t' => {
+                let spaces = width - *column % width;
+                out.resize(out.len() + spaces, b' ');
+                *column += spaces;
+            }
+            b'\n' => {
+                out.push(byte);
+                *column = 0;
+            }
+            0x80..=0xBF => out.push(byte), // Continuation byte of a character
+            _ => {
+                out.push(byte);
+                *column += 1;
+            }
+        }
+    }
+}
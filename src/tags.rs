@@ -0,0 +1,70 @@
+//! Per-file tags from path rules, and `--tag` / `--exclude-tag`; and the
+//! named pattern sets of `--use-set` / `--skip-set`, which work the same way.
+
+use crate::format::display_path;
+use crate::{AppError, Options};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Matches files against the tag rules of the configuration file.
+pub struct Tagger {
+    rules: Vec<(String, Gitignore)>,
+}
+
+impl Tagger {
+    /// Compiles the rules (tag -> path patterns in gitignore syntax).
+    pub fn new(rules: &BTreeMap<String, Vec<String>>) -> Result<Self, AppError> {
+        let rules = rules
+            .iter()
+            .map(|(tag, patterns)| {
+                let mut builder = GitignoreBuilder::new("");
+                for pattern in patterns {
+                    builder.add_line(None, pattern)?;
+                }
+                Ok((tag.clone(), builder.build()?))
+            })
+            .collect::<Result<_, ignore::Error>>()?;
+        Ok(Self { rules })
+    }
+
+    /// The tags of the file at `path`, in alphabetical order.
+    pub fn tags(&self, path: &Path) -> Vec<&str> {
+        let path = display_path(path);
+        self.rules
+            .iter()
+            .filter(|(_, patterns)| {
+                patterns
+                    .matched_path_or_any_parents(path, false)
+                    .is_ignore()
+            })
+            .map(|(tag, _)| tag.as_str())
+            .collect()
+    }
+
+    /// Whether the file at `path` carries one of the `wanted` tags (if any) and
+    /// none of the `unwanted` ones, as for `--tag` and `--exclude-tag`.
+    pub fn selects(&self, path: &Path, wanted: &[String], unwanted: &[String]) -> bool {
+        if wanted.is_empty() && unwanted.is_empty() {
+            return true;
+        }
+        let tags = self.tags(path);
+        let has_any = |names: &[String]| tags.iter().any(|tag| names.iter().any(|n| n == tag));
+        (wanted.is_empty() || has_any(wanted)) && !has_any(unwanted)
+    }
+
+    /// Fails on the names of `options` (--use-set, --skip-set) that aren't rules.
+    pub fn check_sets(&self, options: &Options) -> Result<(), AppError> {
+        let unknown = options
+            .use_sets
+            .iter()
+            .chain(&options.skip_sets)
+            .find(|name| !self.rules.iter().any(|(set, _)| set == *name));
+        match unknown {
+            Some(name) => Err(AppError::Config(format!(
+                "no pattern set '{name}' in the [sets] of the config file"
+            ))),
+            None => Ok(()),
+        }
+    }
+}
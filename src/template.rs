@@ -0,0 +1,43 @@
+//! Variables in the instructions sent along with the files (`--system`,
+//! `--prompt`), so they can refer to the run: `{{git_branch}}`,
+//! `{{git_commit}}`, `{{date}}`, `{{env.VAR}}` and `{{file_count}}`.
+//!
+//! Git variables come from the repository of the first input path. Variables
+//! without a value (no repository, unset environment variable) expand to an
+//! empty string; unknown ones are left as written, with a warning.
+
+use crate::{Options, git, provenance};
+use std::env;
+use tracing::warn;
+
+/// Expands the variables of `text`, for a run that included `file_count` files.
+pub fn expand(text: &str, options: &Options, file_count: usize) -> String {
+    let dir = options.paths.first().map(|path| git::dir_of(path));
+    let mut expanded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        let name = rest[start + 2..end].trim();
+        let value = match name {
+            "git_branch" => Some(dir.and_then(git::branch).unwrap_or_default()),
+            "git_commit" => Some(dir.and_then(git::commit).unwrap_or_default()),
+            "date" => Some(provenance::timestamp()[..10].to_string()), // YYYY-MM-DD
+            "file_count" => Some(file_count.to_string()),
+            _ => name
+                .strip_prefix("env.")
+                .map(|variable| env::var(variable).unwrap_or_default()),
+        };
+        if let Some(value) = value {
+            expanded.push_str(&value);
+        } else {
+            warn!("Unknown template variable {{{{{name}}}}}, left as is");
+            expanded.push_str(&rest[start..end + 2]);
+        }
+        rest = &rest[end + 2..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
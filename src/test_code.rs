@@ -0,0 +1,180 @@
+//! Recognizing test code (`--no-tests`, `--tests-only`), by path conventions
+//! and, for Rust, `#![cfg(test)]` modules, and pairing tests with the sources
+//! they test (`--with-tests`, `--with-sources`).
+
+use crate::format::{language, normalize, relative_to_root};
+use crate::provider::FileProvider;
+use crate::{AppError, Options};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Directory names (and file stems) holding tests.
+const TEST_DIRS: &[&str] = &["test", "tests", "__tests__", "spec", "specs"];
+
+/// File name suffixes of tests, by language convention.
+const TEST_SUFFIXES: &[&str] = &[
+    "_test.go",
+    "_test.py",
+    "_test.rs",
+    "_spec.rb",
+    "_test.rb",
+    "Test.java",
+    "Tests.java",
+    "Test.kt",
+    "Tests.cs",
+];
+
+/// Whether the test filters are in use.
+pub fn is_filtering(options: &Options) -> bool {
+    options.no_tests || options.tests_only
+}
+
+/// Whether the file at `path` passes --no-tests or --tests-only.
+///
+/// Only the part of the path under its input path counts, so that all of
+/// `~/test/project` isn't taken for tests.
+pub fn selects(provider: &dyn FileProvider, path: &Path, options: &Options) -> bool {
+    is_test(provider, path, relative_to_root(path, options)) == options.tests_only
+}
+
+/// Adds the tests of the sources in `files` (--with-tests) and the sources of
+/// the tests (--with-sources), each right after its counterpart.
+///
+/// Counterparts are looked for under the input paths and the current directory
+/// (tests often live in a `tests` directory next to `src`).
+/// Among the files named after each other (`parser.py` and `test_parser.py`,
+/// `Foo.java` and `FooTest.java`, ...), the closest ones in the tree are taken.
+pub fn add_counterparts(
+    files: &mut Vec<PathBuf>,
+    provider: &dyn FileProvider,
+    options: &Options,
+    threads: usize,
+) -> Result<(), AppError> {
+    let mut wider = options.clone();
+    wider.paths.push(PathBuf::from("."));
+    let candidates: Vec<(PathBuf, bool)> = provider
+        .collect_files(&wider, threads)?
+        .into_iter()
+        .map(|path| {
+            let is_test = is_test(provider, &path, relative_to_root(&path, &wider));
+            (normalize(&path), is_test)
+        })
+        .collect();
+
+    let mut seen: HashSet<PathBuf> = files.iter().map(|path| normalize(path)).collect();
+    let mut paired = Vec::with_capacity(files.len());
+    for path in files.drain(..) {
+        let test = is_test(provider, &path, relative_to_root(&path, options));
+        let wanted = if test {
+            options.with_sources
+        } else {
+            options.with_tests
+        };
+        let name = if test {
+            tested_name(&path)
+        } else {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map(str::to_string)
+        };
+        let counterparts: Vec<&Path> = match name.filter(|_| wanted) {
+            Some(name) => closest(
+                &normalize(&path),
+                candidates.iter().filter(|(candidate, candidate_is_test)| {
+                    *candidate_is_test != test
+                        && if test {
+                            candidate.file_name().and_then(|name| name.to_str()) == Some(&name)
+                        } else {
+                            tested_name(candidate).as_deref() == Some(&name)
+                        }
+                }),
+            ),
+            None => Vec::new(),
+        };
+        paired.push(path);
+        for counterpart in counterparts {
+            if seen.insert(counterpart.to_path_buf()) {
+                paired.push(counterpart.to_path_buf());
+            }
+        }
+    }
+    *files = paired;
+    Ok(())
+}
+
+/// The `candidates` sharing the longest leading directories with `path`.
+fn closest<'a>(
+    path: &Path,
+    candidates: impl Iterator<Item = &'a (PathBuf, bool)>,
+) -> Vec<&'a Path> {
+    let shared = |candidate: &Path| {
+        path.components()
+            .zip(candidate.components())
+            .take_while(|(a, b)| a == b)
+            .count()
+    };
+    let mut best = Vec::new();
+    let mut best_shared = 0;
+    for (candidate, _) in candidates {
+        let shared = shared(candidate);
+        if best.is_empty() || shared > best_shared {
+            best = vec![candidate.as_path()];
+            best_shared = shared;
+        } else if shared == best_shared {
+            best.push(candidate.as_path());
+        }
+    }
+    best
+}
+
+/// The file name of the source tested by the test file at `path`: without the
+/// test prefix or suffix of its name (`test_parser.py`, `parser_test.go`,
+/// `ParserTest.java`, `parser.spec.ts`), or its own for a plain file in a test
+/// directory (`tests/parser.rs`).
+fn tested_name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let (stem, extension) = name.split_once('.')?;
+    let extension = extension
+        .strip_prefix("test.")
+        .or_else(|| extension.strip_prefix("spec."))
+        .unwrap_or(extension);
+    let stem = stem
+        .strip_prefix("test_")
+        .or_else(|| stem.strip_suffix("_test"))
+        .or_else(|| stem.strip_suffix("_spec"))
+        .or_else(|| stem.strip_suffix("Tests"))
+        .or_else(|| stem.strip_suffix("Test"))
+        .unwrap_or(stem);
+    (!stem.is_empty() && !TEST_DIRS.contains(&stem)).then(|| format!("{stem}.{extension}"))
+}
+
+/// Whether the file at `path` (`relative` to its input path) is test code.
+fn is_test(provider: &dyn FileProvider, path: &Path, relative: &Path) -> bool {
+    let in_test_dir = relative.components().any(|component| match component {
+        Component::Normal(name) => name.to_str().is_some_and(|name| TEST_DIRS.contains(&name)),
+        _ => false,
+    });
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return in_test_dir;
+    };
+    let stem = name.split('.').next().unwrap_or(name);
+    in_test_dir
+        || TEST_DIRS.contains(&stem)
+        || stem.starts_with("test_")
+        || TEST_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+        // foo.test.ts, foo.spec.jsx...
+        || name.split('.').skip(1).any(|part| part == "test" || part == "spec")
+        || (language(path) == Some("rust") && is_rust_test_module(provider, path))
+}
+
+/// Whether the Rust file at `path` only compiles in tests (`#![cfg(test)]`).
+fn is_rust_test_module(provider: &dyn FileProvider, path: &Path) -> bool {
+    let Ok(content) = provider.read_text(path) else {
+        return false;
+    };
+    content
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("//"))
+        .is_some_and(|line| line.replace(' ', "") == "#![cfg(test)]")
+}
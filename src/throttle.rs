@@ -0,0 +1,100 @@
+//! Read throughput limit (`--throttle-mbps`), so scheduled runs on shared
+//! machines don't saturate the disks.
+
+use crate::mmap::FileContent;
+use crate::provider::FileProvider;
+use crate::{AppError, Options};
+use std::borrow::Cow;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Paces the bytes read by all threads to a rate.
+#[derive(Debug)]
+struct Pacer {
+    bytes_per_second: f64,
+    /// When the bytes read so far will have been paid for.
+    next: Mutex<Instant>,
+}
+
+impl Pacer {
+    /// Charges `bytes` read, sleeping until they (and the earlier reads) fit the rate.
+    fn charge(&self, bytes: usize) {
+        #[allow(clippy::cast_precision_loss)] // Exact enough for a rate
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_second);
+        let paid = {
+            let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+            *next = (*next).max(Instant::now()) + cost;
+            *next
+        };
+        thread::sleep(paid.saturating_duration_since(Instant::now()));
+    }
+}
+
+/// A provider reading from another one at no more than a rate.
+#[derive(Debug)]
+pub struct Throttled<P> {
+    inner: P,
+    pacer: Pacer,
+}
+
+impl<P: FileProvider> Throttled<P> {
+    /// Limits reads from `inner` to `megabytes_per_second` (MB/s, 10^6 bytes).
+    pub fn new(inner: P, megabytes_per_second: f64) -> Self {
+        Self {
+            inner,
+            pacer: Pacer {
+                bytes_per_second: megabytes_per_second * 1_000_000.0,
+                next: Mutex::new(Instant::now()),
+            },
+        }
+    }
+}
+
+impl<P: FileProvider> FileProvider for Throttled<P> {
+    fn exists(&self, path: &Path) -> bool {
+        self.inner.exists(path)
+    }
+
+    fn collect_files(&self, options: &Options, threads: usize) -> Result<Vec<PathBuf>, AppError> {
+        self.inner.collect_files(options, threads)
+    }
+
+    fn len(&self, path: &Path) -> io::Result<u64> {
+        self.inner.len(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Cow<'_, [u8]>> {
+        let content = self.inner.read(path)?;
+        self.pacer.charge(content.len());
+        Ok(content)
+    }
+
+    fn read_text(&self, path: &Path) -> io::Result<FileContent> {
+        let content = self.inner.read_text(path)?;
+        self.pacer.charge(content.len());
+        Ok(content)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(ThrottledReader {
+            inner: self.inner.open(path)?,
+            pacer: &self.pacer,
+        }))
+    }
+}
+
+struct ThrottledReader<'a> {
+    inner: Box<dyn Read + 'a>,
+    pacer: &'a Pacer,
+}
+
+impl Read for ThrottledReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.pacer.charge(read);
+        Ok(read)
+    }
+}
@@ -0,0 +1,128 @@
+//! The `--timings` report: where the wall-clock time of a run went, and the
+//! files that took longest.
+//!
+//! Reading and transforming happen on the worker threads, so their times are
+//! summed over the threads and can exceed the run's wall-clock time. Large
+//! files streamed to the output are read while writing, and count as writing.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A phase of a run.
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    /// Listing the files under the input paths.
+    Walk,
+    /// The selection filters, ordering and sampling.
+    Select,
+    /// Estimating tokens, to --pack the files into --max-tokens.
+    Tokenize,
+    /// Reading the files.
+    Read,
+    /// The filter chain and formatting.
+    Transform,
+    /// Writing the output.
+    Write,
+}
+
+const PHASES: [(Phase, &str); 6] = [
+    (Phase::Walk, "walk"),
+    (Phase::Select, "select"),
+    (Phase::Tokenize, "tokenize"),
+    (Phase::Read, "read"),
+    (Phase::Transform, "transform"),
+    (Phase::Write, "write"),
+];
+
+/// The time spent in each phase of a run, and on its slowest files.
+#[derive(Debug, Default)]
+pub struct Timings {
+    total: Duration,
+    threads: usize,
+    phases: [Duration; PHASES.len()],
+    /// The slowest files and the time spent on them, slowest first.
+    slowest: Vec<(Duration, PathBuf)>,
+    /// How many of the slowest files to keep.
+    keep: usize,
+}
+
+impl Timings {
+    /// Creates the timings of a run on `threads` threads, keeping the `keep` slowest files.
+    pub fn new(threads: usize, keep: usize) -> Self {
+        Self {
+            threads,
+            keep,
+            ..Self::default()
+        }
+    }
+
+    /// Adds `duration` to the time spent in `phase`.
+    pub fn add(&mut self, phase: Phase, duration: Duration) {
+        self.phases[phase as usize] += duration;
+    }
+
+    /// Records the time spent reading, transforming and writing the file at `path`.
+    pub fn add_file(&mut self, path: &Path, read: Duration, transform: Duration, write: Duration) {
+        self.add(Phase::Read, read);
+        self.add(Phase::Transform, transform);
+        self.add(Phase::Write, write);
+        let total = read + transform + write;
+        let at = self.slowest.partition_point(|(slower, _)| *slower >= total);
+        if at < self.keep {
+            self.slowest.insert(at, (total, path.to_path_buf()));
+            self.slowest.truncate(self.keep);
+        }
+    }
+
+    /// Sets the wall-clock time of the whole run.
+    pub fn set_total(&mut self, total: Duration) {
+        self.total = total;
+    }
+
+    /// Writes the report: the phases, then the slowest files.
+    pub fn write(&self, writer: &mut dyn Write) -> io::Result<()> {
+        let plural = if self.threads == 1 { "" } else { "s" };
+        writeln!(
+            writer,
+            "Timings: {} wall clock, {} thread{plural}",
+            format_duration(self.total),
+            self.threads
+        )?;
+        for (phase, name) in PHASES {
+            let note = match phase {
+                Phase::Read | Phase::Transform if self.threads > 1 => " (summed over threads)",
+                _ => "",
+            };
+            writeln!(
+                writer,
+                "  {name:<10}{:>10}{note}",
+                format_duration(self.phases[phase as usize])
+            )?;
+        }
+        if !self.slowest.is_empty() {
+            writeln!(writer, "Slowest files:")?;
+            for (duration, path) in &self.slowest {
+                writeln!(
+                    writer,
+                    "  {:>10}  {}",
+                    format_duration(*duration),
+                    path.display()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats `duration` in the largest unit keeping it above one: "1.25 s", "340 ms", "12 µs".
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs_f64();
+    if seconds >= 1.0 {
+        format!("{seconds:.2} s")
+    } else if duration.as_millis() >= 1 {
+        format!("{} ms", duration.as_millis())
+    } else {
+        format!("{} µs", duration.as_micros())
+    }
+}
@@ -0,0 +1,13 @@
+//! Token count estimates.
+//!
+//! Exact counts depend on the model's tokenizer; about four bytes per token is
+//! the usual rule of thumb for code and English text, and needs no vocabulary.
+
+/// Average number of bytes per token.
+pub const BYTES_PER_TOKEN: usize = 4;
+
+/// Estimates the number of tokens `text` takes up in a prompt.
+#[must_use]
+pub fn estimate(text: &str) -> usize {
+    text.len().div_ceil(BYTES_PER_TOKEN)
+}
@@ -0,0 +1,83 @@
+//! Numbers for people, in summaries such as `--repo-summary` and `cloc`: sizes
+//! in binary (`1.5 KiB`, the default) or SI (`1.5 kB`, `--si`) units, and counts
+//! with thousands separators, both in the conventions of the locale (from
+//! `LC_ALL`, `LC_NUMERIC` or `LANG`).
+
+use crate::Options;
+use std::env;
+
+/// Languages writing `1.234.567,8`.
+const DOT_GROUPING: &[&str] = &[
+    "de", "es", "it", "nl", "pt", "da", "id", "tr", "el", "ro", "hr", "sl", "sr", "vi",
+];
+
+/// Languages writing `1 234 567,8` (with no-break spaces).
+const SPACE_GROUPING: &[&str] = &[
+    "fr", "ru", "pl", "cs", "sk", "sv", "fi", "nb", "nn", "no", "uk", "hu", "bg", "lt", "lv", "et",
+];
+
+/// How sizes and counts are written.
+pub struct Units {
+    si: bool,
+    thousands: char,
+    decimal: char,
+}
+
+impl Units {
+    /// The units selected by `options` (--si, --binary), in the conventions of
+    /// the locale.
+    pub fn new(options: &Options) -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .iter()
+            .find_map(|name| env::var(name).ok().filter(|value| !value.is_empty()))
+            .unwrap_or_default();
+        let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+        let (thousands, decimal) = if DOT_GROUPING.contains(&language) {
+            ('.', ',')
+        } else if SPACE_GROUPING.contains(&language) {
+            ('\u{a0}', ',')
+        } else {
+            (',', '.')
+        };
+        Self {
+            si: options.si,
+            thousands,
+            decimal,
+        }
+    }
+
+    /// Formats a size in bytes, as in `1.5 KiB` (or `1.5 kB` with --si).
+    pub fn size(&self, bytes: u64) -> String {
+        let (base, units) = if self.si {
+            (1000.0, ["kB", "MB", "GB", "TB"])
+        } else {
+            (1024.0, ["KiB", "MiB", "GiB", "TiB"])
+        };
+        #[allow(clippy::cast_precision_loss)] // Only one decimal is shown
+        let mut size = bytes as f64;
+        if size < base {
+            return format!("{} B", self.count(bytes));
+        }
+        size /= base;
+        let mut unit = 0;
+        while size >= base && unit < units.len() - 1 {
+            size /= base;
+            unit += 1;
+        }
+        let size = format!("{size:.1}").replace('.', &self.decimal.to_string());
+        format!("{size} {}", units[unit])
+    }
+
+    /// Formats a count with thousands separators, as in `1,234,567`.
+    pub fn count(&self, count: u64) -> String {
+        let digits = count.to_string();
+        let mut grouped = String::with_capacity(digits.len() * 4 / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push(self.thousands);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+}
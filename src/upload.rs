@@ -0,0 +1,98 @@
+//! Uploading the output to S3 (`--output s3://bucket/key`, `s3` feature).
+//!
+//! The output is written to a staging file first, then uploaded in one request
+//! once the run has succeeded.
+
+use files_ingest::AppError;
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// An object to upload the output to.
+pub struct Destination {
+    pub bucket: String,
+    pub key: String,
+    /// Where the output is written before the upload: a private file with a
+    /// random name, removed when dropped.
+    pub staging: NamedTempFile,
+}
+
+/// Returns the S3 destination named by `--output`, if it is an `s3://` URL.
+pub fn destination(output: &Path) -> Result<Option<Destination>, AppError> {
+    let Some(location) = output.to_str().and_then(|url| url.strip_prefix("s3://")) else {
+        return Ok(None);
+    };
+    let (bucket, key) = match location.split_once('/') {
+        Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => (bucket, key),
+        _ => {
+            return Err(AppError::Download(format!(
+                "expected s3://bucket/key, found '{}'",
+                output.display()
+            )));
+        }
+    };
+    let destination = Destination {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        staging: tempfile::Builder::new()
+            .prefix("files-ingest-")
+            .suffix(".upload")
+            .tempfile()?,
+    };
+    #[cfg(not(feature = "s3"))]
+    upload(&destination)?; // Fails, before the run rather than after
+    Ok(Some(destination))
+}
+
+/// Uploads the staged output to its destination if the run `succeeded`, and
+/// removes the staging file.
+pub fn finish(destination: Destination, succeeded: bool) -> Result<(), AppError> {
+    let uploaded = if succeeded {
+        upload(&destination)
+    } else {
+        Ok(())
+    };
+    let _ = destination.staging.close();
+    uploaded
+}
+
+/// Uploads the staged output to its destination.
+#[cfg(feature = "s3")]
+fn upload(destination: &Destination) -> Result<(), AppError> {
+    use aws_sdk_s3::Client;
+    use aws_sdk_s3::error::DisplayErrorContext;
+    use aws_sdk_s3::primitives::ByteStream;
+
+    let failed = |error: &dyn std::fmt::Display| {
+        AppError::Download(format!(
+            "uploading to s3://{}/{}: {error}",
+            destination.bucket, destination.key
+        ))
+    };
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+        let body = ByteStream::from_path(destination.staging.path())
+            .await
+            .map_err(|e| failed(&e))?;
+        Client::new(&config)
+            .put_object()
+            .bucket(&destination.bucket)
+            .key(&destination.key)
+            .content_type("text/plain; charset=utf-8")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| failed(&DisplayErrorContext(e)))?;
+        Ok(())
+    })
+}
+
+#[cfg(not(feature = "s3"))]
+fn upload(destination: &Destination) -> Result<(), AppError> {
+    Err(AppError::Download(format!(
+        "s3://{}/{}: s3:// outputs need files-ingest built with the `s3` feature",
+        destination.bucket, destination.key
+    )))
+}
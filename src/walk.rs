@@ -0,0 +1,427 @@
+//! Directory walking and file selection.
+
+use crate::Options;
+use clap::ValueEnum;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::{DirEntry, WalkBuilder, WalkState}; // For directory traversal respecting .gitignore etc.
+use std::ffi::OsStr;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError};
+use tracing::warn;
+
+/// What to do with errors encountered while walking directories (permission denied, ...).
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WalkErrorPolicy {
+    /// Print a warning and carry on.
+    #[default]
+    Warn,
+    /// Carry on silently.
+    Skip,
+    /// Abort the run.
+    Fail,
+}
+
+/// Applies the `--on-walk-error` policy to a walk error.
+///
+/// Returns the error back if the run should be aborted.
+fn handle_walk_error(err: ignore::Error, policy: WalkErrorPolicy) -> Result<(), ignore::Error> {
+    match policy {
+        WalkErrorPolicy::Warn => {
+            // Handle errors during the walk (could be permission issues, invalid patterns, etc.)
+            warn!("Error during directory walk: {err}");
+            Ok(())
+        }
+        WalkErrorPolicy::Skip => Ok(()),
+        WalkErrorPolicy::Fail => Err(err),
+    }
+}
+
+/// Builds the matcher for the `--ignore` patterns, relative to `root`.
+///
+/// Overrides normally whitelist what they match; negated, they ignore it.
+pub fn ignore_patterns(options: &Options, root: &Path) -> Result<Override, ignore::Error> {
+    let mut builder = OverrideBuilder::new(root);
+    for pattern in &options.ignore_patterns {
+        builder.add(&format!("!{pattern}"))?;
+    }
+    builder.build()
+}
+
+/// The hidden files and directories let in by `--include-hidden-pattern`.
+pub struct HiddenAllowlist {
+    /// A matcher per root.
+    matchers: Vec<Gitignore>,
+    /// The components of the patterns anchored to the root (those with a slash).
+    anchored: Vec<Vec<String>>,
+}
+
+impl HiddenAllowlist {
+    pub fn new(patterns: &[String], roots: &[PathBuf]) -> Result<Self, ignore::Error> {
+        let mut matchers = Vec::new();
+        for root in roots {
+            let mut builder = GitignoreBuilder::new(root);
+            for pattern in patterns {
+                builder.add_line(None, pattern)?;
+            }
+            matchers.push(builder.build()?);
+        }
+        let anchored = patterns
+            .iter()
+            .map(|pattern| pattern.trim_end_matches('/'))
+            .filter(|pattern| pattern.contains('/'))
+            .map(|pattern| {
+                let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+                pattern.split('/').map(str::to_string).collect()
+            })
+            .collect();
+        Ok(Self { matchers, anchored })
+    }
+
+    /// Whether the walk may keep `path`: it is not hidden, or it matches a pattern,
+    /// or (for directories) a pattern may match something inside it.
+    pub fn allows(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(matcher) = self
+            .matchers
+            .iter()
+            .find(|matcher| path.starts_with(matcher.path()))
+        else {
+            return true;
+        };
+        let relative = path.strip_prefix(matcher.path()).unwrap_or(path);
+        if !is_hidden(relative)
+            || matcher
+                .matched_path_or_any_parents(path, is_dir)
+                .is_ignore()
+        {
+            return true;
+        }
+        is_dir && self.may_match_inside(relative)
+    }
+
+    /// Whether an anchored pattern may match a path inside `dir` (relative to the root).
+    ///
+    /// Components with wildcards are assumed to match, so this errs on the side of walking.
+    fn may_match_inside(&self, dir: &Path) -> bool {
+        let components: Vec<_> = dir.components().collect();
+        self.anchored.iter().any(|pattern| {
+            pattern.len() > components.len()
+                && pattern.iter().zip(&components).all(|(glob, component)| {
+                    glob.contains(['*', '?', '[']) || component.as_os_str() == glob.as_str()
+                })
+        })
+    }
+}
+
+/// Whether any component of `path` is a hidden (dot) file or directory.
+pub fn is_hidden(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => name.as_encoded_bytes().starts_with(b"."),
+        _ => false,
+    })
+}
+
+/// Creates the directory walker for an input path, configured from the CLI options.
+///
+/// `--ignore` patterns are left to [`should_process_entry`] with `--ignore-files-only`,
+/// so that they don't prune directories.
+pub fn build_walker(options: &Options, root: &Path) -> Result<WalkBuilder, ignore::Error> {
+    let mut walker_builder = WalkBuilder::new(root);
+
+    let hidden_patterns = !options.include_hidden && !options.include_hidden_patterns.is_empty();
+    walker_builder
+        .hidden(!options.include_hidden && !hidden_patterns) // Respect --include-hidden flag
+        .git_ignore(!options.ignore_gitignore) // Respect --ignore-gitignore flag
+        .git_global(!options.ignore_gitignore)
+        .git_exclude(!options.ignore_gitignore)
+        .require_git(false) // Don't require a git repo to exist
+        .ignore(!options.ignore_gitignore); // Also respect .ignore files
+
+    // Let in the hidden entries matching --include-hidden-pattern, and no other
+    if hidden_patterns {
+        let allowlist = Arc::new(HiddenAllowlist::new(
+            &options.include_hidden_patterns,
+            &[root.to_path_buf()],
+        )?);
+        walker_builder.filter_entry(move |entry| {
+            entry.depth() == 0
+                || allowlist.allows(
+                    entry.path(),
+                    entry.file_type().is_some_and(|ft| ft.is_dir()),
+                )
+        });
+    }
+
+    // Add custom ignore patterns
+    if !options.ignore_files_only {
+        walker_builder.overrides(ignore_patterns(options, root)?);
+    }
+    for path in &options.ignore_files {
+        if let Some(err) = walker_builder.add_ignore(path) {
+            return Err(err);
+        }
+    }
+
+    Ok(walker_builder)
+}
+
+/// Walks all input paths and returns the files to process, in a deterministic order.
+///
+/// Each root is walked on its own, so that roots keep their command-line order
+/// and anchored `--ignore` patterns are relative to their root. With more than
+/// one thread the walk runs in parallel, so the root's files are sorted
+/// afterwards; the sequential walk visits entries sorted by name, which yields
+/// the same order.
+pub fn collect_files(options: &Options, threads: usize) -> Result<Vec<PathBuf>, ignore::Error> {
+    let mut files = Vec::new();
+    for root in &options.paths {
+        let file_ignores = if options.ignore_files_only {
+            Some(ignore_patterns(options, root)?)
+        } else {
+            None
+        };
+        let walker_builder = build_walker(options, root)?;
+        let mut found = if threads <= 1 {
+            walk_sequential(walker_builder, options, file_ignores.as_ref())?
+        } else {
+            walk_parallel(walker_builder, options, file_ignores.as_ref(), threads)?
+        };
+        files.append(&mut found);
+    }
+    skip_linked_duplicates(&mut files);
+    Ok(files)
+}
+
+/// Walks a root on the current thread, visiting entries sorted by name.
+fn walk_sequential(
+    mut walker_builder: WalkBuilder,
+    options: &Options,
+    file_ignores: Option<&Override>,
+) -> Result<Vec<PathBuf>, ignore::Error> {
+    walker_builder.sort_by_file_name(Ord::cmp);
+    let mut files = Vec::new();
+    for result in walker_builder.build() {
+        match result {
+            Ok(entry) => {
+                if should_process_entry(&entry, options, file_ignores) {
+                    files.push(entry.into_path());
+                }
+            }
+            Err(err) => handle_walk_error(err, options.on_walk_error)?,
+        }
+    }
+    Ok(files)
+}
+
+/// Walks a root on `threads` threads, returning its files sorted.
+fn walk_parallel(
+    mut walker_builder: WalkBuilder,
+    options: &Options,
+    file_ignores: Option<&Override>,
+    threads: usize,
+) -> Result<Vec<PathBuf>, ignore::Error> {
+    let found = Mutex::new(Vec::new());
+    let failure = Mutex::new(None);
+    walker_builder.threads(threads).build_parallel().run(|| {
+        Box::new(|result| {
+            match result {
+                Ok(entry) => {
+                    if should_process_entry(&entry, options, file_ignores) {
+                        found
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .push(entry.into_path());
+                    }
+                }
+                Err(err) => {
+                    if let Err(err) = handle_walk_error(err, options.on_walk_error) {
+                        failure
+                            .lock()
+                            .unwrap_or_else(PoisonError::into_inner)
+                            .get_or_insert(err);
+                        return WalkState::Quit;
+                    }
+                }
+            }
+            WalkState::Continue
+        })
+    });
+    if let Some(err) = failure.into_inner().unwrap_or_else(PoisonError::into_inner) {
+        return Err(err);
+    }
+    let mut found = found.into_inner().unwrap_or_else(PoisonError::into_inner);
+    found.sort();
+    Ok(found)
+}
+
+/// Keeps only the first path to each file: other hardlinks to it, or the same
+/// file seen through a bind mount (same device and inode), are dropped.
+/// Symlinks are paths of their own, not the file they point to.
+#[cfg(unix)]
+fn skip_linked_duplicates(files: &mut Vec<PathBuf>) {
+    use std::collections::HashSet;
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    let mut seen = HashSet::new();
+    files.retain(|path| match fs::symlink_metadata(path) {
+        Ok(metadata) => seen.insert((metadata.dev(), metadata.ino())),
+        Err(_) => true, // Reported when read
+    });
+}
+
+#[cfg(not(unix))]
+fn skip_linked_duplicates(_files: &mut Vec<PathBuf>) {}
+
+/// Checks if a directory entry should be processed based on CLI options.
+///
+/// `file_ignores` holds the `--ignore` patterns with `--ignore-files-only`.
+pub fn should_process_entry(
+    entry: &DirEntry,
+    options: &Options,
+    file_ignores: Option<&Override>,
+) -> bool {
+    // Only process files
+    if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+        return false;
+    }
+
+    let path = entry.path();
+
+    // Opening CON or NUL opens the device, not the file
+    if cfg!(windows) && is_reserved_name(entry.file_name()) {
+        warn!(
+            "Skipping {}: reserved device name on Windows",
+            path.display()
+        );
+        return false;
+    }
+
+    // Files passed explicitly are wanted, whatever the filters say
+    if entry.depth() == 0 {
+        return !options.no_force_explicit || is_walked(path, options, file_ignores);
+    }
+
+    // Filter by extension if specified
+    if !has_included_extension(path, options) {
+        return false;
+    }
+
+    // Apply --ignore patterns to files only if --ignore-files-only is set
+    if let Some(file_ignores) = file_ignores
+        && file_ignores.matched(path, false).is_ignore()
+    {
+        return false;
+    }
+
+    true // Process this entry
+}
+
+/// Whether the file at `path` passes the filters, as if found walking its directory.
+///
+/// The walker never filters out its roots, so this walks the parent directory instead.
+fn is_walked(path: &Path, options: &Options, file_ignores: Option<&Override>) -> bool {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let Ok(mut walker_builder) = build_walker(options, parent) else {
+        return false;
+    };
+    walker_builder
+        .max_depth(Some(1))
+        .build()
+        .flatten()
+        .any(|entry| {
+            entry.depth() == 1
+                && entry.file_name() == path.file_name().unwrap_or_default()
+                && should_process_entry(&entry, options, file_ignores)
+        })
+}
+
+/// Whether `name` is a device name on Windows (`CON`, `NUL`, `COM1`, ...), with
+/// any extension: `nul.txt` names the NUL device too.
+pub fn is_reserved_name(name: &OsStr) -> bool {
+    const DEVICES: &[&str] = &["CON", "PRN", "AUX", "NUL"];
+    let Some(name) = name.to_str() else {
+        return false;
+    };
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    DEVICES
+        .iter()
+        .any(|device| stem.eq_ignore_ascii_case(device))
+        || ["COM", "LPT"].iter().any(|device| {
+            stem.len() == 4
+                && stem.is_char_boundary(3)
+                && stem[..3].eq_ignore_ascii_case(device)
+                && matches!(stem.as_bytes()[3], b'1'..=b'9')
+        })
+}
+
+/// Checks the `--extension` filter: whether `path` has one of the requested extensions, if any.
+pub fn has_included_extension(path: &Path, options: &Options) -> bool {
+    if options.extensions.is_empty() {
+        return true;
+    }
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        // Case-insensitive compare
+        options
+            .extensions
+            .iter()
+            .any(|allowed_ext| ext.eq_ignore_ascii_case(allowed_ext))
+    } else {
+        false // No extension or invalid UTF-8 extension
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+
+    #[test]
+    fn anchored_ignore_patterns_are_relative_to_each_root() {
+        let tree = tempfile::tempdir().unwrap();
+        for file in [
+            "src/it.rs",
+            "src/lib.rs",
+            "tests/it.rs",
+            "tests/common/it.rs",
+        ] {
+            let path = tree.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let (src, tests) = (tree.path().join("src"), tree.path().join("tests"));
+        for files_only in [false, true] {
+            let mut args = vec!["files-ingest", "--ignore", "/it.rs"];
+            if files_only {
+                args.push("--ignore-files-only");
+            }
+            let mut options = Options::parse_from(args);
+            options.paths = vec![src.clone(), tests.clone()];
+            let sequential = collect_files(&options, 1).unwrap();
+            let parallel = collect_files(&options, 4).unwrap();
+            assert_eq!(sequential, parallel);
+            assert_eq!(sequential, [src.join("lib.rs"), tests.join("common/it.rs")]);
+        }
+    }
+
+    #[test]
+    fn reserved_names_are_device_names_with_any_extension() {
+        for name in [
+            "CON",
+            "nul",
+            "Aux.txt",
+            "prn.tar.gz",
+            "com1",
+            "LPT9.log",
+            "nul ",
+        ] {
+            assert!(is_reserved_name(OsStr::new(name)), "{name}");
+        }
+        for name in ["console", "nullable.rs", "com0", "COM10", "lpt", "con_"] {
+            assert!(!is_reserved_name(OsStr::new(name)), "{name}");
+        }
+    }
+}
@@ -0,0 +1,245 @@
+//! Workspace members as input paths (`--workspace-member`, `--package`).
+//!
+//! A Cargo member is resolved to its directory with `cargo metadata`, along with
+//! the local crates it depends on (path dependencies), transitively. A JS/TS
+//! package is looked up in the pnpm, yarn or npm workspace enclosing the current
+//! directory, optionally along with the workspace packages it depends on.
+
+use crate::{AppError, Options};
+use ignore::WalkBuilder;
+use ignore::gitignore::GitignoreBuilder;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// A workspace package, as far as resolution goes.
+struct Package {
+    dir: PathBuf,
+    /// The directories of its (non-dev) path dependencies.
+    path_dependencies: Vec<PathBuf>,
+}
+
+/// Adds the paths of the --workspace-member and --package of `options` to them.
+pub fn resolve(options: &mut Cow<Options>) -> Result<(), AppError> {
+    if !options.workspace_members.is_empty() {
+        let members = cargo_member_paths(&options.workspace_members)?;
+        options.to_mut().paths.extend(members);
+    }
+    if !options.packages.is_empty() {
+        let packages = js_package_paths(&options.packages, options.package_deps)?;
+        options.to_mut().paths.extend(packages);
+    }
+    Ok(())
+}
+
+/// Returns the input paths for the Cargo workspace members `names`: their
+/// directories and those of their path dependencies.
+fn cargo_member_paths(names: &[String]) -> Result<Vec<PathBuf>, AppError> {
+    let output = Command::new("cargo")
+        .args(["metadata", "--format-version", "1", "--no-deps"])
+        .output()
+        .map_err(|e| AppError::Workspace(format!("could not run cargo: {e}")))?;
+    if !output.status.success() {
+        let error = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Workspace(format!(
+            "cargo metadata failed: {}",
+            error.trim()
+        )));
+    }
+    let metadata: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| AppError::Workspace(format!("invalid cargo metadata: {e}")))?;
+
+    let mut packages = HashMap::new();
+    for package in metadata["packages"].as_array().into_iter().flatten() {
+        let (Some(name), Some(manifest)) =
+            (package["name"].as_str(), package["manifest_path"].as_str())
+        else {
+            continue;
+        };
+        let path_dependencies = package["dependencies"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|dependency| dependency["kind"].as_str() != Some("dev"))
+            .filter_map(|dependency| dependency["path"].as_str().map(PathBuf::from))
+            .collect();
+        let dir = Path::new(manifest)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        packages.insert(
+            name.to_string(),
+            Package {
+                dir,
+                path_dependencies,
+            },
+        );
+    }
+
+    // Follow the path dependencies from the requested members
+    let mut dirs = BTreeSet::new();
+    let mut pending: Vec<PathBuf> = Vec::new();
+    for name in names {
+        let package = packages.get(name).ok_or_else(|| {
+            let mut known: Vec<_> = packages.keys().map(String::as_str).collect();
+            known.sort_unstable();
+            AppError::Workspace(format!(
+                "no workspace member named {name} (members: {})",
+                known.join(", ")
+            ))
+        })?;
+        pending.push(package.dir.clone());
+    }
+    while let Some(dir) = pending.pop() {
+        if !dirs.insert(dir.clone()) {
+            continue;
+        }
+        if let Some(package) = packages.values().find(|package| package.dir == dir) {
+            pending.extend(package.path_dependencies.iter().cloned());
+        }
+    }
+
+    let member_dirs: Vec<&PathBuf> = packages.values().map(|package| &package.dir).collect();
+    let cwd = env::current_dir().unwrap_or_default();
+    let mut paths = Vec::new();
+    for dir in &dirs {
+        if member_dirs
+            .iter()
+            .any(|member| *member != dir && member.starts_with(dir))
+        {
+            // A workspace root package: only its own sources, not the other members
+            paths.extend(
+                ["Cargo.toml", "build.rs", "src"]
+                    .iter()
+                    .map(|name| dir.join(name))
+                    .filter(|path| path.exists()),
+            );
+        } else {
+            paths.push(dir.clone());
+        }
+    }
+    Ok(paths
+        .into_iter()
+        .map(|path| relative_to(path, &cwd))
+        .collect())
+}
+
+/// Returns the input paths for the JS/TS workspace packages `names`: their
+/// directories and, with `dependencies`, those of the workspace packages they
+/// depend on (transitively, ignoring devDependencies).
+fn js_package_paths(names: &[String], dependencies: bool) -> Result<Vec<PathBuf>, AppError> {
+    let cwd = env::current_dir()?;
+    let (root, patterns) = cwd
+        .ancestors()
+        .find_map(|dir| Some((dir, js_workspace_patterns(dir)?)))
+        .ok_or_else(|| {
+            AppError::Workspace(
+                "no pnpm-workspace.yaml or package.json with workspaces found".to_string(),
+            )
+        })?;
+
+    // Workspace globs match package directories; `!` patterns exclude some
+    let mut builder = GitignoreBuilder::new(root);
+    for pattern in &patterns {
+        builder
+            .add_line(None, pattern.trim_start_matches("./"))
+            .map_err(|e| AppError::Workspace(format!("invalid workspace pattern: {e}")))?;
+    }
+    let globs = builder
+        .build()
+        .map_err(|e| AppError::Workspace(format!("invalid workspace pattern: {e}")))?;
+
+    let mut packages = HashMap::new();
+    let walker = WalkBuilder::new(root)
+        .filter_entry(|entry| entry.file_name() != "node_modules")
+        .build();
+    for entry in walker.flatten() {
+        let dir = entry.path();
+        if dir == root || !globs.matched(dir, true).is_ignore() {
+            continue;
+        }
+        let Some(manifest) = read_json(&dir.join("package.json")) else {
+            continue;
+        };
+        let Some(name) = manifest["name"].as_str() else {
+            continue;
+        };
+        let dependencies: Vec<String> =
+            ["dependencies", "peerDependencies", "optionalDependencies"]
+                .iter()
+                .filter_map(|kind| manifest[kind].as_object())
+                .flat_map(|dependencies| dependencies.keys().cloned())
+                .collect();
+        packages.insert(name.to_string(), (dir.to_path_buf(), dependencies));
+    }
+
+    let mut selected = BTreeSet::new();
+    let mut pending: Vec<&str> = Vec::new();
+    for name in names {
+        if !packages.contains_key(name) {
+            let mut known: Vec<_> = packages.keys().map(String::as_str).collect();
+            known.sort_unstable();
+            return Err(AppError::Workspace(format!(
+                "no workspace package named {name} (packages: {})",
+                known.join(", ")
+            )));
+        }
+        pending.push(name);
+    }
+    while let Some(name) = pending.pop() {
+        let Some((dir, package_dependencies)) = packages.get(name) else {
+            continue; // Not a workspace package
+        };
+        if selected.insert(dir.clone()) && dependencies {
+            pending.extend(package_dependencies.iter().map(String::as_str));
+        }
+    }
+    Ok(selected
+        .into_iter()
+        .map(|path| relative_to(path, &cwd))
+        .collect())
+}
+
+/// Returns the package globs of the pnpm, yarn or npm workspace rooted at `dir`, if any.
+fn js_workspace_patterns(dir: &Path) -> Option<Vec<String>> {
+    let strings = |value: &Value| -> Vec<String> {
+        value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|pattern| pattern.as_str().map(str::to_string))
+            .collect()
+    };
+    if let Ok(text) = fs::read_to_string(dir.join("pnpm-workspace.yaml")) {
+        let config: serde_yaml::Value = serde_yaml::from_str(&text).ok()?;
+        let packages = config.get("packages")?.as_sequence()?;
+        return Some(
+            packages
+                .iter()
+                .filter_map(|pattern| pattern.as_str().map(str::to_string))
+                .collect(),
+        );
+    }
+    let manifest = read_json(&dir.join("package.json"))?;
+    match &manifest["workspaces"] {
+        workspaces @ Value::Array(_) => Some(strings(workspaces)),
+        Value::Object(workspaces) => Some(strings(workspaces.get("packages")?)), // Yarn's form
+        _ => None,
+    }
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+/// Returns `path` relative to `cwd` if it is inside it.
+fn relative_to(path: PathBuf, cwd: &Path) -> PathBuf {
+    match path.strip_prefix(cwd) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.to_path_buf(),
+        _ => path,
+    }
+}
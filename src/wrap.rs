@@ -0,0 +1,43 @@
+//! Soft-wrapping of long lines (`--wrap`).
+//!
+//! Minified code and data rows can hold lines of tens of thousands of
+//! characters, which many renderers and diff tools choke on. Lines longer than
+//! the limit are broken up, each continuation starting with [`MARKER`]. Wrapping
+//! applies to the rendered lines, line numbers included, so only the first part
+//! of a wrapped line has a number.
+
+/// Start of the continuation of a wrapped line.
+pub const MARKER: &str = "↪ ";
+
+/// Width of [`MARKER`], in characters.
+const MARKER_WIDTH: usize = 2;
+
+/// Wraps the lines of `content` longer than `columns` characters.
+pub fn wrap_lines(content: &str, columns: usize) -> String {
+    let mut wrapped = Vec::with_capacity(content.len());
+    wrap(content.as_bytes(), columns, &mut 0, &mut wrapped);
+    String::from_utf8(wrapped).expect("breaks are between characters")
+}
+
+/// Wraps the lines of `bytes` into `out`, `column` being the current column.
+///
+/// Columns count characters: UTF-8 continuation bytes don't advance them, so
+/// `bytes` may start or end in the middle of a character.
+pub fn wrap(bytes: &[u8], columns: usize, column: &mut usize, out: &mut Vec<u8>) {
+    let columns = columns.max(MARKER_WIDTH + 1);
+    for &byte in bytes {
+        match byte {
+            b'\n' => *column = 0,
+            0x80..=0xBF => {} // Continuation byte of a character
+            _ => {
+                if *column == columns {
+                    out.push(b'\n');
+                    out.extend_from_slice(MARKER.as_bytes());
+                    *column = MARKER_WIDTH;
+                }
+                *column += 1;
+            }
+        }
+        out.push(byte);
+    }
+}